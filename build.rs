@@ -0,0 +1,44 @@
+// 在编译时嵌入构建信息，供 `GET /version` 使用。
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    // 指定了 rerun-if 之后 Cargo 不再在任意文件变化时重新运行构建脚本，需要列出所有相关的输入
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    // 切换分支会修改 HEAD，在当前分支上提交会修改 HEAD 指向的引用
+    let git = Path::new(".git");
+    if let Ok(head) = std::fs::read_to_string(git.join("HEAD")) {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        if let Some(reference) = head.trim().strip_prefix("ref: ") {
+            if git.join(reference).exists() {
+                println!("cargo:rerun-if-changed=.git/{}", reference);
+            }
+        }
+        if git.join("packed-refs").exists() {
+            println!("cargo:rerun-if-changed=.git/packed-refs");
+        }
+    }
+
+    // 在没有 .git 目录的环境（例如 Docker 构建）中可以通过环境变量 GIT_COMMIT 传入
+    let commit = std::env::var("GIT_COMMIT")
+        .ok()
+        .filter(|commit| !commit.is_empty())
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+                .map(|commit| commit.trim().to_owned())
+        })
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=GIT_COMMIT={}", commit);
+    println!(
+        "cargo:rustc-env=BUILD_TIME={}",
+        chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    );
+}
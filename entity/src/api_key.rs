@@ -0,0 +1,149 @@
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Set};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "api_key")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    // 密钥信息
+    // - 便于辨认的名称
+    pub name: String,
+    // - 密钥的前若干位，用于在列表中辨认密钥
+    pub prefix: String,
+    // - 完整密钥的 SHA-256 摘要，密钥本身不会被储存
+    #[sea_orm(unique)]
+    pub key_hash: String,
+    // - 权限范围，以逗号分隔
+    pub scopes: String,
+    // 元信息
+    // - 创建时间
+    pub created_at: DateTime,
+    // - 过期时间，为空表示永不过期
+    pub expires_at: Option<DateTime>,
+    // - 最近一次使用的时间
+    pub last_used_at: Option<DateTime>,
+    /// 是否已经吊销
+    #[sea_orm(default_value = "false")]
+    pub revoked: bool,
+    // 外键连接
+    // - User
+    pub user_id: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// API Key 的权限范围。
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ApiKeyScope {
+    /// 只允许只读请求（`GET`/`HEAD`）
+    Read,
+    /// 允许修改数据的请求
+    Write,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::Write => "write",
+        }
+    }
+
+    pub fn parse_list(scopes: &str) -> Vec<Self> {
+        scopes
+            .split(',')
+            .filter_map(|scope| match scope.trim() {
+                "read" => Some(ApiKeyScope::Read),
+                "write" => Some(ApiKeyScope::Write),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn join_list(scopes: &[Self]) -> String {
+        scopes
+            .iter()
+            .map(ApiKeyScope::as_str)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[derive(ToSchema, Deserialize)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub expires_at: Option<DateTime>,
+}
+
+impl NewApiKey {
+    pub fn into_active_model(self, user_id: i32, prefix: String, key_hash: String) -> ActiveModel {
+        ActiveModel {
+            id: NotSet,
+            name: Set(self.name),
+            prefix: Set(prefix),
+            key_hash: Set(key_hash),
+            scopes: Set(ApiKeyScope::join_list(&self.scopes)),
+            created_at: Set(Utc::now().naive_utc()),
+            expires_at: Set(self.expires_at),
+            last_used_at: Set(None),
+            revoked: Set(false),
+            user_id: Set(user_id),
+        }
+    }
+}
+
+#[derive(ToSchema, Serialize)]
+pub struct GetApiKey {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: DateTime,
+    pub expires_at: Option<DateTime>,
+    pub last_used_at: Option<DateTime>,
+    pub revoked: bool,
+}
+
+impl From<Model> for GetApiKey {
+    fn from(value: Model) -> Self {
+        GetApiKey {
+            id: value.id,
+            name: value.name,
+            prefix: value.prefix,
+            scopes: ApiKeyScope::parse_list(&value.scopes),
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            last_used_at: value.last_used_at,
+            revoked: value.revoked,
+        }
+    }
+}
+
+/// 创建 API Key 的结果。完整的密钥只会在此时返回一次。
+#[derive(ToSchema, Serialize)]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub info: GetApiKey,
+}
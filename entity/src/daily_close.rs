@@ -0,0 +1,40 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 已经日结的营业日。
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "daily_close")]
+pub struct Model {
+    /// 营业日的日期（按 `tz_offset` 所在时区）
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub date: Date,
+    /// 划分营业日所用的时区偏移（秒）
+    pub tz_offset: i32,
+    /// 日结时的报表快照（JSON）
+    #[sea_orm(column_type = "Text")]
+    pub report: String,
+    // 外键连接
+    // - 执行日结的 User
+    pub closed_by: i32,
+    // 元信息
+    // - 日结时间
+    pub closed_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::ClosedBy",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,46 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 定时任务的一次执行记录。
+///
+/// 主键为任务名与计划执行时间，多个实例同时调度同一任务时只有插入成功的实例会执行。
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "job_run")]
+pub struct Model {
+    /// 任务名
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub job: String,
+    /// 计划执行的时间
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub scheduled_at: DateTime,
+    /// 执行任务的实例
+    pub instance: String,
+    pub status: JobStatus,
+    /// 执行结果的说明或错误信息
+    #[sea_orm(column_type = "Text", nullable)]
+    pub detail: Option<String>,
+    // 元信息
+    // - 开始时间
+    pub started_at: DateTime,
+    // - 结束时间，为空表示仍在执行
+    pub finished_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+pub enum JobStatus {
+    #[sea_orm(string_value = "running")]
+    Running,
+    #[sea_orm(string_value = "succeeded")]
+    Succeeded,
+    #[sea_orm(string_value = "failed")]
+    Failed,
+    /// 停止服务时仍未结束而被中止
+    #[sea_orm(string_value = "interrupted")]
+    Interrupted,
+}
@@ -1,5 +1,11 @@
+pub mod api_key;
 pub mod book;
+pub mod daily_close;
+pub mod job_run;
 pub mod order_list;
+pub mod password_history;
+pub mod password_reset;
+pub mod refresh_token;
 pub mod transaction;
 pub mod user;
 
@@ -15,11 +21,21 @@ pub fn to_active<V: Into<Value>, T: IntoActiveValue<V>>(value: Option<T>) -> Act
 }
 
 #[derive(
-    Debug, PartialEq, Eq, Clone, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Clone,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
 )]
 #[non_exhaustive]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "ticket_status")]
 pub enum TicketStatus {
+    #[default]
     #[sea_orm(string_value = "Pending")]
     Pending,
     #[sea_orm(string_value = "StockPaid")]
@@ -30,12 +46,6 @@ pub enum TicketStatus {
     Revoked,
 }
 
-impl Default for TicketStatus {
-    fn default() -> Self {
-        TicketStatus::Pending
-    }
-}
-
 impl IntoActiveValue<TicketStatus> for TicketStatus {
     fn into_active_value(self) -> ActiveValue<TicketStatus> {
         ActiveValue::Set(self)
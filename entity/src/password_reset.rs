@@ -0,0 +1,41 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 由超级管理员签发的一次性密码重置凭据。
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "password_reset")]
+pub struct Model {
+    /// 重置凭据的 SHA-256 摘要，凭据本身不会被储存
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub token_hash: String,
+    // 外键连接
+    // - 需要重置密码的 User
+    pub user_id: i32,
+    // - 签发凭据的 User
+    pub created_by: i32,
+    // 元信息
+    // - 签发时间
+    pub created_at: DateTime,
+    // - 过期时间
+    pub expires_at: DateTime,
+    // - 被使用的时间，为空表示尚未使用
+    pub used_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
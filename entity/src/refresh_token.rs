@@ -0,0 +1,43 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "refresh_token")]
+pub struct Model {
+    /// Refresh Token 的唯一编号，即 JWT 中的 `jti`
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub jti: String,
+    // 所属的 Token 家族（同一次登录后轮换得到的所有 Refresh Token）
+    pub family: String,
+    // 外键连接
+    // - User
+    pub user_id: i32,
+    // 元信息
+    // - 签发时间
+    pub created_at: DateTime,
+    // - 过期时间
+    pub expires_at: DateTime,
+    // - 被用于刷新的时间，为空表示尚未使用
+    pub used_at: Option<DateTime>,
+    /// 整个家族是否已被吊销
+    #[sea_orm(default_value = "false")]
+    pub revoked: bool,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
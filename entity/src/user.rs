@@ -25,9 +25,7 @@ pub struct Model {
     /// 是否已经删除
     #[sea_orm(default_value = "false")]
     pub is_deleted: bool,
-    // - TODO: 绩效指标
-    #[sea_orm(ignore)]
-    pub kpi: (),
+    // 绩效指标由订单实时统计得出，不储存在用户表中，见 `GET /user/{id}/kpi`
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -68,7 +66,8 @@ impl From<Model> for GetUser {
 
 #[derive(ToSchema, Deserialize)]
 pub struct NewUser {
-    pub password_salt: String,
+    /// 明文密码，储存前会被加盐
+    pub password: String,
     pub role: String,
     pub real_name: String,
     pub sex: Sex,
@@ -79,7 +78,7 @@ impl IntoActiveModel<ActiveModel> for NewUser {
     fn into_active_model(self) -> ActiveModel {
         ActiveModel {
             id: NotSet,
-            password_salt: Set(self.password_salt),
+            password_salt: Set(self.password),
             secret_key: NotSet,
             role: Set(self.role),
             real_name: Set(self.real_name),
@@ -92,7 +91,8 @@ impl IntoActiveModel<ActiveModel> for NewUser {
 
 #[derive(ToSchema, Deserialize)]
 pub struct UpdateUser {
-    pub password_salt: Option<String>,
+    /// 明文密码，储存前会被加盐
+    pub password: Option<String>,
     pub role: Option<String>,
     pub real_name: Option<String>,
     pub sex: Option<Sex>,
@@ -103,7 +103,7 @@ impl IntoActiveModel<ActiveModel> for UpdateUser {
     fn into_active_model(self) -> ActiveModel {
         ActiveModel {
             id: NotSet,
-            password_salt: to_active(self.password_salt),
+            password_salt: to_active(self.password),
             secret_key: NotSet,
             role: to_active(self.role),
             real_name: to_active(self.real_name),
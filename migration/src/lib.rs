@@ -1,7 +1,15 @@
 pub use sea_orm_migration::prelude::*;
 
+mod m20230511_000000_create_enum_types;
 mod m20230511_164244_create_table;
 mod m20230526_035013_add_birth;
+mod m20230526_035013_add_birth_portable;
+mod m20230612_083000_add_refresh_token;
+mod m20230618_101500_add_api_key;
+mod m20230625_140000_add_password_history;
+mod m20230702_093000_add_password_reset;
+mod m20230709_180000_add_daily_close;
+mod m20230716_120000_add_job_run;
 
 pub struct Migrator;
 
@@ -9,8 +17,15 @@ pub struct Migrator;
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
         vec![
+            Box::new(m20230511_000000_create_enum_types::Migration),
             Box::new(m20230511_164244_create_table::Migration),
-            Box::new(m20230526_035013_add_birth::Migration),
+            Box::new(m20230526_035013_add_birth_portable::Migration),
+            Box::new(m20230612_083000_add_refresh_token::Migration),
+            Box::new(m20230618_101500_add_api_key::Migration),
+            Box::new(m20230625_140000_add_password_history::Migration),
+            Box::new(m20230702_093000_add_password_reset::Migration),
+            Box::new(m20230709_180000_add_daily_close::Migration),
+            Box::new(m20230716_120000_add_job_run::Migration),
         ]
     }
 }
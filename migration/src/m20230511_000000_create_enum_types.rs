@@ -0,0 +1,67 @@
+//! PostgreSQL 的枚举列需要先创建同名的类型，`m20230511_164244_create_table`
+//! 没有这么做，所以在它之前补上这一步。MySQL 与 SQLite 上什么也不做。
+
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    sea_orm::{ConnectionTrait, DbBackend, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// 类型名与取值，需要和 `m20230511_164244_create_table` 中的枚举保持一致
+const ENUM_TYPES: [(&str, &[&str]); 3] = [
+    (
+        "ticket_status",
+        &["Pending", "StockPaid", "Done", "Revoked"],
+    ),
+    ("ticket_type", &["Sell", "Stock"]),
+    ("sex", &["Male", "Female", "NonBinary"]),
+];
+
+async fn type_exists(manager: &SchemaManager<'_>, name: &str) -> Result<bool, DbErr> {
+    let query = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "SELECT 1 FROM pg_type WHERE typname = $1",
+        [name.into()],
+    );
+    Ok(manager.get_connection().query_one(query).await?.is_some())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Postgres {
+            return Ok(());
+        }
+        for (name, values) in ENUM_TYPES {
+            // 已经手动建过类型的库直接跳过
+            if type_exists(manager, name).await? {
+                continue;
+            }
+            manager
+                .create_type(
+                    Type::create()
+                        .as_enum(Alias::new(name))
+                        .values(values.iter().map(|v| Alias::new(v)))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Postgres {
+            return Ok(());
+        }
+        manager
+            .drop_type(
+                Type::drop()
+                    .if_exists()
+                    .names(ENUM_TYPES.map(|(name, _)| Alias::new(name)))
+                    .to_owned(),
+            )
+            .await
+    }
+}
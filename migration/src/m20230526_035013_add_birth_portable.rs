@@ -0,0 +1,47 @@
+//! `m20230526_035013_add_birth` 的默认值写成了 `NOW()`，SQLite 无法解析，
+//! 并且 SQLite 新增的列不能使用非常量的默认值。
+//!
+//! 这里沿用原迁移的名字，已经执行过它的库不会重复执行；MySQL 与 PostgreSQL
+//! 仍然执行原迁移，只有 SQLite 改用常量默认值。
+
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+use crate::m20230526_035013_add_birth as original;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        original::Migration.name()
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            return original::Migration.up(manager).await;
+        }
+        let query = Table::alter()
+            .table(User::Table)
+            .add_column(
+                ColumnDef::new(User::Birth)
+                    .date_time()
+                    .not_null()
+                    .default("1970-01-01 00:00:00"),
+            )
+            .to_owned();
+
+        manager.exec_stmt(query).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        original::Migration.down(manager).await
+    }
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Birth,
+}
@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum RefreshToken {
+    Table,
+    Jti,
+    Family,
+    UserId,
+    CreatedAt,
+    ExpiresAt,
+    UsedAt,
+    Revoked,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RefreshToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RefreshToken::Jti)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RefreshToken::Family).string().not_null())
+                    .col(ColumnDef::new(RefreshToken::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(RefreshToken::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RefreshToken::ExpiresAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RefreshToken::UsedAt).date_time().null())
+                    .col(
+                        ColumnDef::new(RefreshToken::Revoked)
+                            .boolean()
+                            .default(false)
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // 吊销时按家族批量更新
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_refresh_token_family")
+                    .table(RefreshToken::Table)
+                    .col(RefreshToken::Family)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RefreshToken::Table).to_owned())
+            .await
+    }
+}
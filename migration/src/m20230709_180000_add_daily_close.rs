@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum DailyClose {
+    Table,
+    Date,
+    TzOffset,
+    Report,
+    ClosedBy,
+    ClosedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DailyClose::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(DailyClose::Date)
+                            .date()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(DailyClose::TzOffset).integer().not_null())
+                    .col(ColumnDef::new(DailyClose::Report).text().not_null())
+                    .col(ColumnDef::new(DailyClose::ClosedBy).integer().not_null())
+                    .col(ColumnDef::new(DailyClose::ClosedAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DailyClose::Table).to_owned())
+            .await
+    }
+}
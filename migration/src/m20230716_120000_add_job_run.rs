@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum JobRun {
+    Table,
+    Job,
+    ScheduledAt,
+    Instance,
+    Status,
+    Detail,
+    StartedAt,
+    FinishedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(JobRun::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(JobRun::Job).string_len(64).not_null())
+                    .col(ColumnDef::new(JobRun::ScheduledAt).date_time().not_null())
+                    .col(ColumnDef::new(JobRun::Instance).string().not_null())
+                    .col(ColumnDef::new(JobRun::Status).string_len(16).not_null())
+                    .col(ColumnDef::new(JobRun::Detail).text().null())
+                    .col(ColumnDef::new(JobRun::StartedAt).date_time().not_null())
+                    .col(ColumnDef::new(JobRun::FinishedAt).date_time().null())
+                    // 同一任务的同一次调度只能被一个实例执行
+                    .primary_key(Index::create().col(JobRun::Job).col(JobRun::ScheduledAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(JobRun::Table).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,98 @@
+use crate::utils::api_key::gen_api_key;
+use crate::utils::errors::{not_found, unprocessable_entity};
+use crate::utils::jwt::{hash_secret_token, AllowAdmin, JwtClaims};
+use crate::utils::permission::APermission;
+
+use super::preclude::*;
+
+use actix_web::web::Data;
+use actix_web::{delete, get, post, web::Path};
+use chrono::Utc;
+use entity::api_key::{self, CreatedApiKey, GetApiKey, NewApiKey};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder, Set,
+};
+
+#[p(
+    responses(
+        (status = OK, description = "Get API keys successful", body = [GetApiKey]),
+    ),
+    security(("jwt_token" = []))
+)]
+#[get("/user/me/api_keys")]
+pub async fn get_api_keys(
+    auth: APermission<JwtClaims, AllowAdmin>,
+    db: Data<DatabaseConnection>,
+) -> AResult<AJson<Vec<GetApiKey>>> {
+    Ok(AJson(
+        api_key::Entity::find()
+            .filter(api_key::Column::UserId.eq(auth.auth_info.id))
+            .order_by_desc(api_key::Column::CreatedAt)
+            .all(db.get_ref())
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    ))
+}
+
+#[p(
+    request_body = NewApiKey,
+    responses(
+        (status = OK, description = "Create API key successful. The key is only shown once", body = CreatedApiKey),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid scopes or expiry", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []))
+)]
+#[post("/user/me/api_keys")]
+pub async fn create_api_key(
+    info: AJson<NewApiKey>,
+    auth: APermission<JwtClaims, AllowAdmin>,
+    db: Data<DatabaseConnection>,
+) -> AResult<AJson<CreatedApiKey>> {
+    let info = info.into_inner();
+    if info.scopes.is_empty() {
+        return Err(unprocessable_entity("At least one scope is required").into());
+    }
+    if info
+        .expires_at
+        .map_or(false, |expires_at| expires_at <= Utc::now().naive_utc())
+    {
+        return Err(unprocessable_entity("Expiry must be in the future").into());
+    }
+
+    let (key, prefix) = gen_api_key();
+    let record = info
+        .into_active_model(auth.auth_info.id, prefix, hash_secret_token(&key))
+        .insert(db.get_ref())
+        .await?;
+    Ok(AJson(CreatedApiKey {
+        key,
+        info: record.into(),
+    }))
+}
+
+#[p(
+    responses(
+        (status = OK, description = "Revoke API key successful", body = GetApiKey),
+        (status = NOT_FOUND, description = "API key not found", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []))
+)]
+#[delete("/user/me/api_keys/{id}")]
+pub async fn revoke_api_key(
+    id: Path<i32>,
+    auth: APermission<JwtClaims, AllowAdmin>,
+    db: Data<DatabaseConnection>,
+) -> AResult<AJson<GetApiKey>> {
+    // 只能吊销自己的 API Key
+    let record = api_key::Entity::find_by_id(id.into_inner())
+        .filter(api_key::Column::UserId.eq(auth.auth_info.id))
+        .one(db.get_ref())
+        .await?
+        .ok_or_else(|| not_found("API key not found"))?;
+    let mut active_record = record.into_active_model();
+    active_record.revoked = Set(true);
+    Ok(AJson(active_record.update(db.get_ref()).await?.into()))
+}
@@ -1,9 +1,15 @@
+use crate::config::{AuthConfig, Config};
 use crate::contants::user_type;
-use crate::utils::errors::{bad_request, conflict, forbidden, iam_a_teapot, not_found, AError};
+use crate::utils::errors::{
+    bad_request, conflict, forbidden, iam_a_teapot, not_found, unauthorized,
+};
 use crate::utils::ext::SelectExt;
+use crate::utils::jwk::JwtKeys;
 use crate::utils::jwt::{
-    gen_secret_key, issue_acc_ref_token, AllowAdmin, AllowRefresh, AllowSuperAdmin, JwtClaims,
+    gen_secret_key, gen_token_id, hash_secret_token, issue_acc_ref_token, AllowAdmin, AllowRefresh,
+    AllowSuperAdmin, JwtClaims,
 };
+use crate::utils::password::{to_salted_password, verify_password};
 use crate::utils::permission::APermission;
 
 use super::preclude::*;
@@ -16,22 +22,21 @@ use actix_web::{
     HttpRequest,
 };
 use actix_web::{FromRequest, HttpResponse};
-use argon2::password_hash::SaltString;
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use chrono::{Duration, NaiveDateTime, Utc};
 use entity::user::{self, GetUser, NewUser, UpdateUser};
-use once_cell::sync::Lazy;
-use rand::rngs::OsRng;
+use entity::{api_key, password_history, password_reset, refresh_token};
 use redis::aio::MultiplexedConnection;
 use redis::AsyncCommands;
+use sea_orm::sea_query::Expr;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
-    Select, Set, Unchanged,
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    IntoActiveModel, NotSet, QueryFilter, QueryOrder, QuerySelect, Select, Set, TransactionTrait,
+    Unchanged,
 };
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use tokio::sync::Mutex;
-use utoipa::ToSchema;
-
-static ARGON: Lazy<Argon2> = Lazy::new(Argon2::default);
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Deserialize, ToSchema)]
 pub struct LoginRequest {
@@ -39,6 +44,25 @@ pub struct LoginRequest {
     password: String,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    old_password: String,
+    new_password: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResetToken {
+    /// 一次性的重置凭据，只会返回一次
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    token: String,
+    new_password: String,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct JwtToken {
     pub access_token: String,
@@ -61,7 +85,7 @@ pub fn find_user() -> Select<user::Entity> {
 
 /// 删除 Redis 缓存中的用户信息。
 ///
-/// 注意：总是应该在修改数据库前调用此函数，或者在数据库事务中调用此函数。否则，可能会导致 Redis 缓存与数据库不一致。
+/// 注意：总是应该在修改提交到数据库后再调用此函数。否则，并发的请求可能在提交前把旧的用户信息重新写入缓存。
 async fn invalidate_key(key: i32, rd: Data<Option<Mutex<MultiplexedConnection>>>) -> AResult<()> {
     if let Some(rd) = rd.get_ref() {
         let mut rd = rd.lock().await;
@@ -70,9 +94,95 @@ async fn invalidate_key(key: i32, rd: Data<Option<Mutex<MultiplexedConnection>>>
     Ok(())
 }
 
-fn to_salted_password(password: &String) -> Result<String, argon2::password_hash::Error> {
-    let salt = SaltString::generate(&mut OsRng);
-    Ok(ARGON.hash_password(password.as_bytes(), &salt)?.to_string())
+/// 签发一对新的 Token，并将 Refresh Token 记录到数据库中。
+///
+/// `family` 为空时表示一次新的登录，会开启一个新的 Token 家族。
+async fn issue_token_pair<C: ConnectionTrait>(
+    user_id: i32,
+    secret_key: String,
+    family: Option<String>,
+    config: &AuthConfig,
+    keys: &JwtKeys,
+    db: &C,
+) -> AResult<JwtToken> {
+    let jti = gen_token_id();
+    let now = Utc::now().naive_utc();
+    refresh_token::ActiveModel {
+        jti: Set(jti.clone()),
+        family: Set(family.unwrap_or_else(gen_token_id)),
+        user_id: Set(user_id),
+        created_at: Set(now),
+        expires_at: Set(now + Duration::seconds(config.refresh_token_lifetime)),
+        used_at: Set(None),
+        revoked: Set(false),
+    }
+    .insert(db)
+    .await?;
+
+    Ok(issue_acc_ref_token(user_id, secret_key, jti, config, keys)?)
+}
+
+/// 替换用户的密码。
+///
+/// 会检查密码策略、记录旧密码，更换 Secret Key 使已签发的 Token 全部失效，并吊销用户的全部 API Key。
+/// 调用者需要在事务提交后使缓存失效。
+async fn replace_password<C: ConnectionTrait>(
+    user: user::Model,
+    password: &str,
+    config: &Config,
+    db: &C,
+) -> AResult<user::Model> {
+    config.password.check(password)?;
+    config.password.check_reuse(&user, password, db).await?;
+
+    password_history::ActiveModel {
+        id: NotSet,
+        password_salt: Set(user.password_salt.clone()),
+        created_at: Set(Utc::now().naive_utc()),
+        user_id: Set(user.id),
+    }
+    .insert(db)
+    .await?;
+
+    // 密码泄露时，用它创建的 API Key 也不再可信
+    api_key::Entity::update_many()
+        .col_expr(api_key::Column::Revoked, Expr::value(true))
+        .filter(api_key::Column::UserId.eq(user.id))
+        .filter(api_key::Column::Revoked.eq(false))
+        .exec(db)
+        .await?;
+
+    let mut user = user.into_active_model();
+    user.password_salt = Set(to_salted_password(password)?);
+    user.secret_key = Set(gen_secret_key(&config.auth, user.secret_key.take()));
+    Ok(user.update(db).await?)
+}
+
+/// 系统中至少要保留一个超级管理员。`target` 即将被删除或降级时，在同一个事务中调用。
+///
+/// 用 `SELECT ... FOR UPDATE` 锁住所有超级管理员，并发删除或降级彼此的请求会依次执行，
+/// 后执行的请求能看到前一个请求的修改。SQLite 不支持行锁，但同一时间只允许一个写事务。
+async fn ensure_other_super_admin<C: ConnectionTrait>(
+    target: &user::Model,
+    message: &str,
+    db: &C,
+) -> AResult<()> {
+    if target.role != user_type::SUPER_ADMIN {
+        return Ok(());
+    }
+    let super_admins: Vec<i32> = find_user()
+        .select_only()
+        .column(user::Column::Id)
+        .filter(user::Column::Role.eq(user_type::SUPER_ADMIN))
+        .order_by_asc(user::Column::Id)
+        .lock_exclusive()
+        .into_tuple()
+        .all(db)
+        .await?;
+    if super_admins.contains(&target.id) && super_admins.len() <= 1 {
+        return Err(conflict(message).into());
+    }
+    Ok(())
 }
 
 #[p(
@@ -86,31 +196,30 @@ fn to_salted_password(password: &String) -> Result<String, argon2::password_hash
 pub async fn login(
     creds: AJson<LoginRequest>,
     db: Data<DatabaseConnection>,
+    config: Data<Config>,
+    keys: Data<JwtKeys>,
 ) -> AResult<AJson<JwtToken>> {
     // 验证用户存在及密码正确
     let user = find_user_by_id(creds.id)
         .one(db.get_ref())
         .await?
         .ok_or_else(|| not_found("User not found"))?;
-    ARGON
-        .verify_password(
-            creds.password.as_bytes(),
-            &PasswordHash::new(&user.password_salt)?,
-        )
-        .map_or_else(
-            |err| match err {
-                // 密码错误转换为响应
-                argon2::password_hash::Error::Password => {
-                    Err(AError::from(iam_a_teapot("Invalid credentials")))
-                }
-                // 其他错误视为服务器错误
-                e => Err(e.into()),
-            },
-            |_| Ok(()),
-        )?;
+    if !verify_password(&creds.password, &user.password_salt)? {
+        return Err(iam_a_teapot("Invalid credentials").into());
+    }
 
     // 分配 JWT
-    Ok(AJson(issue_acc_ref_token(user.id, user.secret_key)?))
+    Ok(AJson(
+        issue_token_pair(
+            user.id,
+            user.secret_key,
+            None,
+            &config.auth,
+            &keys,
+            db.get_ref(),
+        )
+        .await?,
+    ))
 }
 
 #[p(
@@ -121,11 +230,73 @@ pub async fn login(
     security(("jwt_token" = []))
 )]
 #[post("/user/refresh")]
-pub async fn refresh(auth: APermission<JwtClaims, AllowRefresh>) -> AResult<AJson<JwtToken>> {
-    Ok(AJson(issue_acc_ref_token(
+pub async fn refresh(
+    auth: APermission<JwtClaims, AllowRefresh>,
+    db: Data<DatabaseConnection>,
+    rd: Data<Option<Mutex<MultiplexedConnection>>>,
+    config: Data<Config>,
+    keys: Data<JwtKeys>,
+) -> AResult<AJson<JwtToken>> {
+    let trans = db.begin().await?;
+    let token = refresh_token::Entity::find_by_id(auth.extracted_info.jti.clone())
+        .one(&trans)
+        .await?
+        .filter(|token| token.user_id == auth.auth_info.id)
+        .ok_or_else(|| unauthorized("Unknown refresh token"))?;
+    if token.revoked {
+        return Err(unauthorized("Refresh token has been revoked").into());
+    }
+
+    // Refresh Token 只能使用一次。条件更新保证并发刷新时只有一个请求能成功
+    let marked = refresh_token::Entity::update_many()
+        .col_expr(
+            refresh_token::Column::UsedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(refresh_token::Column::Jti.eq(token.jti.clone()))
+        .filter(refresh_token::Column::UsedAt.is_null())
+        .exec(&trans)
+        .await?;
+    if marked.rows_affected == 0 {
+        // 已使用过的 Refresh Token 被再次提交，说明 Token 可能已被盗用：
+        // 吊销整个家族，并更换 Secret Key 使所有已签发的 Access Token 失效
+        refresh_token::Entity::update_many()
+            .col_expr(refresh_token::Column::Revoked, Expr::value(true))
+            .filter(refresh_token::Column::Family.eq(token.family))
+            .exec(&trans)
+            .await?;
+
+        let user_id = auth.auth_info.id;
+        let mut user = auth.auth_info.into_active_model();
+        user.secret_key = Set(gen_secret_key(&config.auth, user.secret_key.take()));
+        user.update(&trans).await?;
+
+        trans.commit().await?;
+        invalidate_key(user_id, rd).await?;
+        return Err(unauthorized("Refresh token reuse detected, please login again").into());
+    }
+
+    let pair = issue_token_pair(
         auth.auth_info.id,
         auth.auth_info.secret_key,
-    )?))
+        Some(token.family),
+        &config.auth,
+        &keys,
+        &trans,
+    )
+    .await?;
+    trans.commit().await?;
+    Ok(AJson(pair))
+}
+
+#[p(
+    responses(
+        (status = OK, description = "JSON Web Key Set containing all active token verification keys"),
+    ),
+)]
+#[get("/.well-known/jwks.json")]
+pub async fn jwks(keys: Data<JwtKeys>) -> HttpResponse {
+    HttpResponse::Ok().json(keys.jwks())
 }
 
 #[p(
@@ -139,17 +310,18 @@ pub async fn refresh(auth: APermission<JwtClaims, AllowRefresh>) -> AResult<AJso
 pub async fn logout(
     db: Data<DatabaseConnection>,
     rd: Data<Option<Mutex<MultiplexedConnection>>>,
+    config: Data<Config>,
     auth: APermission<JwtClaims, AllowAdmin>,
 ) -> AResult<AJson<GeneralResponse>> {
     let user_id = auth.auth_info.id;
     let mut user = auth.auth_info.into_active_model();
-    user.secret_key = Set(gen_secret_key(user.secret_key.take()));
-
-    invalidate_key(user_id, rd).await?;
+    user.secret_key = Set(gen_secret_key(&config.auth, user.secret_key.take()));
     user.update(db.get_ref()).await?;
+    invalidate_key(user_id, rd).await?;
 
     Ok(AJson(GeneralResponse {
         message: "Logout successful".to_string(),
+        request_id: None,
     }))
 }
 
@@ -159,6 +331,7 @@ pub async fn logout(
         (status = OK, description = "Register successful", body = GetUser),
         (status = BAD_REQUEST, description = "Invalid credentials", body = GeneralResponse),
         (status = CONFLICT, description = "User already exists", body = GeneralResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "Password does not satisfy the policy", body = GeneralResponse),
     ),
     security(("jwt_token" = []))
 )]
@@ -166,6 +339,7 @@ pub async fn logout(
 pub async fn register(
     info: AJson<NewUser>,
     db: Data<DatabaseConnection>,
+    config: Data<Config>,
     req: HttpRequest,
     payload: Payload,
 ) -> AResult<AJson<GetUser>> {
@@ -191,18 +365,30 @@ pub async fn register(
         _ => return Err(bad_request("Invalid user role").into()),
     }
     let mut info = info.into_inner();
-    // 密码加盐
-    info.password_salt = to_salted_password(&info.password_salt)?;
+    // 检查密码强度并加盐
+    config.password.check(&info.password)?;
+    info.password = to_salted_password(&info.password)?;
     // 初始化 Secret Key
     let mut active_info = info.into_active_model();
-    active_info.secret_key = Set(gen_secret_key(None));
+    active_info.secret_key = Set(gen_secret_key(&config.auth, None));
     // 储存用户
     let user = active_info.insert(db.get_ref()).await?;
     Ok(AJson(user.into()))
 }
 
+#[serde_as]
+#[derive(Deserialize, IntoParams)]
+pub struct UserFilter {
+    /// 为 `true` 时只列出已删除的用户
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub deleted: Option<bool>,
+    #[serde(flatten)]
+    pub paging: PagingRequest,
+}
+
 #[p(
-    params(PagingRequest),
+    params(UserFilter),
     responses(
         (status = OK, description = "Get users successful", body = [GetUser])
     ),
@@ -210,12 +396,18 @@ pub async fn register(
 )]
 #[get("/user")]
 pub async fn get_users(
-    paging: Query<PagingRequest>,
+    params: Query<UserFilter>,
     _auth: APermission<JwtClaims, AllowSuperAdmin>,
     db: Data<DatabaseConnection>,
 ) -> AResult<HttpResponse> {
-    find_user()
-        .paged::<DatabaseConnection, _, GetUser>(paging.into_inner(), db.get_ref())
+    let params = params.into_inner();
+    let query = if params.deleted.unwrap_or(false) {
+        user::Entity::find().filter(user::Column::IsDeleted.eq(true))
+    } else {
+        find_user()
+    };
+    query
+        .paged::<DatabaseConnection, _, GetUser>(params.paging, db.get_ref())
         .await
 }
 
@@ -230,6 +422,39 @@ pub async fn get_self(auth: APermission<JwtClaims, AllowAdmin>) -> AResult<AJson
     Ok(AJson(auth.auth_info.into()))
 }
 
+#[p(
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = OK, description = "Password changed, all previous tokens are revoked", body = JwtToken),
+        (status = IM_A_TEAPOT, description = "Invalid credentials"),
+        (status = UNPROCESSABLE_ENTITY, description = "Password does not satisfy the policy", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []))
+)]
+#[post("/user/me/password")]
+pub async fn change_password(
+    info: AJson<ChangePasswordRequest>,
+    auth: APermission<JwtClaims, AllowAdmin>,
+    rd: Data<Option<Mutex<MultiplexedConnection>>>,
+    db: Data<DatabaseConnection>,
+    config: Data<Config>,
+    keys: Data<JwtKeys>,
+) -> AResult<AJson<JwtToken>> {
+    if !verify_password(&info.old_password, &auth.auth_info.password_salt)? {
+        return Err(iam_a_teapot("Invalid credentials").into());
+    }
+
+    let trans = db.begin().await?;
+    let user = replace_password(auth.auth_info, &info.new_password, &config, &trans).await?;
+    // Secret Key 已经更换，签发新的 Token 以保持当前登录状态
+    let token =
+        issue_token_pair(user.id, user.secret_key, None, &config.auth, &keys, &trans).await?;
+    trans.commit().await?;
+    invalidate_key(user.id, rd).await?;
+
+    Ok(AJson(token))
+}
+
 #[p(
     responses(
         (status = OK, description = "Get user successful", body = GetUser),
@@ -262,16 +487,18 @@ pub async fn get_user(
         (status = OK, description = "Update user successful", body = GetUser),
         (status = BAD_REQUEST, description = "Invalid credentials", body = GeneralResponse),
         (status = NOT_FOUND, description = "User not found", body = GeneralResponse),
+        (status = CONFLICT, description = "Cannot demote the last super admin", body = GeneralResponse),
     ),
     security(("jwt_token" = []))
 )]
 #[patch("/user/{id}")]
 pub async fn update_user(
     id: Path<i32>,
-    mut info: AJson<UpdateUser>,
+    info: AJson<UpdateUser>,
     auth: APermission<JwtClaims, AllowAdmin>,
     rd: Data<Option<Mutex<MultiplexedConnection>>>,
     db: Data<DatabaseConnection>,
+    config: Data<Config>,
 ) -> AResult<AJson<GetUser>> {
     let id = id.into_inner();
     // 只有自己或超级管理员才能修改用户信息
@@ -284,15 +511,38 @@ pub async fn update_user(
                 return Err(forbidden("Permission denied").into());
             }
         }
-        if let Some(ref password) = info.password_salt {
-            // 如果有密码，需要给密码加盐
-            info.password_salt = Some(to_salted_password(password)?);
+        let mut info = info.into_inner();
+        let trans = db.begin().await?;
+        if info
+            .role
+            .as_deref()
+            .map_or(false, |role| role != user_type::SUPER_ADMIN)
+        {
+            let target = find_user_by_id(id)
+                .one(&trans)
+                .await?
+                .ok_or_else(|| not_found("User not found"))?;
+            ensure_other_super_admin(&target, "Cannot demote the last super admin", &trans).await?;
+        }
+        if let Some(password) = info.password.take() {
+            // 修改自己的密码需要验证旧密码，只能通过专门的接口进行
+            if auth.auth_info.id == id {
+                return Err(
+                    forbidden("Use POST /user/me/password to change your own password").into(),
+                );
+            }
+            let target = find_user_by_id(id)
+                .one(&trans)
+                .await?
+                .ok_or_else(|| not_found("User not found"))?;
+            replace_password(target, &password, &config, &trans).await?;
         }
-        let mut info = info.into_inner().into_active_model();
+        let mut info = info.into_active_model();
         info.id = Unchanged(id);
-
+        let user = info.update(&trans).await?;
+        trans.commit().await?;
         invalidate_key(id, rd).await?;
-        Ok(AJson(info.update(db.get_ref()).await?.into()))
+        Ok(AJson(user.into()))
     }
 }
 
@@ -300,6 +550,7 @@ pub async fn update_user(
     responses(
         (status = OK, description = "Delete user successful", body = GeneralResponse),
         (status = NOT_FOUND, description = "User not found", body = GeneralResponse),
+        (status = CONFLICT, description = "Cannot delete the last super admin", body = GeneralResponse),
     ),
     security(("jwt_token" = []))
 )]
@@ -315,23 +566,156 @@ pub async fn delete_user(
     if auth.auth_info.role != user_type::SUPER_ADMIN && auth.auth_info.id != id {
         Err(forbidden("Permission denied").into())
     } else {
+        let trans = db.begin().await?;
         let target = if id == auth.auth_info.id {
             auth.auth_info
         } else {
             find_user_by_id(id)
-                .one(db.get_ref())
+                .one(&trans)
                 .await?
                 .ok_or_else(|| not_found("User not found"))?
         };
 
-        invalidate_key(id, rd).await?;
+        ensure_other_super_admin(&target, "Cannot delete the last super admin", &trans).await?;
 
         let mut active_target = target.into_active_model();
         active_target.is_deleted = Set(true);
-        active_target.update(db.get_ref()).await?;
+        active_target.update(&trans).await?;
+        trans.commit().await?;
+        invalidate_key(id, rd).await?;
 
         Ok(AJson(GeneralResponse {
             message: "Delete user successful".to_string(),
+            request_id: None,
         }))
     }
 }
+
+#[p(
+    responses(
+        (status = OK, description = "Restore user successful", body = GetUser),
+        (status = NOT_FOUND, description = "Deleted user not found", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []))
+)]
+#[post("/user/{id}/restore")]
+pub async fn restore_user(
+    id: Path<i32>,
+    _auth: APermission<JwtClaims, AllowSuperAdmin>,
+    db: Data<DatabaseConnection>,
+    rd: Data<Option<Mutex<MultiplexedConnection>>>,
+    config: Data<Config>,
+) -> AResult<AJson<GetUser>> {
+    let id = id.into_inner();
+    let target = user::Entity::find_by_id(id)
+        .filter(user::Column::IsDeleted.eq(true))
+        .one(db.get_ref())
+        .await?
+        .ok_or_else(|| not_found("Deleted user not found"))?;
+
+    // 更换 Secret Key，删除前签发的 Token 不再有效
+    let mut active_target = target.into_active_model();
+    active_target.is_deleted = Set(false);
+    active_target.secret_key = Set(gen_secret_key(
+        &config.auth,
+        active_target.secret_key.take(),
+    ));
+    let user = active_target.update(db.get_ref()).await?;
+    invalidate_key(id, rd).await?;
+    Ok(AJson(user.into()))
+}
+
+#[p(
+    responses(
+        (status = OK, description = "Reset token issued", body = ResetToken),
+        (status = NOT_FOUND, description = "User not found", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []))
+)]
+#[post("/user/{id}/reset_password")]
+pub async fn issue_reset_token(
+    id: Path<i32>,
+    auth: APermission<JwtClaims, AllowSuperAdmin>,
+    db: Data<DatabaseConnection>,
+    config: Data<Config>,
+) -> AResult<AJson<ResetToken>> {
+    let target = find_user_by_id(id.into_inner())
+        .one(db.get_ref())
+        .await?
+        .ok_or_else(|| not_found("User not found"))?;
+
+    let trans = db.begin().await?;
+    let now = Utc::now().naive_utc();
+    // 同一时间只保留一个有效的重置凭据
+    password_reset::Entity::update_many()
+        .col_expr(password_reset::Column::UsedAt, Expr::value(now))
+        .filter(password_reset::Column::UserId.eq(target.id))
+        .filter(password_reset::Column::UsedAt.is_null())
+        .exec(&trans)
+        .await?;
+
+    let token = gen_token_id();
+    let expires_at = now + Duration::seconds(config.auth.reset_token_lifetime);
+    password_reset::ActiveModel {
+        token_hash: Set(hash_secret_token(&token)),
+        user_id: Set(target.id),
+        created_by: Set(auth.auth_info.id),
+        created_at: Set(now),
+        expires_at: Set(expires_at),
+        used_at: Set(None),
+    }
+    .insert(&trans)
+    .await?;
+    trans.commit().await?;
+
+    Ok(AJson(ResetToken { token, expires_at }))
+}
+
+#[p(
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = OK, description = "Password reset successful", body = GeneralResponse),
+        (status = UNAUTHORIZED, description = "Invalid or expired reset token", body = GeneralResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "Password does not satisfy the policy", body = GeneralResponse),
+    ),
+)]
+#[post("/user/reset_password")]
+pub async fn reset_password(
+    info: AJson<ResetPasswordRequest>,
+    rd: Data<Option<Mutex<MultiplexedConnection>>>,
+    db: Data<DatabaseConnection>,
+    config: Data<Config>,
+) -> AResult<AJson<GeneralResponse>> {
+    let now = Utc::now().naive_utc();
+    let trans = db.begin().await?;
+    let reset = password_reset::Entity::find_by_id(hash_secret_token(&info.token))
+        .filter(password_reset::Column::UsedAt.is_null())
+        .filter(password_reset::Column::ExpiresAt.gt(now))
+        .one(&trans)
+        .await?
+        .ok_or_else(|| unauthorized("Invalid or expired reset token"))?;
+    let user = find_user_by_id(reset.user_id)
+        .one(&trans)
+        .await?
+        .ok_or_else(|| unauthorized("Invalid or expired reset token"))?;
+
+    // 重置凭据只能使用一次。条件更新保证并发请求时只有一个能成功
+    let marked = password_reset::Entity::update_many()
+        .col_expr(password_reset::Column::UsedAt, Expr::value(now))
+        .filter(password_reset::Column::TokenHash.eq(reset.token_hash))
+        .filter(password_reset::Column::UsedAt.is_null())
+        .exec(&trans)
+        .await?;
+    if marked.rows_affected == 0 {
+        return Err(unauthorized("Invalid or expired reset token").into());
+    }
+
+    let user = replace_password(user, &info.new_password, &config, &trans).await?;
+    trans.commit().await?;
+    invalidate_key(user.id, rd).await?;
+
+    Ok(AJson(GeneralResponse {
+        message: "Password reset successful".to_string(),
+        request_id: None,
+    }))
+}
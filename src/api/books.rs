@@ -1,9 +1,9 @@
 use crate::utils::errors::not_found;
 use crate::utils::errors::unprocessable_entity;
 
+use crate::utils::api_key::{Credential, OrApiKey};
 use crate::utils::ext::SelectExt;
 use crate::utils::jwt::AllowAdmin;
-use crate::utils::jwt::JwtClaims;
 use crate::utils::permission::APermission;
 
 use super::preclude::*;
@@ -36,7 +36,8 @@ pub struct BookFilter {
     pub publisher: Option<String>,
     #[serde(flatten)]
     pub paging: PagingRequest,
-    #[serde(alias = "sort")] 
+    /// 排序方式，不能与游标分页同时使用
+    #[serde(alias = "sort")]
     pub sort_by: Option<BookSort>,
 }
 
@@ -55,14 +56,15 @@ pub enum BookSort {
 #[p(
     params(BookFilter),
     responses(
-        (status = OK, description = "Get books successful", body = [Model])
+        (status = OK, description = "Get books successful", body = [Model]),
+        (status = BAD_REQUEST, description = "Cursor paging with sort_by", body = GeneralResponse),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[get("/book")]
 pub async fn get_books(
     data: Query<BookFilter>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<HttpResponse> {
     let mut query = entity::book::Entity::find();
@@ -79,6 +81,9 @@ pub async fn get_books(
         q.filter(entity::book::Column::Isbn.eq(v))
     });
 
+    if data.sort_by.is_some() {
+        data.paging.reject_cursor()?;
+    }
     query = query.apply_if(data.sort_by.as_ref(), |q, v| match v {
         BookSort::InventoryAsc => q.order_by_asc(entity::book::Column::InventoryCount),
         BookSort::InventoryDesc => q.order_by_desc(entity::book::Column::InventoryCount),
@@ -96,12 +101,12 @@ pub async fn get_books(
     responses(
         (status = OK, description = "Update book successful", body = Model),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[patch("/book/{isbn}")]
 pub async fn update_book(
     isbn: Path<String>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     book: AJson<UpdateBook>,
     db: Data<DatabaseConnection>,
 ) -> AResult<AJson<Model>> {
@@ -0,0 +1,171 @@
+// 供编排系统探测的存活、就绪检查，以及构建信息。
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use actix_web::web::Data;
+use actix_web::{get, HttpResponse};
+use migration::{Migrator, MigratorTrait};
+use redis::aio::MultiplexedConnection;
+use sea_orm::{ConnectionTrait, DatabaseConnection, Statement};
+use serde::Serialize;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use super::preclude::*;
+use super::GeneralResponse;
+
+/// 单项依赖检查的超时时间
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, ToSchema, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Error,
+    /// 未配置该依赖，例如未设置 Redis
+    Disabled,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct DependencyCheck {
+    pub status: CheckStatus,
+    /// 检查耗时（毫秒）
+    pub latency_ms: Option<f64>,
+    /// 检查失败的原因
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Readiness {
+    /// 所有依赖都可用时为 `true`
+    pub ready: bool,
+    pub database: DependencyCheck,
+    pub redis: DependencyCheck,
+    pub migrations: DependencyCheck,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    /// RFC 3339 格式的 UTC 时间
+    pub build_time: &'static str,
+}
+
+/// 计时执行一项检查，超时视为失败。
+async fn timed<F>(check: F) -> DependencyCheck
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let start = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("Timed out after {:?}", CHECK_TIMEOUT)),
+    };
+    let latency_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+    match result {
+        Ok(()) => DependencyCheck {
+            status: CheckStatus::Ok,
+            latency_ms,
+            detail: None,
+        },
+        Err(err) => DependencyCheck {
+            status: CheckStatus::Error,
+            latency_ms,
+            detail: Some(err),
+        },
+    }
+}
+
+#[p(
+    responses(
+        (status = OK, description = "The process is alive", body = GeneralResponse),
+    ),
+)]
+#[get("/healthz")]
+pub async fn healthz() -> AJson<GeneralResponse> {
+    AJson(GeneralResponse {
+        message: "ok".to_owned(),
+        request_id: None,
+    })
+}
+
+#[p(
+    responses(
+        (status = OK, description = "All dependencies are available", body = Readiness),
+        (status = SERVICE_UNAVAILABLE, description = "Some dependencies are unavailable", body = Readiness),
+    ),
+)]
+#[get("/readyz")]
+pub async fn readyz(
+    db: Data<DatabaseConnection>,
+    rd: Data<Option<Mutex<MultiplexedConnection>>>,
+) -> HttpResponse {
+    let database = timed(async {
+        let backend = db.get_database_backend();
+        db.execute(Statement::from_string(backend, "SELECT 1".to_owned()))
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    })
+    .await;
+
+    let redis = match rd.as_ref() {
+        Some(conn) => {
+            timed(async {
+                let mut conn = conn.lock().await;
+                redis::cmd("PING")
+                    .query_async::<_, String>(&mut *conn)
+                    .await
+                    .map(|_| ())
+                    .map_err(|err| err.to_string())
+            })
+            .await
+        }
+        None => DependencyCheck {
+            status: CheckStatus::Disabled,
+            latency_ms: None,
+            detail: None,
+        },
+    };
+
+    let migrations = timed(async {
+        let pending = Migrator::get_pending_migrations(db.get_ref())
+            .await
+            .map_err(|err| err.to_string())?;
+        match pending.is_empty() {
+            true => Ok(()),
+            false => Err(format!("{} pending migrations", pending.len())),
+        }
+    })
+    .await;
+
+    let ready = [&database, &redis, &migrations]
+        .iter()
+        .all(|check| check.status != CheckStatus::Error);
+    let readiness = Readiness {
+        ready,
+        database,
+        redis,
+        migrations,
+    };
+    match ready {
+        true => HttpResponse::Ok().json(readiness),
+        false => HttpResponse::ServiceUnavailable().json(readiness),
+    }
+}
+
+#[p(
+    responses(
+        (status = OK, description = "Build information", body = VersionInfo),
+    ),
+)]
+#[get("/version")]
+pub async fn version() -> AJson<VersionInfo> {
+    AJson(VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("GIT_COMMIT"),
+        build_time: env!("BUILD_TIME"),
+    })
+}
@@ -0,0 +1,37 @@
+use actix_web::http::header;
+use actix_web::web::Data;
+use actix_web::{get, HttpRequest, HttpResponse};
+use sea_orm::DatabaseConnection;
+
+use crate::config::Config;
+use crate::utils::errors::{not_found, unauthorized};
+use crate::utils::jwt::hash_secret_token;
+use crate::utils::metrics::METRICS;
+
+use super::preclude::*;
+
+/// Prometheus 格式的指标。不在 API 文档中列出，访问控制见 [`crate::config::MetricsConfig`]。
+#[get("/metrics")]
+pub async fn metrics(
+    req: HttpRequest,
+    db: Data<DatabaseConnection>,
+    config: Data<Config>,
+) -> AResult<HttpResponse> {
+    if !config.metrics.enabled {
+        return Err(not_found("Metrics are disabled").into());
+    }
+    if let Some(ref token) = config.metrics.token {
+        let provided = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        // 比较摘要而不是原文，避免比较耗时泄露令牌的内容
+        if provided.map(hash_secret_token) != Some(hash_secret_token(token)) {
+            return Err(unauthorized("Invalid metrics token").into());
+        }
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(METRICS.render(db.get_ref())))
+}
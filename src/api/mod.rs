@@ -2,12 +2,17 @@ use actix_web::web::ServiceConfig;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use utoipa::{
-    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
     IntoParams, Modify, OpenApi, ToSchema,
 };
 
+use crate::utils::errors::{bad_request, AResult};
+
+pub mod api_keys;
 pub mod auth;
 pub mod books;
+pub mod health;
+pub mod metrics;
 pub mod orders;
 mod preclude;
 pub mod stats;
@@ -16,17 +21,60 @@ pub mod transactions;
 #[derive(Serialize, ToSchema)]
 pub struct GeneralResponse {
     pub message: String,
+    /// 出错时附带请求编号，便于对照日志排查
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
+/// 分页参数，支持两种方式：
+///
+/// - 页码：`page` 与 `page_size`，响应体为数组；
+/// - 游标：`cursor` 与 `limit`（即 `page_size`），第一页传入空的 `cursor`，
+///   之后传入上一页响应中的 `next_cursor`。结果按主键倒序排列，
+///   订单列表按更新时间与主键倒序排列。翻页期间新增的记录不会导致重复或遗漏，
+///   但翻页期间被修改的订单会移到最前面。响应体为 [`CursorPage`]。
+///   使用其他排序方式的接口不支持游标分页。
+///
+/// `page_size` 超出 1 至 100 时返回 422。
 #[serde_as]
 #[derive(Deserialize, ToSchema, IntoParams)]
 pub struct PagingRequest {
     // 从字符串解析数据类型，是Query中无法使用#[serde(flatten)]的临时解决方案
     // 见 https://docs.rs/serde_qs/latest/serde_qs/index.html#flatten-workaround。
-    #[serde_as(as = "DisplayFromStr")]
-    pub page: u64,
-    #[serde_as(as = "DisplayFromStr")]
-    pub page_size: u64,
+    /// 页码，从 0 开始
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub page: Option<u64>,
+    /// 每页数量，默认为 20，范围为 1 至 100
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default, alias = "limit")]
+    pub page_size: Option<u64>,
+    /// 游标，传入时使用游标分页
+    pub cursor: Option<String>,
+    /// 是否在 `X-Item-Count` 响应头中返回总数，页码分页默认返回，游标分页默认不返回
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub count: Option<bool>,
+}
+
+impl PagingRequest {
+    /// 使用自定义排序的接口调用，传入游标时返回 400，而不是忽略接口的排序。
+    pub fn reject_cursor(&self) -> AResult<()> {
+        match self.cursor {
+            Some(_) => {
+                Err(bad_request("Cursor paging cannot be combined with a custom sort order").into())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// 游标分页的响应。
+#[derive(Serialize)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    /// 下一页的游标，没有下一页时为空
+    pub next_cursor: Option<String>,
 }
 
 #[derive(OpenApi)]
@@ -34,13 +82,21 @@ pub struct PagingRequest {
     paths(
         auth::login,
         auth::refresh,
+        auth::jwks,
         auth::logout,
         auth::register,
         auth::get_users,
         auth::get_self,
+        auth::change_password,
+        auth::issue_reset_token,
+        auth::reset_password,
         auth::get_user,
         auth::update_user,
         auth::delete_user,
+        auth::restore_user,
+        api_keys::get_api_keys,
+        api_keys::create_api_key,
+        api_keys::revoke_api_key,
         books::get_books,
         books::update_book,
         books::put_on_shelf,
@@ -54,28 +110,82 @@ pub struct PagingRequest {
         orders::revoke_stock,
         orders::confirm_stock,
         transactions::get_transaction_list,
+        health::healthz,
+        health::readyz,
+        health::version,
         stats::stat_transaction,
         stats::stat_stock,
         stats::stat_sell,
         stats::stat_book,
-        stats::stat_bestsell,
+        stats::bestsell::stat_bestsell,
+        stats::kpi::get_user_kpi,
+        stats::kpi::stat_kpi_ranking,
+        stats::timeseries::stat_timeseries,
+        stats::margin::stat_margin,
+        stats::inventory::stat_inventory_value,
+        stats::slow_movers::stat_slow_movers,
+        stats::forecast::get_forecast,
+        stats::forecast::get_forecasts,
+        stats::daily_close::get_daily_close,
+        stats::daily_close::close_day,
     ),
     components(schemas(
         auth::LoginRequest,
+        auth::ChangePasswordRequest,
+        auth::ResetToken,
+        auth::ResetPasswordRequest,
         auth::JwtToken,
         books::BookSort,
         books::PutOnShelfRequest,
+        health::CheckStatus,
+        health::DependencyCheck,
+        health::Readiness,
+        health::VersionInfo,
         stats::StatSpan,
+        stats::StatDelta,
+        stats::StatComparison,
         stats::StatTransaction,
         stats::StatStock,
         stats::StatSell,
         stats::StatBook,
-        stats::StatBestsell,
+        stats::bestsell::StatBestsell,
+        stats::bestsell::BestsellRank,
+        stats::bestsell::BestsellGroup,
+        stats::cost::CostMethod,
+        stats::margin::Margin,
+        stats::margin::BookMargin,
+        stats::margin::PublisherMargin,
+        stats::margin::PeriodMargin,
+        stats::margin::MarginReport,
+        stats::inventory::InventoryValue,
+        stats::inventory::BookInventoryValue,
+        stats::inventory::PublisherInventoryValue,
+        stats::inventory::InventoryValuation,
+        stats::slow_movers::SlowMover,
+        stats::forecast::ForecastMethod,
+        stats::forecast::ForecastPoint,
+        stats::forecast::Forecast,
+        stats::daily_close::CloseFigures,
+        stats::daily_close::OperatorClose,
+        stats::daily_close::DailyCloseReport,
+        stats::daily_close::ClosedDay,
+        stats::daily_close::DailyClose,
+        stats::kpi::KpiMetric,
+        stats::kpi::Kpi,
+        stats::kpi::RankedKpi,
+        stats::timeseries::TimeSeriesMetric,
+        stats::timeseries::TimeBucket,
+        stats::timeseries::TimeSeriesPoint,
+        stats::timeseries::TimeSeries,
         GeneralResponse,
         PagingRequest,
         entity::user::GetUser,
         entity::user::NewUser,
         entity::user::UpdateUser,
+        entity::api_key::ApiKeyScope,
+        entity::api_key::NewApiKey,
+        entity::api_key::GetApiKey,
+        entity::api_key::CreatedApiKey,
         entity::book::Model,
         entity::book::UpdateBook,
         entity::book::NewBookInfo,
@@ -103,6 +213,12 @@ impl Modify for SecurityAddon {
                     .bearer_format("JWT")
                     .build(),
             ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(
+                crate::contants::API_KEY_HEADER,
+            ))),
         )
     }
 }
@@ -111,13 +227,21 @@ pub fn configure() -> impl FnOnce(&mut ServiceConfig) {
     move |app: &mut ServiceConfig| {
         app.service(auth::login)
             .service(auth::refresh)
+            .service(auth::jwks)
             .service(auth::logout)
             .service(auth::register)
             .service(auth::get_users)
             .service(auth::get_self)
+            .service(auth::change_password)
+            .service(auth::issue_reset_token)
+            .service(auth::reset_password)
+            .service(api_keys::get_api_keys)
+            .service(api_keys::create_api_key)
+            .service(api_keys::revoke_api_key)
             .service(auth::get_user)
             .service(auth::update_user)
             .service(auth::delete_user)
+            .service(auth::restore_user)
             .service(books::get_books)
             .service(books::update_book)
             .service(books::put_on_shelf)
@@ -131,10 +255,24 @@ pub fn configure() -> impl FnOnce(&mut ServiceConfig) {
             .service(orders::revoke_stock)
             .service(orders::confirm_stock)
             .service(transactions::get_transaction_list)
+            .service(health::healthz)
+            .service(health::readyz)
+            .service(health::version)
+            .service(metrics::metrics)
             .service(stats::stat_transaction)
             .service(stats::stat_stock)
             .service(stats::stat_sell)
             .service(stats::stat_book)
-            .service(stats::stat_bestsell);
+            .service(stats::bestsell::stat_bestsell)
+            .service(stats::kpi::get_user_kpi)
+            .service(stats::kpi::stat_kpi_ranking)
+            .service(stats::timeseries::stat_timeseries)
+            .service(stats::margin::stat_margin)
+            .service(stats::inventory::stat_inventory_value)
+            .service(stats::slow_movers::stat_slow_movers)
+            .service(stats::forecast::get_forecast)
+            .service(stats::forecast::get_forecasts)
+            .service(stats::daily_close::get_daily_close)
+            .service(stats::daily_close::close_day);
     }
 }
@@ -1,6 +1,8 @@
+use crate::utils::api_key::{Credential, OrApiKey};
 use crate::utils::errors::{conflict, not_found, unprocessable_entity};
 use crate::utils::ext::SelectExt;
-use crate::utils::jwt::{AllowAdmin, JwtClaims};
+use crate::utils::jwt::AllowAdmin;
+use crate::utils::metrics::METRICS;
 use crate::utils::permission::APermission;
 
 use super::preclude::*;
@@ -19,7 +21,7 @@ use entity::order_list::{GetOrder, NewOrder};
 use entity::{order_list, TicketStatus, TicketType};
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
-    IntoActiveModel, QueryFilter, QueryOrder, QueryTrait, Set, TransactionTrait,
+    IntoActiveModel, QueryFilter, QueryTrait, Set, TransactionTrait,
 };
 use serde::Deserialize;
 use utoipa::IntoParams;
@@ -28,7 +30,7 @@ use utoipa::IntoParams;
 pub struct OrderFilter {
     pub status: Option<TicketStatus>,
     pub operator: Option<i32>,
-    #[serde(alias = "isbn")] 
+    #[serde(alias = "isbn")]
     pub book_isbn: Option<String>,
     pub id: Option<i32>,
     #[serde(flatten)]
@@ -40,12 +42,12 @@ pub struct OrderFilter {
     responses(
         (status = OK, description = "Book sold successfully", body = GetOrder),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[post("/sell")]
 pub async fn sell_book(
     order: AJson<NewOrder>,
-    auth: APermission<JwtClaims, AllowAdmin>,
+    auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<AJson<GetOrder>> {
     // 校验合法性
@@ -58,14 +60,13 @@ pub async fn sell_book(
         .await?
         .ok_or_else(|| not_found("Book not found"))?;
     // 创建订单
-    Ok(AJson(
-        order
-            .into_inner()
-            .into_active_model(auth.auth_info.id, TicketType::Sell)
-            .insert(db.get_ref())
-            .await?
-            .into(),
-    ))
+    let order = order
+        .into_inner()
+        .into_active_model(auth.auth_info.id, TicketType::Sell)
+        .insert(db.get_ref())
+        .await?;
+    METRICS.order(TicketType::Sell, "created");
+    Ok(AJson(order.into()))
 }
 
 async fn get_order_list(
@@ -76,7 +77,6 @@ async fn get_order_list(
     let params = params.into_inner();
     entity::order_list::Entity::find()
         .filter(order_list::Column::Typ.eq(typ))
-        .order_by_desc(order_list::Column::UpdatedAt)
         .apply_if(params.status.as_ref(), |q, v| {
             q.filter(order_list::Column::Status.eq(v.clone()))
         })
@@ -89,7 +89,11 @@ async fn get_order_list(
         .apply_if(params.id.as_ref(), |q, v| {
             q.filter(order_list::Column::Id.eq(*v))
         })
-        .paged::<DatabaseConnection, _, GetOrder>(params.paging, db.get_ref())
+        .paged_by::<DatabaseConnection, _, GetOrder>(
+            order_list::Column::UpdatedAt,
+            params.paging,
+            db.get_ref(),
+        )
         .await
 }
 
@@ -98,12 +102,12 @@ async fn get_order_list(
     responses(
         (status = OK, description = "Get sell list successfully", body = [GetOrder]),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[get("/sell")]
 pub async fn get_sell_list(
     paging: Query<OrderFilter>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<HttpResponse> {
     get_order_list(paging, db, TicketType::Sell).await
@@ -113,12 +117,12 @@ pub async fn get_sell_list(
     responses(
         (status = OK, description = "Customer buy book successfully", body = GetOrder),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[post("/sell/{id}/pay")]
 pub async fn pay_sell(
     id: Path<i32>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<AJson<GetOrder>> {
     let trans = db.begin().await?;
@@ -150,6 +154,8 @@ pub async fn pay_sell(
     active_book.update(&trans).await?;
 
     trans.commit().await?;
+    METRICS.order(TicketType::Sell, "paid");
+    METRICS.books_sold.inc_by(order.total_count as u64);
 
     Ok(AJson(order.into()))
 }
@@ -158,25 +164,24 @@ pub async fn pay_sell(
     responses(
         (status = OK, description = "Customer revoke order successfully", body = GetOrder),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[post("/sell/{id}/revoke")]
 pub async fn revoke_sell(
     id: Path<i32>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<AJson<GetOrder>> {
-    Ok(AJson(
-        change_order_status(
-            id.into_inner(),
-            TicketStatus::Pending,
-            TicketType::Sell,
-            TicketStatus::Revoked,
-            db.get_ref(),
-        )
-        .await?
-        .into(),
-    ))
+    let order = change_order_status(
+        id.into_inner(),
+        TicketStatus::Pending,
+        TicketType::Sell,
+        TicketStatus::Revoked,
+        db.get_ref(),
+    )
+    .await?;
+    METRICS.order(TicketType::Sell, "revoked");
+    Ok(AJson(order.into()))
 }
 
 #[p(
@@ -184,12 +189,12 @@ pub async fn revoke_sell(
     responses(
         (status = OK, description = "Stock book successfully", body = GetOrder),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[post("/stock")]
 pub async fn stock_book(
     order: AJson<NewOrder>,
-    auth: APermission<JwtClaims, AllowAdmin>,
+    auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<AJson<GetOrder>> {
     let order = order.into_inner();
@@ -223,6 +228,7 @@ pub async fn stock_book(
 
     // 提交更改
     trans.commit().await?;
+    METRICS.order(TicketType::Stock, "created");
     Ok(AJson(order.into()))
 }
 
@@ -231,12 +237,12 @@ pub async fn stock_book(
     responses(
         (status = OK, description = "Get stock list successfully", body = [GetOrder]),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[get("/stock")]
 pub async fn get_stock_list(
     params: Query<OrderFilter>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<HttpResponse> {
     get_order_list(params, db, TicketType::Stock).await
@@ -246,12 +252,12 @@ pub async fn get_stock_list(
     responses(
         (status = OK, description = "We pay for the book successfully", body = GetOrder),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[post("/stock/{id}/pay")]
 pub async fn pay_stock(
     id: Path<i32>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<AJson<GetOrder>> {
     let trans = db.begin().await?;
@@ -264,6 +270,7 @@ pub async fn pay_stock(
     )
     .await?;
     trans.commit().await?;
+    METRICS.order(TicketType::Stock, "paid");
     Ok(AJson(order.into()))
 }
 
@@ -271,37 +278,36 @@ pub async fn pay_stock(
     responses(
         (status = OK, description = "We revoke the order successfully", body = GetOrder),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[post("/stock/{id}/revoke")]
 pub async fn revoke_stock(
     id: Path<i32>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<AJson<GetOrder>> {
-    Ok(AJson(
-        change_order_status(
-            id.into_inner(),
-            TicketStatus::Pending,
-            TicketType::Stock,
-            TicketStatus::Revoked,
-            db.get_ref(),
-        )
-        .await?
-        .into(),
-    ))
+    let order = change_order_status(
+        id.into_inner(),
+        TicketStatus::Pending,
+        TicketType::Stock,
+        TicketStatus::Revoked,
+        db.get_ref(),
+    )
+    .await?;
+    METRICS.order(TicketType::Stock, "revoked");
+    Ok(AJson(order.into()))
 }
 
 #[p(
     responses(
         (status = OK, description = "We confirm the order successfully", body = GetOrder),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[post("/stock/{id}/confirm")]
 pub async fn confirm_stock(
     id: Path<i32>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<AJson<GetOrder>> {
     let trans = db.begin().await?;
@@ -325,6 +331,7 @@ pub async fn confirm_stock(
     book.update(&trans).await?;
 
     trans.commit().await?;
+    METRICS.order(TicketType::Stock, "confirmed");
 
     Ok(AJson(order.into()))
 }
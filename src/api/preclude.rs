@@ -1,5 +1,3 @@
-pub use actix_web::{
-    web::Json as AJson
-};
+pub use crate::utils::errors::AResult;
+pub use actix_web::web::Json as AJson;
 pub use utoipa::path as p;
-pub use crate::utils::errors::AResult;
\ No newline at end of file
@@ -0,0 +1,319 @@
+// 畅销排行。
+
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::utils::api_key::{Credential, OrApiKey};
+use crate::utils::errors::unprocessable_entity;
+use crate::utils::jwt::AllowAdmin;
+use crate::utils::permission::APermission;
+
+use crate::api::preclude::*;
+
+use actix_web::web::Data;
+use actix_web::{get, web::Query};
+use chrono::Utc;
+use entity::book::{self, NewBookInfo};
+use entity::{order_list, TicketStatus, TicketType};
+use sea_orm::sea_query::types::Alias;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use utoipa::{IntoParams, ToSchema};
+
+use super::cost::{sold_costs_in, CostMethod};
+use super::{
+    as_double, as_sint, should_filter_user, StatPeriod, StatRange, TOTAL_COUNT, TOTAL_PRICE,
+};
+
+/// 默认返回的条数
+const DEFAULT_LIMIT: u64 = 10;
+/// 最多返回的条数
+const MAX_LIMIT: u64 = 100;
+
+#[derive(Deserialize, ToSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BestsellRank {
+    /// 售出册数
+    #[serde(alias = "count")]
+    Copies,
+    /// 营业额
+    Revenue,
+    /// 毛利
+    Profit,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BestsellGroup {
+    Book,
+    Author,
+    Publisher,
+}
+
+impl BestsellGroup {
+    fn column(self) -> book::Column {
+        match self {
+            BestsellGroup::Book => book::Column::Isbn,
+            BestsellGroup::Author => book::Column::Author,
+            BestsellGroup::Publisher => book::Column::Publisher,
+        }
+    }
+
+    fn key(self, book: &book::Model) -> &str {
+        match self {
+            BestsellGroup::Book => &book.isbn,
+            BestsellGroup::Author => &book.author,
+            BestsellGroup::Publisher => &book.publisher,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Deserialize, IntoParams)]
+pub struct BestsellOption {
+    #[serde(flatten)]
+    pub period: StatPeriod,
+    /// 排名依据，默认为售出册数
+    pub rank_by: Option<BestsellRank>,
+    /// 分组方式，默认按书籍分组
+    pub group_by: Option<BestsellGroup>,
+    /// 返回的条数，默认为 10，最多为 100
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub limit: Option<u64>,
+    /// 按毛利排名时使用的成本计价方法，默认使用服务器配置
+    pub method: Option<CostMethod>,
+    /// 超级管理员可以查看所有人的数据
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub all: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StatBestsell {
+    /// 排名，从 1 开始
+    pub rank: usize,
+    /// 分组的键：按书籍分组时为 ISBN，否则为作者或出版社名称
+    pub key: String,
+    /// 只在按书籍分组时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isbn: Option<String>,
+    /// 只在按书籍分组时返回
+    #[serde(flatten)]
+    #[schema(inline)]
+    pub info: Option<NewBookInfo>,
+    pub total_sell_count: i64,
+    pub total_revenue: f64,
+    /// 毛利，只在按毛利排名时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gross_profit: Option<f64>,
+    /// 按排名依据计算的、在周期内全部销售中所占的百分比
+    pub share: f64,
+}
+
+/// 排名中的一个分组。
+struct RankedGroup {
+    key: String,
+    count: i64,
+    revenue: f64,
+    profit: Option<f64>,
+}
+
+#[derive(FromQueryResult)]
+struct BestsellRow {
+    key: String,
+    tc: Option<i64>,
+    tp: Option<f64>,
+}
+
+#[derive(FromQueryResult)]
+struct BestsellTotal {
+    tc: Option<i64>,
+    tp: Option<f64>,
+}
+
+/// 按毛利排名。成本需要重放订单记录得到，因此在内存中分组。
+///
+/// 返回排名靠前的分组，以及全部分组的毛利之和。
+async fn rank_by_profit(
+    db: &DatabaseConnection,
+    range: &StatRange,
+    method: CostMethod,
+    group: BestsellGroup,
+    operator: Option<i32>,
+) -> AResult<(Vec<RankedGroup>, f64)> {
+    let sold = sold_costs_in(db, method, range, operator).await?;
+
+    let mut books: BTreeMap<String, book::Model> = BTreeMap::new();
+    if group != BestsellGroup::Book {
+        books = book::Entity::find()
+            .filter(book::Column::Isbn.is_in(sold.iter().map(|sold| sold.isbn.clone())))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|book| (book.isbn.clone(), book))
+            .collect();
+    }
+
+    let mut groups: BTreeMap<String, (i64, f64, f64)> = BTreeMap::new();
+    for sold in sold {
+        let key = match books.get(&sold.isbn) {
+            Some(book) => group.key(book).to_owned(),
+            None => sold.isbn,
+        };
+        let (count, revenue, profit) = groups.entry(key).or_default();
+        *count += sold.count as i64;
+        *revenue += sold.revenue;
+        *profit += sold.revenue - sold.cogs;
+    }
+    let total = groups.values().map(|(_, _, profit)| profit).sum();
+    let mut ranked: Vec<_> = groups
+        .into_iter()
+        .map(|(key, (count, revenue, profit))| RankedGroup {
+            key,
+            count,
+            revenue,
+            profit: Some(profit),
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.profit.unwrap_or(0.0).total_cmp(&a.profit.unwrap_or(0.0)));
+    Ok((ranked, total))
+}
+
+#[p(
+    params(BestsellOption),
+    responses(
+        (status = OK, description = "Stat successful", body = Vec<StatBestsell>),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid limit", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/bestsell")]
+pub async fn stat_bestsell(
+    param: Query<BestsellOption>,
+    db: Data<DatabaseConnection>,
+    config: Data<Config>,
+    auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<Vec<StatBestsell>>> {
+    let limit = param.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(
+            unprocessable_entity(format!("Limit must be between 1 and {}", MAX_LIMIT)).into(),
+        );
+    }
+    let (range, _) = param.period.resolve(Utc::now())?;
+    let rank_by = param.rank_by.unwrap_or(BestsellRank::Copies);
+    let group = param.group_by.unwrap_or(BestsellGroup::Book);
+    let operator = should_filter_user(param.all, &auth.auth_info).then_some(auth.auth_info.id);
+
+    let (ranked, total) = match rank_by {
+        BestsellRank::Profit => {
+            let method = param.method.unwrap_or(config.stats.cost_method);
+            let (mut ranked, total) =
+                rank_by_profit(db.get_ref(), &range, method, group, operator).await?;
+            ranked.truncate(limit as usize);
+            (ranked, total)
+        }
+        BestsellRank::Copies | BestsellRank::Revenue => {
+            // 与按毛利排名一致，按订单完成的时间统计
+            let query = range
+                .with_constraint(order_list::Entity::find(), order_list::Column::UpdatedAt)
+                .filter(order_list::Column::Typ.eq(TicketType::Sell))
+                .filter(order_list::Column::Status.eq(TicketStatus::Done))
+                .apply_if(operator, |q, v| {
+                    q.filter(order_list::Column::OperatorId.eq(v))
+                })
+                .select_only()
+                .column_as(
+                    as_sint(db.get_ref(), order_list::Column::TotalCount.sum()),
+                    TOTAL_COUNT,
+                )
+                .column_as(
+                    as_double(db.get_ref(), order_list::Column::TotalPrice.sum()),
+                    TOTAL_PRICE,
+                );
+
+            let totals = query
+                .clone()
+                .into_model::<BestsellTotal>()
+                .one(db.get_ref())
+                .await?;
+            let total = match (rank_by, totals) {
+                (BestsellRank::Copies, Some(totals)) => totals.tc.unwrap_or(0) as f64,
+                (_, Some(totals)) => totals.tp.unwrap_or(0.0),
+                (_, None) => 0.0,
+            };
+
+            let order_by = match rank_by {
+                BestsellRank::Copies => TOTAL_COUNT,
+                _ => TOTAL_PRICE,
+            };
+            let rows = query
+                .inner_join(book::Entity)
+                .column_as(group.column(), "key")
+                .group_by(group.column())
+                .order_by_desc(Expr::col(Alias::new(order_by)))
+                .order_by_asc(group.column())
+                .limit(limit)
+                .into_model::<BestsellRow>()
+                .all(db.get_ref())
+                .await?;
+            (
+                rows.into_iter()
+                    .map(|row| RankedGroup {
+                        key: row.key,
+                        count: row.tc.unwrap_or(0),
+                        revenue: row.tp.unwrap_or(0.0),
+                        profit: None,
+                    })
+                    .collect::<Vec<_>>(),
+                total,
+            )
+        }
+    };
+
+    let mut books: BTreeMap<String, book::Model> = BTreeMap::new();
+    if group == BestsellGroup::Book {
+        books = book::Entity::find()
+            .filter(book::Column::Isbn.is_in(ranked.iter().map(|group| group.key.clone())))
+            .all(db.get_ref())
+            .await?
+            .into_iter()
+            .map(|book| (book.isbn.clone(), book))
+            .collect();
+    }
+
+    Ok(AJson(
+        ranked
+            .into_iter()
+            .enumerate()
+            .map(|(index, group)| {
+                let value = match rank_by {
+                    BestsellRank::Copies => group.count as f64,
+                    BestsellRank::Revenue => group.revenue,
+                    BestsellRank::Profit => group.profit.unwrap_or(0.0),
+                };
+                let book = books.remove(&group.key);
+                StatBestsell {
+                    rank: index + 1,
+                    isbn: book.as_ref().map(|book| book.isbn.clone()),
+                    info: book.map(Into::into),
+                    key: group.key,
+                    total_sell_count: group.count,
+                    total_revenue: group.revenue,
+                    gross_profit: group.profit,
+                    share: if total != 0.0 {
+                        value / total * 100.0
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect(),
+    ))
+}
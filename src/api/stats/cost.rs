@@ -0,0 +1,208 @@
+// 售出书籍的成本核算。
+//
+// 按时间顺序重放已确认入库的进货订单和已完成的售书订单，得到每笔售书订单的销售成本。
+
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use entity::{order_list, TicketStatus, TicketType};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::preclude::AResult;
+
+use super::StatRange;
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CostMethod {
+    /// 移动加权平均
+    Average,
+    /// 先进先出
+    Fifo,
+}
+
+impl FromStr for CostMethod {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "average" => Ok(CostMethod::Average),
+            "fifo" => Ok(CostMethod::Fifo),
+            _ => Err(()),
+        }
+    }
+}
+
+/// 一笔已完成的售书订单及其销售成本。
+#[derive(Debug, Clone)]
+pub struct SoldCost {
+    pub order_id: i32,
+    pub isbn: String,
+    pub operator_id: i32,
+    /// 订单完成的时间
+    pub sold_at: NaiveDateTime,
+    pub count: i32,
+    pub revenue: f64,
+    pub cogs: f64,
+}
+
+/// 单本书的库存成本。
+#[derive(Default)]
+pub struct CostLedger {
+    /// 尚未售出的进货批次：(数量, 单价)
+    lots: VecDeque<(i64, f64)>,
+    /// 最近一次进货的单价。库存成本不足以覆盖售出数量时（例如历史库存没有进货记录），按此单价计算
+    last_unit_cost: f64,
+}
+
+impl CostLedger {
+    fn stock(&mut self, method: CostMethod, count: i64, total_price: f64) {
+        if count <= 0 {
+            return;
+        }
+        self.last_unit_cost = total_price / count as f64;
+        match method {
+            CostMethod::Fifo => self.lots.push_back((count, self.last_unit_cost)),
+            CostMethod::Average => {
+                // 加权平均只需要一个批次
+                let (qty, unit) = self.lots.pop_front().unwrap_or((0, 0.0));
+                let qty_after = qty + count;
+                let unit_after = (qty as f64 * unit + total_price) / qty_after as f64;
+                self.lots.push_back((qty_after, unit_after));
+            }
+        }
+    }
+
+    /// 售出 `count` 本，返回销售成本。
+    fn sell(&mut self, count: i64) -> f64 {
+        let mut remaining = count;
+        let mut cogs = 0.0;
+        while remaining > 0 {
+            let Some((qty, unit)) = self.lots.front_mut() else {
+                break;
+            };
+            let taken = remaining.min(*qty);
+            cogs += taken as f64 * *unit;
+            remaining -= taken;
+            *qty -= taken;
+            if *qty == 0 {
+                self.lots.pop_front();
+            }
+        }
+        cogs + remaining as f64 * self.last_unit_cost
+    }
+
+    /// 剩余库存的平均单位成本。没有剩余批次时使用最近一次进货的单价。
+    pub fn unit_cost(&self) -> f64 {
+        let qty: i64 = self.lots.iter().map(|(qty, _)| qty).sum();
+        if qty == 0 {
+            return self.last_unit_cost;
+        }
+        self.lots
+            .iter()
+            .map(|(qty, unit)| *qty as f64 * unit)
+            .sum::<f64>()
+            / qty as f64
+    }
+}
+
+/// 重放订单记录的结果。
+pub struct CostReplay {
+    /// 所有已完成的售书订单，按完成时间排序
+    pub sold: Vec<SoldCost>,
+    /// 每本书在重放结束时的库存成本
+    pub ledgers: BTreeMap<String, CostLedger>,
+}
+
+/// 重放截至 `until`（不包含）的所有已完成订单。`isbns` 不为空时只重放这些书籍的订单。
+///
+/// 成本取决于此前全部的进货记录，因此总是从头开始计算，开销随所涉及书籍的订单数增长。
+/// 调用者应尽量通过 `isbns` 限定需要的书籍；不限定时（例如全部库存的估值）会读取全部订单记录。
+pub async fn replay<C: ConnectionTrait>(
+    db: &C,
+    method: CostMethod,
+    until: Option<NaiveDateTime>,
+    isbns: Option<Vec<String>>,
+) -> AResult<CostReplay> {
+    let orders = order_list::Entity::find()
+        .filter(order_list::Column::Status.eq(TicketStatus::Done))
+        .apply_if(until, |q, until| {
+            q.filter(order_list::Column::UpdatedAt.lt(until))
+        })
+        .apply_if(isbns, |q, isbns| {
+            q.filter(order_list::Column::BookIsbn.is_in(isbns))
+        })
+        .order_by_asc(order_list::Column::UpdatedAt)
+        .order_by_asc(order_list::Column::Id)
+        .all(db)
+        .await?;
+
+    let mut ledgers: BTreeMap<String, CostLedger> = BTreeMap::new();
+    let mut sold = Vec::new();
+    for order in orders {
+        let ledger = ledgers.entry(order.book_isbn.clone()).or_default();
+        match order.typ {
+            TicketType::Stock => {
+                ledger.stock(method, order.total_count as i64, order.total_price as f64)
+            }
+            TicketType::Sell => {
+                let cogs = ledger.sell(order.total_count as i64);
+                sold.push(SoldCost {
+                    order_id: order.id,
+                    isbn: order.book_isbn,
+                    operator_id: order.operator_id,
+                    sold_at: order.updated_at,
+                    count: order.total_count,
+                    revenue: order.total_price as f64,
+                    cogs,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(CostReplay { sold, ledgers })
+}
+
+/// 计算统计周期内已完成的售书订单的销售成本，按完成时间排序。`operator` 不为空时只保留该操作员的订单。
+///
+/// 只重放周期内有销售的书籍。
+pub async fn sold_costs_in<C: ConnectionTrait>(
+    db: &C,
+    method: CostMethod,
+    range: &StatRange,
+    operator: Option<i32>,
+) -> AResult<Vec<SoldCost>> {
+    let isbns = match range.from {
+        Some(_) => Some(
+            range
+                .with_constraint(order_list::Entity::find(), order_list::Column::UpdatedAt)
+                .filter(order_list::Column::Typ.eq(TicketType::Sell))
+                .filter(order_list::Column::Status.eq(TicketStatus::Done))
+                .apply_if(operator, |q, id| {
+                    q.filter(order_list::Column::OperatorId.eq(id))
+                })
+                .select_only()
+                .column(order_list::Column::BookIsbn)
+                .distinct()
+                .into_tuple::<String>()
+                .all(db)
+                .await?,
+        ),
+        None => None,
+    };
+    if isbns.as_ref().map_or(false, Vec::is_empty) {
+        return Ok(vec![]);
+    }
+    Ok(replay(db, method, range.to, isbns)
+        .await?
+        .sold
+        .into_iter()
+        .filter(|sold| range.from.map_or(true, |from| sold.sold_at >= from))
+        .filter(|sold| operator.map_or(true, |id| sold.operator_id == id))
+        .collect())
+}
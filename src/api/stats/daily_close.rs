@@ -0,0 +1,311 @@
+// 日结报表。
+
+use std::collections::BTreeMap;
+
+use crate::utils::api_key::{Credential, OrApiKey};
+use crate::utils::errors::{conflict, internal_server_error, unprocessable_entity};
+use crate::utils::jwt::{AllowAdmin, AllowSuperAdmin, JwtClaims};
+use crate::utils::permission::APermission;
+
+use crate::api::preclude::*;
+
+use actix_web::web::Data;
+use actix_web::{get, post, web::Query};
+use chrono::{Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use entity::order_list::GetOrder;
+use entity::{daily_close, order_list, transaction, user, TicketStatus, TicketType};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::TzOffset;
+
+#[derive(Deserialize, IntoParams)]
+pub struct DailyCloseOption {
+    /// 营业日，默认为今天
+    pub date: Option<NaiveDate>,
+    /// 划分营业日所用的时区，例如 `+08:00`（在 URL 中需写作 `%2B08:00`），默认为 UTC。已日结的营业日总是使用日结时的时区
+    #[param(value_type = Option<String>)]
+    pub tz: Option<TzOffset>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Default, Clone, PartialEq)]
+pub struct CloseFigures {
+    /// 完成的售书订单数
+    pub sell_count: i64,
+    /// 售出的册数
+    pub copies_sold: i64,
+    /// 售书收入
+    pub revenue: f64,
+    /// 撤销的售书订单数
+    pub revoked_count: i64,
+    /// 撤销的售书订单中的册数
+    pub revoked_copies: i64,
+    /// 支付的进货款
+    pub stock_payments: f64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct OperatorClose {
+    pub operator_id: i32,
+    pub real_name: String,
+    #[serde(flatten)]
+    pub figures: CloseFigures,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct DailyCloseReport {
+    pub date: NaiveDate,
+    /// 划分营业日所用的时区
+    pub tz: String,
+    /// 营业日的起止时间（UTC）
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    #[serde(flatten)]
+    pub totals: CloseFigures,
+    /// 按员工编号排序
+    pub operators: Vec<OperatorClose>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ClosedDay {
+    pub closed_at: NaiveDateTime,
+    pub closed_by: i32,
+    /// 日结时的报表
+    pub snapshot: DailyCloseReport,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DailyClose {
+    /// 根据当前数据计算的报表
+    #[serde(flatten)]
+    pub report: DailyCloseReport,
+    /// 日结信息，尚未日结时为空
+    pub closed: Option<ClosedDay>,
+    /// 日结后又被修改过的、属于该营业日的订单
+    pub adjustments: Vec<GetOrder>,
+}
+
+/// 营业日在 UTC 中的起止时间。
+fn day_bounds(date: NaiveDate, tz: FixedOffset) -> (NaiveDateTime, NaiveDateTime) {
+    let starts_at = tz
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .unwrap()
+        .naive_utc();
+    (starts_at, starts_at + Duration::days(1))
+}
+
+/// 根据当前数据计算某个营业日的报表。
+async fn build_report(
+    db: &DatabaseConnection,
+    date: NaiveDate,
+    tz: FixedOffset,
+) -> AResult<DailyCloseReport> {
+    let (starts_at, ends_at) = day_bounds(date, tz);
+    let mut operators: BTreeMap<i32, CloseFigures> = BTreeMap::new();
+
+    // 售书订单在完成或撤销时更新 `updated_at`
+    let orders = order_list::Entity::find()
+        .filter(order_list::Column::Typ.eq(TicketType::Sell))
+        .filter(order_list::Column::Status.is_in([TicketStatus::Done, TicketStatus::Revoked]))
+        .filter(order_list::Column::UpdatedAt.gte(starts_at))
+        .filter(order_list::Column::UpdatedAt.lt(ends_at))
+        .all(db)
+        .await?;
+    for order in orders {
+        let figures = operators.entry(order.operator_id).or_default();
+        if order.status == TicketStatus::Done {
+            figures.sell_count += 1;
+            figures.copies_sold += order.total_count as i64;
+        } else {
+            figures.revoked_count += 1;
+            figures.revoked_copies += order.total_count as i64;
+        }
+    }
+
+    // 金额以当天的支付记录为准
+    let payments = transaction::Entity::find()
+        .inner_join(order_list::Entity)
+        .filter(transaction::Column::CreatedAt.gte(starts_at))
+        .filter(transaction::Column::CreatedAt.lt(ends_at))
+        .select_only()
+        .column(order_list::Column::OperatorId)
+        .column(order_list::Column::Typ)
+        .column(order_list::Column::TotalPrice)
+        .into_tuple::<(i32, TicketType, f32)>()
+        .all(db)
+        .await?;
+    for (operator_id, typ, price) in payments {
+        let figures = operators.entry(operator_id).or_default();
+        match typ {
+            TicketType::Sell => figures.revenue += price as f64,
+            TicketType::Stock => figures.stock_payments += price as f64,
+            _ => {}
+        }
+    }
+
+    let names: BTreeMap<i32, String> = user::Entity::find()
+        .filter(user::Column::Id.is_in(operators.keys().copied()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user.real_name))
+        .collect();
+
+    let mut totals = CloseFigures::default();
+    for figures in operators.values() {
+        totals.sell_count += figures.sell_count;
+        totals.copies_sold += figures.copies_sold;
+        totals.revenue += figures.revenue;
+        totals.revoked_count += figures.revoked_count;
+        totals.revoked_copies += figures.revoked_copies;
+        totals.stock_payments += figures.stock_payments;
+    }
+
+    Ok(DailyCloseReport {
+        date,
+        tz: tz.to_string(),
+        starts_at,
+        ends_at,
+        totals,
+        operators: operators
+            .into_iter()
+            .map(|(operator_id, figures)| OperatorClose {
+                operator_id,
+                real_name: names.get(&operator_id).cloned().unwrap_or_default(),
+                figures,
+            })
+            .collect(),
+    })
+}
+
+/// 解析请求中的营业日和时区。
+fn resolve_day(param: &DailyCloseOption) -> (NaiveDate, FixedOffset) {
+    let tz = param.tz.unwrap_or_default().0;
+    let date = param
+        .date
+        .unwrap_or_else(|| tz.from_utc_datetime(&Utc::now().naive_utc()).date_naive());
+    (date, tz)
+}
+
+#[p(
+    params(DailyCloseOption),
+    responses(
+        (status = OK, description = "Get daily close successful", body = DailyClose),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/daily_close")]
+pub async fn get_daily_close(
+    param: Query<DailyCloseOption>,
+    db: Data<DatabaseConnection>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<DailyClose>> {
+    let (date, tz) = resolve_day(&param);
+    let closed = daily_close::Entity::find_by_id(date)
+        .one(db.get_ref())
+        .await?;
+
+    let (closed, tz) = match closed {
+        Some(closed) => {
+            let tz = FixedOffset::east_opt(closed.tz_offset)
+                .ok_or_else(|| internal_server_error("Invalid time zone offset"))?;
+            let snapshot: DailyCloseReport =
+                serde_json::from_str(&closed.report).map_err(internal_server_error)?;
+            (
+                Some(ClosedDay {
+                    closed_at: closed.closed_at,
+                    closed_by: closed.closed_by,
+                    snapshot,
+                }),
+                tz,
+            )
+        }
+        None => (None, tz),
+    };
+    let report = build_report(db.get_ref(), date, tz).await?;
+
+    // 日结之后修改过的、在该营业日创建或更新的订单
+    let adjustments = match closed {
+        Some(ref closed) => order_list::Entity::find()
+            .filter(order_list::Column::UpdatedAt.gt(closed.closed_at))
+            .filter(
+                Condition::any()
+                    .add(
+                        Condition::all()
+                            .add(order_list::Column::CreatedAt.gte(report.starts_at))
+                            .add(order_list::Column::CreatedAt.lt(report.ends_at)),
+                    )
+                    .add(
+                        Condition::all()
+                            .add(order_list::Column::UpdatedAt.gte(report.starts_at))
+                            .add(order_list::Column::UpdatedAt.lt(report.ends_at)),
+                    ),
+            )
+            .order_by_asc(order_list::Column::UpdatedAt)
+            .all(db.get_ref())
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        None => vec![],
+    };
+
+    Ok(AJson(DailyClose {
+        report,
+        closed,
+        adjustments,
+    }))
+}
+
+#[p(
+    params(DailyCloseOption),
+    responses(
+        (status = OK, description = "Close the day successful", body = DailyCloseReport),
+        (status = CONFLICT, description = "The day is already closed", body = GeneralResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "The day has not started yet", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []))
+)]
+#[post("/stats/daily_close")]
+pub async fn close_day(
+    param: Query<DailyCloseOption>,
+    db: Data<DatabaseConnection>,
+    auth: APermission<JwtClaims, AllowSuperAdmin>,
+) -> AResult<AJson<DailyCloseReport>> {
+    let (date, tz) = resolve_day(&param);
+    let now = Utc::now().naive_utc();
+    if day_bounds(date, tz).0 > now {
+        return Err(unprocessable_entity("Cannot close a day that has not started").into());
+    }
+    if daily_close::Entity::find_by_id(date)
+        .one(db.get_ref())
+        .await?
+        .is_some()
+    {
+        return Err(conflict("The day is already closed").into());
+    }
+
+    let report = build_report(db.get_ref(), date, tz).await?;
+    let record = daily_close::ActiveModel {
+        date: Set(date),
+        tz_offset: Set(tz.local_minus_utc()),
+        report: Set(serde_json::to_string(&report).map_err(internal_server_error)?),
+        closed_by: Set(auth.auth_info.id),
+        closed_at: Set(now),
+    };
+    if let Err(err) = record.insert(db.get_ref()).await {
+        // 并发的日结可能抢先插入了记录，不同数据库的主键冲突错误不同，只能再查询一次
+        return match daily_close::Entity::find_by_id(date)
+            .one(db.get_ref())
+            .await?
+        {
+            Some(_) => Err(conflict("The day is already closed").into()),
+            None => Err(err.into()),
+        };
+    }
+    Ok(AJson(report))
+}
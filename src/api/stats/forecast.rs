@@ -0,0 +1,341 @@
+// 销量预测与补货建议。
+
+use std::collections::BTreeMap;
+
+use crate::utils::api_key::{Credential, OrApiKey};
+use crate::utils::errors::{not_found, unprocessable_entity};
+use crate::utils::jwt::AllowAdmin;
+use crate::utils::permission::APermission;
+
+use crate::api::preclude::*;
+
+use actix_web::web::Data;
+use actix_web::{
+    get,
+    web::{Path, Query},
+};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use entity::{book, order_list, TicketStatus, TicketType};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, QueryTrait};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use utoipa::{IntoParams, ToSchema};
+
+use super::TzOffset;
+
+const DEFAULT_HORIZON: u32 = 30;
+const MAX_HORIZON: u32 = 365;
+const DEFAULT_HISTORY: u32 = 56;
+const MIN_HISTORY: u32 = 7;
+const MAX_HISTORY: u32 = 730;
+const DEFAULT_ALPHA: f64 = 0.3;
+/// 批量预测时最多可以指定的 ISBN 数量
+const MAX_ISBNS: usize = 100;
+/// 每次查询销售记录的书籍数量，避免 `IN` 中的参数过多
+const ISBN_BATCH_SIZE: usize = 500;
+
+#[derive(Deserialize, Serialize, ToSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastMethod {
+    /// 历史窗口内的日均销量
+    MovingAverage,
+    /// 带周季节性的指数平滑：先按星期几计算季节系数，再对去季节化的销量做指数平滑
+    Seasonal,
+}
+
+#[serde_as]
+#[derive(Deserialize, IntoParams)]
+pub struct ForecastOption {
+    /// 预测未来多少天，默认为 30，最多为 365
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub horizon: Option<u32>,
+    /// 使用最近多少天的销售记录，默认为 56，范围为 7 至 730
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub history: Option<u32>,
+    /// 预测方法，默认为带周季节性的指数平滑
+    pub method: Option<ForecastMethod>,
+    /// 指数平滑系数，范围为 (0, 1]，默认为 0.3
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub alpha: Option<f64>,
+    /// 按天划分销量所用的时区，例如 `+08:00`（在 URL 中需写作 `%2B08:00`），默认为 UTC
+    #[param(value_type = Option<String>)]
+    pub tz: Option<TzOffset>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct BulkForecastOption {
+    /// 以逗号分隔的 ISBN，最多 100 个。不指定时预测所有有库存或在历史窗口内有销售的书籍
+    pub isbns: Option<String>,
+    #[serde(flatten)]
+    pub option: ForecastOption,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ForecastPoint {
+    pub date: NaiveDate,
+    pub demand: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Forecast {
+    pub isbn: String,
+    pub title: String,
+    pub method: ForecastMethod,
+    /// 库存与架上数量之和
+    pub stock_count: i64,
+    /// 历史窗口内的日均销量
+    pub average_daily_demand: f64,
+    /// 预测期内的总需求
+    pub projected_demand: f64,
+    /// 预计售罄的日期，预测期内不会售罄时为空
+    pub stockout_date: Option<NaiveDate>,
+    /// 建议的进货数量，即预测期内的总需求减去现有库存
+    pub suggested_order_quantity: i64,
+    /// 每天的预测需求，批量预测时不返回
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub daily: Vec<ForecastPoint>,
+}
+
+/// 根据每天的历史销量预测未来 `horizon` 天的需求。
+///
+/// `start` 为历史销量第一天的日期，用于确定星期几。
+pub fn project_demand(
+    history: &[f64],
+    start: NaiveDate,
+    horizon: usize,
+    method: ForecastMethod,
+    alpha: f64,
+) -> Vec<f64> {
+    let mean = if history.is_empty() {
+        0.0
+    } else {
+        history.iter().sum::<f64>() / history.len() as f64
+    };
+    let weekday = |offset: usize| {
+        (start + Duration::days(offset as i64))
+            .weekday()
+            .num_days_from_monday() as usize
+    };
+
+    match method {
+        ForecastMethod::MovingAverage => vec![mean; horizon],
+        ForecastMethod::Seasonal => {
+            if mean == 0.0 {
+                return vec![0.0; horizon];
+            }
+            // 季节系数：某个星期几的平均销量与整体平均销量之比
+            let mut sums = [0.0; 7];
+            let mut days = [0usize; 7];
+            for (offset, value) in history.iter().enumerate() {
+                sums[weekday(offset)] += value;
+                days[weekday(offset)] += 1;
+            }
+            let mut season = [1.0; 7];
+            for day in 0..7 {
+                if days[day] > 0 {
+                    season[day] = sums[day] / days[day] as f64 / mean;
+                }
+            }
+
+            // 对去季节化的销量做指数平滑。季节系数为 0 的日子没有信息，跳过
+            let mut level = mean;
+            for (offset, value) in history.iter().enumerate() {
+                let factor = season[weekday(offset)];
+                if factor > 0.0 {
+                    level = alpha * (value / factor) + (1.0 - alpha) * level;
+                }
+            }
+
+            (0..horizon)
+                .map(|day| level * season[weekday(history.len() + day)])
+                .collect()
+        }
+    }
+}
+
+/// 按选项预测多本书的需求。
+async fn forecast_books(
+    db: &DatabaseConnection,
+    books: Vec<book::Model>,
+    option: &ForecastOption,
+    with_daily: bool,
+) -> AResult<Vec<Forecast>> {
+    let horizon = option.horizon.unwrap_or(DEFAULT_HORIZON);
+    if !(1..=MAX_HORIZON).contains(&horizon) {
+        return Err(
+            unprocessable_entity(format!("Horizon must be between 1 and {}", MAX_HORIZON)).into(),
+        );
+    }
+    let history = option.history.unwrap_or(DEFAULT_HISTORY);
+    if !(MIN_HISTORY..=MAX_HISTORY).contains(&history) {
+        return Err(unprocessable_entity(format!(
+            "History must be between {} and {}",
+            MIN_HISTORY, MAX_HISTORY
+        ))
+        .into());
+    }
+    let alpha = option.alpha.unwrap_or(DEFAULT_ALPHA);
+    if !(alpha > 0.0 && alpha <= 1.0) {
+        return Err(unprocessable_entity("Alpha must be in (0, 1]").into());
+    }
+    let method = option.method.unwrap_or(ForecastMethod::Seasonal);
+    let tz = option.tz.unwrap_or_default().0;
+
+    // 历史窗口为今天之前的 `history` 个完整的天
+    let today = tz.from_utc_datetime(&Utc::now().naive_utc()).date_naive();
+    let start = today - Duration::days(history as i64);
+    let start_utc = tz
+        .from_local_datetime(&start.and_hms_opt(0, 0, 0).unwrap())
+        .unwrap()
+        .naive_utc();
+    let end_utc = start_utc + Duration::days(history as i64);
+
+    let isbns: Vec<String> = books.iter().map(|book| book.isbn.clone()).collect();
+    let mut daily: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for batch in isbns.chunks(ISBN_BATCH_SIZE) {
+        for (isbn, at, count) in order_list::Entity::find()
+            .filter(order_list::Column::Typ.eq(TicketType::Sell))
+            .filter(order_list::Column::Status.eq(TicketStatus::Done))
+            .filter(order_list::Column::UpdatedAt.gte(start_utc))
+            .filter(order_list::Column::UpdatedAt.lt(end_utc))
+            .filter(order_list::Column::BookIsbn.is_in(batch.iter().cloned()))
+            .select_only()
+            .column(order_list::Column::BookIsbn)
+            .column(order_list::Column::UpdatedAt)
+            .column(order_list::Column::TotalCount)
+            .into_tuple::<(String, NaiveDateTime, i32)>()
+            .all(db)
+            .await?
+        {
+            let day = (tz.from_utc_datetime(&at).date_naive() - start).num_days() as usize;
+            let series = daily
+                .entry(isbn)
+                .or_insert_with(|| vec![0.0; history as usize]);
+            if let Some(value) = series.get_mut(day) {
+                *value += count as f64;
+            }
+        }
+    }
+
+    let empty = vec![0.0; history as usize];
+    Ok(books
+        .into_iter()
+        .map(|book| {
+            let series = daily.get(&book.isbn).unwrap_or(&empty);
+            let projected = project_demand(series, start, horizon as usize, method, alpha);
+            let stock_count = book.inventory_count as i64 + book.on_shelf_count as i64;
+
+            let mut cumulative = 0.0;
+            let mut stockout_date = None;
+            for (day, demand) in projected.iter().enumerate() {
+                cumulative += demand;
+                if stockout_date.is_none() && cumulative > stock_count as f64 {
+                    stockout_date = Some(today + Duration::days(day as i64));
+                }
+            }
+
+            Forecast {
+                isbn: book.isbn,
+                title: book.title,
+                method,
+                stock_count,
+                average_daily_demand: series.iter().sum::<f64>() / series.len() as f64,
+                projected_demand: cumulative,
+                stockout_date,
+                suggested_order_quantity: (cumulative.ceil() as i64 - stock_count).max(0),
+                daily: if with_daily {
+                    projected
+                        .into_iter()
+                        .enumerate()
+                        .map(|(day, demand)| ForecastPoint {
+                            date: today + Duration::days(day as i64),
+                            demand,
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                },
+            }
+        })
+        .collect())
+}
+
+#[p(
+    params(("isbn" = String, Path, description = "ISBN"), ForecastOption),
+    responses(
+        (status = OK, description = "Forecast successful", body = Forecast),
+        (status = NOT_FOUND, description = "Book not found", body = GeneralResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid option", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/forecast/{isbn}")]
+pub async fn get_forecast(
+    isbn: Path<String>,
+    param: Query<ForecastOption>,
+    db: Data<DatabaseConnection>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<Forecast>> {
+    let book = book::Entity::find_by_id(isbn.into_inner())
+        .one(db.get_ref())
+        .await?
+        .ok_or_else(|| not_found("Book not found"))?;
+    let forecast = forecast_books(db.get_ref(), vec![book], &param, true)
+        .await?
+        .pop()
+        .expect("one forecast per book");
+    Ok(AJson(forecast))
+}
+
+#[p(
+    params(BulkForecastOption),
+    responses(
+        (status = OK, description = "Forecast successful", body = [Forecast]),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid option", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/forecast")]
+pub async fn get_forecasts(
+    param: Query<BulkForecastOption>,
+    db: Data<DatabaseConnection>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<Vec<Forecast>>> {
+    let isbns = param.isbns.as_ref().map(|isbns| {
+        isbns
+            .split(',')
+            .map(str::trim)
+            .filter(|isbn| !isbn.is_empty())
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    });
+    if isbns
+        .as_ref()
+        .map_or(false, |isbns| isbns.len() > MAX_ISBNS)
+    {
+        return Err(
+            unprocessable_entity(format!("At most {} ISBNs are allowed", MAX_ISBNS)).into(),
+        );
+    }
+    let books = book::Entity::find()
+        .apply_if(isbns, |q, isbns| q.filter(book::Column::Isbn.is_in(isbns)))
+        .all(db.get_ref())
+        .await?;
+
+    let mut forecasts = forecast_books(db.get_ref(), books, &param.option, false).await?;
+    if param.isbns.is_none() {
+        forecasts
+            .retain(|forecast| forecast.stock_count > 0 || forecast.average_daily_demand > 0.0);
+    }
+    // 最先售罄的排在前面，不会售罄的排在最后
+    forecasts.sort_by(|a, b| match (a.stockout_date, b.stockout_date) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.isbn.cmp(&b.isbn),
+    });
+    Ok(AJson(forecasts))
+}
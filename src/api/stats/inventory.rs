@@ -0,0 +1,172 @@
+// 库存估值。
+
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::utils::api_key::{Credential, OrApiKey};
+use crate::utils::jwt::AllowAdmin;
+use crate::utils::permission::APermission;
+
+use crate::api::preclude::*;
+
+use actix_web::web::Data;
+use actix_web::{get, web::Query};
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use entity::{book, order_list, TicketStatus, TicketType};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::cost::{replay, CostMethod};
+
+#[derive(Deserialize, IntoParams)]
+pub struct InventoryValueOption {
+    /// 估值的时间点，RFC 3339 格式，默认为当前时间。库存数量根据此后的订单记录倒推得出
+    pub as_of: Option<DateTime<FixedOffset>>,
+    /// 成本计价方法，默认使用服务器配置
+    pub method: Option<CostMethod>,
+}
+
+#[derive(Serialize, ToSchema, Default, Clone, Copy)]
+pub struct InventoryValue {
+    /// 库存与架上数量之和
+    pub count: i64,
+    /// 按成本计算的价值
+    pub cost_value: f64,
+    /// 按建议售价计算的价值
+    pub retail_value: f64,
+}
+
+impl InventoryValue {
+    fn add(&mut self, other: &InventoryValue) {
+        self.count += other.count;
+        self.cost_value += other.cost_value;
+        self.retail_value += other.retail_value;
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BookInventoryValue {
+    pub isbn: String,
+    pub title: String,
+    pub publisher: String,
+    /// 单位成本
+    pub unit_cost: f64,
+    /// 建议售价。历史售价没有记录，总是使用当前售价
+    pub out_price: f32,
+    #[serde(flatten)]
+    pub value: InventoryValue,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PublisherInventoryValue {
+    pub publisher: String,
+    #[serde(flatten)]
+    pub value: InventoryValue,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct InventoryValuation {
+    /// 估值的时间点（UTC）
+    pub as_of: NaiveDateTime,
+    pub method: CostMethod,
+    pub total: InventoryValue,
+    /// 只包含有库存的书籍，按 ISBN 排序
+    pub books: Vec<BookInventoryValue>,
+    /// 按出版社名称排序
+    pub publishers: Vec<PublisherInventoryValue>,
+}
+
+#[p(
+    params(InventoryValueOption),
+    responses(
+        (status = OK, description = "Stat successful", body = InventoryValuation),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/inventory_value")]
+pub async fn stat_inventory_value(
+    param: Query<InventoryValueOption>,
+    db: Data<DatabaseConnection>,
+    config: Data<Config>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<InventoryValuation>> {
+    let method = param.method.unwrap_or(config.stats.cost_method);
+    let as_of = param.as_of.map(|as_of| as_of.naive_utc());
+
+    let mut counts: BTreeMap<String, i64> = BTreeMap::new();
+    let books = book::Entity::find().all(db.get_ref()).await?;
+    for book in &books {
+        counts.insert(
+            book.isbn.clone(),
+            book.inventory_count as i64 + book.on_shelf_count as i64,
+        );
+    }
+
+    // 撤销 `as_of` 之后完成的订单对库存的影响。上架与下架只在两个数量之间转移，不影响总数
+    if let Some(as_of) = as_of {
+        let changes = order_list::Entity::find()
+            .filter(order_list::Column::Status.eq(TicketStatus::Done))
+            .filter(order_list::Column::UpdatedAt.gte(as_of))
+            .select_only()
+            .column(order_list::Column::BookIsbn)
+            .column(order_list::Column::Typ)
+            .column(order_list::Column::TotalCount)
+            .into_tuple::<(String, TicketType, i32)>()
+            .all(db.get_ref())
+            .await?;
+        for (isbn, typ, count) in changes {
+            let entry = counts.entry(isbn).or_default();
+            match typ {
+                TicketType::Stock => *entry -= count as i64,
+                TicketType::Sell => *entry += count as i64,
+                _ => {}
+            }
+        }
+    }
+
+    let ledgers = replay(db.get_ref(), method, as_of, None).await?.ledgers;
+
+    let mut total = InventoryValue::default();
+    let mut by_publisher: BTreeMap<String, InventoryValue> = BTreeMap::new();
+    let mut values = Vec::new();
+    for book in books {
+        let count = counts.get(&book.isbn).copied().unwrap_or(0);
+        if count <= 0 {
+            continue;
+        }
+        let unit_cost = ledgers
+            .get(&book.isbn)
+            .map_or(0.0, |ledger| ledger.unit_cost());
+        let value = InventoryValue {
+            count,
+            cost_value: count as f64 * unit_cost,
+            retail_value: count as f64 * book.out_price as f64,
+        };
+        total.add(&value);
+        by_publisher
+            .entry(book.publisher.clone())
+            .or_default()
+            .add(&value);
+        values.push(BookInventoryValue {
+            isbn: book.isbn,
+            title: book.title,
+            publisher: book.publisher,
+            unit_cost,
+            out_price: book.out_price,
+            value,
+        });
+    }
+    values.sort_by(|a, b| a.isbn.cmp(&b.isbn));
+
+    Ok(AJson(InventoryValuation {
+        as_of: as_of.unwrap_or_else(|| chrono::Utc::now().naive_utc()),
+        method,
+        total,
+        books: values,
+        publishers: by_publisher
+            .into_iter()
+            .map(|(publisher, value)| PublisherInventoryValue { publisher, value })
+            .collect(),
+    }))
+}
@@ -0,0 +1,265 @@
+// 员工绩效指标。
+
+use std::collections::BTreeMap;
+
+use crate::api::auth::find_user_by_id;
+use crate::contants::user_type;
+use crate::utils::errors::{forbidden, not_found};
+use crate::utils::jwt::{AllowAdmin, AllowSuperAdmin, JwtClaims};
+use crate::utils::permission::APermission;
+
+use crate::api::preclude::*;
+
+use actix_web::web::Data;
+use actix_web::{
+    get,
+    web::{Path, Query},
+};
+use chrono::Utc;
+use entity::{order_list, user, TicketStatus, TicketType};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, QueryFilter, QuerySelect,
+    QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{as_double, as_sint, StatPeriod, StatRange};
+
+#[derive(Deserialize, IntoParams)]
+pub struct KpiOption {
+    #[serde(flatten)]
+    pub period: StatPeriod,
+}
+
+#[serde_as]
+#[derive(Deserialize, IntoParams)]
+pub struct KpiRankOption {
+    #[serde(flatten)]
+    pub period: StatPeriod,
+    /// 排名依据，默认为营业额
+    pub sort_by: Option<KpiMetric>,
+    /// 最多返回的人数，默认返回全部
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum KpiMetric {
+    SellTicketsCompleted,
+    Revenue,
+    AverageTicketSize,
+    RevokeRate,
+    StockOrdersConfirmed,
+}
+
+/// 单个员工在统计周期内的绩效指标。
+#[derive(Serialize, ToSchema, Default)]
+pub struct Kpi {
+    pub operator_id: i32,
+    pub real_name: String,
+    /// 已完成的售书订单数
+    pub sell_tickets_completed: i64,
+    /// 已完成的售书订单中售出的册数
+    pub copies_sold: i64,
+    /// 已完成的售书订单的总金额
+    pub revenue: f64,
+    /// 平均每张已完成售书订单的金额
+    pub average_ticket_size: f64,
+    /// 被撤销的售书订单数
+    pub sell_tickets_revoked: i64,
+    /// 撤销的售书订单占全部售书订单的比例
+    pub revoke_rate: f64,
+    /// 已确认入库的进货订单数
+    pub stock_orders_confirmed: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RankedKpi {
+    pub rank: usize,
+    #[serde(flatten)]
+    pub kpi: Kpi,
+}
+
+#[derive(FromQueryResult)]
+struct OperatorTicketStat {
+    operator_id: i32,
+    typ: TicketType,
+    status: TicketStatus,
+    ticket_count: i64,
+    total_price: Option<f64>,
+    total_count: Option<i64>,
+}
+
+/// 按员工汇总订单，计算统计周期内的绩效指标。
+///
+/// `operator` 为空时计算所有员工的指标，结果按员工编号排序。
+/// 与其他报表一致，订单按最后一次变更（完成、入库或撤销）的时间归入统计周期。
+async fn compute_kpis(
+    db: &DatabaseConnection,
+    range: &StatRange,
+    operator: Option<i32>,
+) -> AResult<BTreeMap<i32, Kpi>> {
+    let rows = range
+        .with_constraint(order_list::Entity::find(), order_list::Column::UpdatedAt)
+        .apply_if(operator, |q, v| {
+            q.filter(order_list::Column::OperatorId.eq(v))
+        })
+        .select_only()
+        .column(order_list::Column::OperatorId)
+        .column(order_list::Column::Typ)
+        .column(order_list::Column::Status)
+        .column_as(order_list::Column::Id.count(), "ticket_count")
+        .column_as(
+            as_double(db, order_list::Column::TotalPrice.sum()),
+            "total_price",
+        )
+        .column_as(
+            as_sint(db, order_list::Column::TotalCount.sum()),
+            "total_count",
+        )
+        .group_by(order_list::Column::OperatorId)
+        .group_by(order_list::Column::Typ)
+        .group_by(order_list::Column::Status)
+        .into_model::<OperatorTicketStat>()
+        .all(db)
+        .await?;
+
+    let mut kpis: BTreeMap<i32, Kpi> = BTreeMap::new();
+    let mut sell_tickets: BTreeMap<i32, i64> = BTreeMap::new();
+    for row in rows {
+        let kpi = kpis.entry(row.operator_id).or_insert_with(|| Kpi {
+            operator_id: row.operator_id,
+            ..Default::default()
+        });
+        match row.typ {
+            TicketType::Sell => {
+                *sell_tickets.entry(row.operator_id).or_default() += row.ticket_count;
+                match row.status {
+                    TicketStatus::Done => {
+                        kpi.sell_tickets_completed += row.ticket_count;
+                        kpi.copies_sold += row.total_count.unwrap_or(0);
+                        kpi.revenue += row.total_price.unwrap_or(0.0);
+                    }
+                    TicketStatus::Revoked => kpi.sell_tickets_revoked += row.ticket_count,
+                    _ => {}
+                }
+            }
+            TicketType::Stock => {
+                if row.status == TicketStatus::Done {
+                    kpi.stock_orders_confirmed += row.ticket_count;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (operator_id, kpi) in kpis.iter_mut() {
+        if kpi.sell_tickets_completed > 0 {
+            kpi.average_ticket_size = kpi.revenue / kpi.sell_tickets_completed as f64;
+        }
+        let total = sell_tickets.get(operator_id).copied().unwrap_or(0);
+        if total > 0 {
+            kpi.revoke_rate = kpi.sell_tickets_revoked as f64 / total as f64;
+        }
+    }
+    Ok(kpis)
+}
+
+#[p(
+    params(KpiOption),
+    responses(
+        (status = OK, description = "Get KPI successful", body = Kpi),
+        (status = NOT_FOUND, description = "User not found", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []))
+)]
+#[get("/user/{id}/kpi")]
+pub async fn get_user_kpi(
+    id: Path<i32>,
+    param: Query<KpiOption>,
+    auth: APermission<JwtClaims, AllowAdmin>,
+    db: Data<DatabaseConnection>,
+) -> AResult<AJson<Kpi>> {
+    let id = id.into_inner();
+    // 只有自己或超级管理员才能查看绩效
+    if auth.auth_info.role != user_type::SUPER_ADMIN && auth.auth_info.id != id {
+        return Err(forbidden("Permission denied").into());
+    }
+    let user = find_user_by_id(id)
+        .one(db.get_ref())
+        .await?
+        .ok_or_else(|| not_found("User not found"))?;
+
+    let (range, _) = param.period.resolve(Utc::now())?;
+    let kpi = compute_kpis(db.get_ref(), &range, Some(id))
+        .await?
+        .remove(&id)
+        .unwrap_or_else(|| Kpi {
+            operator_id: id,
+            ..Default::default()
+        });
+    Ok(AJson(Kpi {
+        real_name: user.real_name,
+        ..kpi
+    }))
+}
+
+#[p(
+    params(KpiRankOption),
+    responses(
+        (status = OK, description = "Get KPI ranking successful", body = [RankedKpi]),
+    ),
+    security(("jwt_token" = []))
+)]
+#[get("/stats/kpi")]
+pub async fn stat_kpi_ranking(
+    param: Query<KpiRankOption>,
+    _auth: APermission<JwtClaims, AllowSuperAdmin>,
+    db: Data<DatabaseConnection>,
+) -> AResult<AJson<Vec<RankedKpi>>> {
+    let (range, _) = param.period.resolve(Utc::now())?;
+    let mut kpis = compute_kpis(db.get_ref(), &range, None).await?;
+
+    // 没有订单的在职员工也参与排名；已删除的员工只在有订单时出现
+    let users = user::Entity::find().all(db.get_ref()).await?;
+    for user in users {
+        if user.is_deleted && !kpis.contains_key(&user.id) {
+            continue;
+        }
+        kpis.entry(user.id)
+            .or_insert_with(|| Kpi {
+                operator_id: user.id,
+                ..Default::default()
+            })
+            .real_name = user.real_name;
+    }
+
+    let metric = param.sort_by.unwrap_or(KpiMetric::Revenue);
+    let mut kpis: Vec<Kpi> = kpis.into_values().collect();
+    kpis.sort_by(|a, b| {
+        let key = |kpi: &Kpi| match metric {
+            KpiMetric::SellTicketsCompleted => kpi.sell_tickets_completed as f64,
+            KpiMetric::Revenue => kpi.revenue,
+            KpiMetric::AverageTicketSize => kpi.average_ticket_size,
+            // 撤销率越低越好
+            KpiMetric::RevokeRate => -kpi.revoke_rate,
+            KpiMetric::StockOrdersConfirmed => kpi.stock_orders_confirmed as f64,
+        };
+        key(b).total_cmp(&key(a))
+    });
+
+    Ok(AJson(
+        kpis.into_iter()
+            .take(param.limit.unwrap_or(usize::MAX))
+            .enumerate()
+            .map(|(index, kpi)| RankedKpi {
+                rank: index + 1,
+                kpi,
+            })
+            .collect(),
+    ))
+}
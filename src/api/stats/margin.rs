@@ -0,0 +1,180 @@
+// 毛利统计。
+
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::utils::api_key::{Credential, OrApiKey};
+use crate::utils::jwt::AllowAdmin;
+use crate::utils::permission::APermission;
+
+use crate::api::preclude::*;
+
+use actix_web::web::Data;
+use actix_web::{get, web::Query};
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use entity::book;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use utoipa::{IntoParams, ToSchema};
+
+use super::cost::{sold_costs_in, CostMethod};
+use super::timeseries::TimeBucket;
+use super::{should_filter_user, StatPeriod};
+
+#[serde_as]
+#[derive(Deserialize, IntoParams)]
+pub struct MarginOption {
+    #[serde(flatten)]
+    pub period: StatPeriod,
+    /// 成本计价方法，默认使用服务器配置
+    pub method: Option<CostMethod>,
+    /// 按时间段分组的粒度，不指定时不按时间段分组
+    pub bucket: Option<TimeBucket>,
+    /// 超级管理员可以查看所有人的数据
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub all: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema, Default, Clone, Copy)]
+pub struct Margin {
+    pub revenue: f64,
+    /// 销售成本
+    pub cogs: f64,
+    pub gross_profit: f64,
+    /// 毛利率（百分比），营业额为 0 时为空
+    pub margin_percent: Option<f64>,
+}
+
+impl Margin {
+    fn add(&mut self, revenue: f64, cogs: f64) {
+        self.revenue += revenue;
+        self.cogs += cogs;
+        self.gross_profit = self.revenue - self.cogs;
+        self.margin_percent =
+            (self.revenue != 0.0).then_some(self.gross_profit / self.revenue * 100.0);
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BookMargin {
+    pub isbn: String,
+    pub title: String,
+    pub publisher: String,
+    /// 售出册数
+    pub count: i64,
+    #[serde(flatten)]
+    pub margin: Margin,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PublisherMargin {
+    pub publisher: String,
+    #[serde(flatten)]
+    pub margin: Margin,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PeriodMargin {
+    /// 时间段的起始时间
+    pub start: DateTime<FixedOffset>,
+    #[serde(flatten)]
+    pub margin: Margin,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MarginReport {
+    pub method: CostMethod,
+    pub overall: Margin,
+    /// 按毛利从高到低排序
+    pub books: Vec<BookMargin>,
+    /// 按毛利从高到低排序
+    pub publishers: Vec<PublisherMargin>,
+    /// 按时间排序，只包含有销售的时间段
+    pub periods: Vec<PeriodMargin>,
+}
+
+#[p(
+    params(MarginOption),
+    responses(
+        (status = OK, description = "Stat successful", body = MarginReport),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/margin")]
+pub async fn stat_margin(
+    param: Query<MarginOption>,
+    db: Data<DatabaseConnection>,
+    config: Data<Config>,
+    auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<MarginReport>> {
+    let (range, _) = param.period.resolve(Utc::now())?;
+    let method = param.method.unwrap_or(config.stats.cost_method);
+    let operator = should_filter_user(param.all, &auth.auth_info).then_some(auth.auth_info.id);
+    let tz = param.period.tz.unwrap_or_default().0;
+
+    let sold = sold_costs_in(db.get_ref(), method, &range, operator).await?;
+
+    let mut overall = Margin::default();
+    let mut by_book: BTreeMap<String, (i64, Margin)> = BTreeMap::new();
+    let mut by_period: BTreeMap<DateTime<FixedOffset>, Margin> = BTreeMap::new();
+    for sold in &sold {
+        overall.add(sold.revenue, sold.cogs);
+        let (count, margin) = by_book.entry(sold.isbn.clone()).or_default();
+        *count += sold.count as i64;
+        margin.add(sold.revenue, sold.cogs);
+        if let Some(bucket) = param.bucket {
+            by_period
+                .entry(bucket.floor(tz.from_utc_datetime(&sold.sold_at)))
+                .or_default()
+                .add(sold.revenue, sold.cogs);
+        }
+    }
+
+    let books: BTreeMap<String, book::Model> = book::Entity::find()
+        .filter(book::Column::Isbn.is_in(by_book.keys().cloned()))
+        .all(db.get_ref())
+        .await?
+        .into_iter()
+        .map(|book| (book.isbn.clone(), book))
+        .collect();
+
+    let mut by_publisher: BTreeMap<String, Margin> = BTreeMap::new();
+    let mut book_margins = Vec::with_capacity(by_book.len());
+    for (isbn, (count, margin)) in by_book {
+        let (title, publisher) = books
+            .get(&isbn)
+            .map(|book| (book.title.clone(), book.publisher.clone()))
+            .unwrap_or_default();
+        by_publisher
+            .entry(publisher.clone())
+            .or_default()
+            .add(margin.revenue, margin.cogs);
+        book_margins.push(BookMargin {
+            isbn,
+            title,
+            publisher,
+            count,
+            margin,
+        });
+    }
+    book_margins.sort_by(|a, b| b.margin.gross_profit.total_cmp(&a.margin.gross_profit));
+
+    let mut publishers: Vec<_> = by_publisher
+        .into_iter()
+        .map(|(publisher, margin)| PublisherMargin { publisher, margin })
+        .collect();
+    publishers.sort_by(|a, b| b.margin.gross_profit.total_cmp(&a.margin.gross_profit));
+
+    Ok(AJson(MarginReport {
+        method,
+        overall,
+        books: book_margins,
+        publishers,
+        periods: by_period
+            .into_iter()
+            .map(|(start, margin)| PeriodMargin { start, margin })
+            .collect(),
+    }))
+}
@@ -0,0 +1,645 @@
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+
+use crate::contants;
+use crate::utils::errors::{internal_server_error, unprocessable_entity};
+
+use crate::utils::api_key::{Credential, OrApiKey};
+use crate::utils::jwt::AllowAdmin;
+use crate::utils::permission::APermission;
+
+use super::preclude::*;
+
+use actix_web::web::Data;
+
+use actix_web::{get, web::Query};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Months, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Utc,
+};
+
+use entity::TicketStatus;
+use entity::TicketType;
+
+use sea_orm::sea_query::types::Alias;
+use sea_orm::sea_query::SimpleExpr;
+
+use sea_orm::ConnectionTrait;
+use sea_orm::DbBackend;
+use sea_orm::QuerySelect;
+use sea_orm::QueryTrait;
+use sea_orm::Select;
+
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::Deserialize;
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+pub mod bestsell;
+pub mod cost;
+pub mod daily_close;
+pub mod forecast;
+pub mod inventory;
+pub mod kpi;
+pub mod margin;
+pub mod slow_movers;
+pub mod timeseries;
+
+const TOTAL_PRICE: &str = "tp";
+const TOTAL_COUNT: &str = "tc";
+
+/// 将 `SUM` 或 `COUNT` 的结果转换为 64 位整数。各数据库的类型名不同
+fn as_sint(db: &impl ConnectionTrait, expr: SimpleExpr) -> SimpleExpr {
+    expr.cast_as(Alias::new(match db.get_database_backend() {
+        DbBackend::MySql => "signed integer",
+        DbBackend::Postgres => "bigint",
+        DbBackend::Sqlite => "integer",
+    }))
+}
+
+/// 将金额的 `SUM` 结果转换为双精度浮点数。MySQL 与 SQLite 的结果本来就是双精度
+fn as_double(db: &impl ConnectionTrait, expr: SimpleExpr) -> SimpleExpr {
+    match db.get_database_backend() {
+        DbBackend::Postgres => expr.cast_as(Alias::new("double precision")),
+        _ => expr,
+    }
+}
+
+#[serde_as]
+#[derive(Deserialize, IntoParams)]
+pub struct StatOption {
+    #[serde(flatten)]
+    pub period: StatPeriod,
+    /// 超级管理员可以查看所有人的数据
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub all: Option<bool>,
+    /// 同时返回上一个等长周期的数据，以及两者的差值
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub compare: Option<bool>,
+}
+
+/// 只有超级管理员在 `all=true` 时才能查看所有人的数据，否则只统计自己经手的订单。
+pub fn should_filter_user(all: Option<bool>, user: &entity::user::Model) -> bool {
+    !all.unwrap_or(false) || user.role != contants::user_type::SUPER_ADMIN
+}
+
+impl StatOption {
+    pub fn should_filter_user(&self, user: &entity::user::Model) -> bool {
+        should_filter_user(self.all, user)
+    }
+
+    /// 需要过滤的操作员编号。
+    pub fn operator(&self, user: &entity::user::Model) -> Option<i32> {
+        self.should_filter_user(user).then_some(user.id)
+    }
+
+    /// 解析统计周期。需要对比时，同时返回上一个等长周期。
+    pub fn ranges(&self) -> AResult<(StatRange, Option<StatRange>)> {
+        let (current, previous) = self.period.resolve(Utc::now())?;
+        if !self.compare.unwrap_or(false) {
+            return Ok((current, None));
+        }
+        let previous = previous.ok_or_else(|| {
+            unprocessable_entity("Comparison requires a bounded period, use a span or `from`")
+        })?;
+        Ok((current, Some(previous)))
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum StatSpan {
+    /// 最近 24 小时
+    Day,
+    /// 最近 7 天
+    Week,
+    /// 最近 30 天
+    Month,
+    All,
+    /// 本周（以周一为一周的开始）至今
+    ThisWeek,
+    /// 本月至今
+    ThisMonth,
+    /// 本季度至今
+    ThisQuarter,
+    /// 本年至今
+    ThisYear,
+}
+
+/// 以 `+08:00`、`-05:30` 或 `Z` 表示的固定时区偏移。
+#[derive(Deserialize, Clone, Copy)]
+#[serde(try_from = "String")]
+pub struct TzOffset(pub FixedOffset);
+
+impl Default for TzOffset {
+    fn default() -> Self {
+        TzOffset(FixedOffset::east_opt(0).unwrap())
+    }
+}
+
+impl TryFrom<String> for TzOffset {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid time zone offset {}, expect +HH:MM", value);
+        if value == "Z" || value.eq_ignore_ascii_case("utc") {
+            return Ok(TzOffset::default());
+        }
+        let (sign, rest) = if let Some(rest) = value.strip_prefix('+') {
+            (1, rest)
+        } else if let Some(rest) = value.strip_prefix('-') {
+            (-1, rest)
+        } else {
+            return Err(invalid());
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if !(0..24).contains(&hours) || !(0..60).contains(&minutes) {
+            return Err(invalid());
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(TzOffset)
+            .ok_or_else(invalid)
+    }
+}
+
+/// 统计周期。可以使用 `span`，也可以使用 `from`/`to` 指定任意时间段，两者不能同时使用。
+///
+/// 都不指定时统计全部数据。
+#[derive(Deserialize, IntoParams)]
+pub struct StatPeriod {
+    pub span: Option<StatSpan>,
+    /// 起始时间（包含），RFC 3339 格式
+    pub from: Option<DateTime<FixedOffset>>,
+    /// 结束时间（不包含），RFC 3339 格式，默认为当前时间
+    pub to: Option<DateTime<FixedOffset>>,
+    /// 计算本周、本月等自然周期所用的时区，例如 `+08:00`（在 URL 中需写作 `%2B08:00`），默认为 UTC
+    #[param(value_type = Option<String>)]
+    pub tz: Option<TzOffset>,
+}
+
+impl StatPeriod {
+    /// 解析出当前周期，以及用于对比的上一个等长周期。
+    ///
+    /// 对于本周、本月等自然周期，上一个周期是上一个自然周期中相同长度的部分，例如 6 月 1 日至 6 月 15 日对应 5 月 1 日至 5 月 15 日。
+    pub fn resolve(&self, now: DateTime<Utc>) -> AResult<(StatRange, Option<StatRange>)> {
+        let now = now.naive_utc();
+        if self.from.is_some() || self.to.is_some() {
+            if self.span.is_some() {
+                return Err(unprocessable_entity("`span` cannot be used with `from`/`to`").into());
+            }
+            let from = self.from.map(|from| from.naive_utc());
+            let to = self.to.map_or(now, |to| to.naive_utc());
+            if from.map_or(false, |from| from >= to) {
+                return Err(unprocessable_entity("`from` must be earlier than `to`").into());
+            }
+            let previous = from.map(|from| StatRange::new(from - (to - from), from));
+            return Ok((
+                StatRange {
+                    from,
+                    to: self.to.map(|to| to.naive_utc()),
+                },
+                previous,
+            ));
+        }
+
+        let rolling = |days| {
+            let from = now - Duration::days(days);
+            Ok((
+                StatRange::since(from),
+                Some(StatRange::new(from - Duration::days(days), from)),
+            ))
+        };
+        let tz = self.tz.unwrap_or_default().0;
+        let local = tz.from_utc_datetime(&now).date_naive();
+        let (start, months) = match self.span {
+            Some(StatSpan::Day) => return rolling(1),
+            Some(StatSpan::Week) => return rolling(7),
+            Some(StatSpan::Month) => return rolling(30),
+            Some(StatSpan::All) | None => return Ok((StatRange::default(), None)),
+            Some(StatSpan::ThisWeek) => {
+                let start = local - Duration::days(local.weekday().num_days_from_monday() as i64);
+                let start = local_to_utc(tz, start.and_time(NaiveTime::MIN));
+                let week = Duration::weeks(1);
+                return Ok((
+                    StatRange::since(start),
+                    Some(StatRange::new(start - week, now - week)),
+                ));
+            }
+            Some(StatSpan::ThisMonth) => (local.with_day(1), 1),
+            Some(StatSpan::ThisQuarter) => (
+                NaiveDate::from_ymd_opt(local.year(), (local.month0() / 3 * 3) + 1, 1),
+                3,
+            ),
+            Some(StatSpan::ThisYear) => (NaiveDate::from_ymd_opt(local.year(), 1, 1), 12),
+        };
+        let start = start.expect("valid period start").and_time(NaiveTime::MIN);
+        // 上一个周期按本地日历回退，而不是回退 UTC 时间，否则月初的边界会错位
+        let months = Months::new(months);
+        let local_now = tz.from_utc_datetime(&now).naive_local();
+        let previous = start
+            .checked_sub_months(months)
+            .zip(local_now.checked_sub_months(months))
+            .map(|(from, to)| StatRange::new(local_to_utc(tz, from), local_to_utc(tz, to)));
+        Ok((StatRange::since(local_to_utc(tz, start)), previous))
+    }
+}
+
+/// 把某个时区中的本地时间转换为 UTC 时间。
+fn local_to_utc(tz: FixedOffset, local: NaiveDateTime) -> NaiveDateTime {
+    local - Duration::seconds(tz.local_minus_utc() as i64)
+}
+
+/// 解析后的统计时间段 `[from, to)`，均为 UTC 时间，为空表示不限制。
+#[derive(Default, Clone, Copy)]
+pub struct StatRange {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+impl StatRange {
+    pub fn new(from: NaiveDateTime, to: NaiveDateTime) -> Self {
+        Self {
+            from: Some(from),
+            to: Some(to),
+        }
+    }
+
+    pub fn since(from: NaiveDateTime) -> Self {
+        Self {
+            from: Some(from),
+            to: None,
+        }
+    }
+
+    pub fn with_constraint<E: EntityTrait>(
+        &self,
+        query: Select<E>,
+        column: E::Column,
+    ) -> Select<E> {
+        query
+            .apply_if(self.from, |q, from| q.filter(column.gte(from)))
+            .apply_if(self.to, |q, to| q.filter(column.lt(to)))
+    }
+}
+
+/// 某个数值字段相对于上一周期的变化。
+#[derive(Serialize, ToSchema)]
+pub struct StatDelta {
+    pub absolute: f64,
+    /// 变化的百分比。上一周期为 0 时为空
+    pub percentage: Option<f64>,
+}
+
+/// 当前周期与上一周期的对比。
+#[derive(Serialize, ToSchema)]
+pub struct StatComparison {
+    #[schema(value_type = Object)]
+    pub current: serde_json::Value,
+    #[schema(value_type = Object)]
+    pub previous: serde_json::Value,
+    /// 各数值字段的变化
+    pub delta: BTreeMap<String, StatDelta>,
+}
+
+/// 统计结果。请求对比时返回 [`StatComparison`]，否则直接返回统计数据。
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum StatResult<T> {
+    Single(T),
+    Compared(StatComparison),
+}
+
+impl<T: Serialize> StatResult<T> {
+    pub fn new(current: T, previous: Option<T>) -> AResult<Self> {
+        let previous = match previous {
+            Some(previous) => previous,
+            None => return Ok(StatResult::Single(current)),
+        };
+        let current = serde_json::to_value(current).map_err(internal_server_error)?;
+        let previous = serde_json::to_value(previous).map_err(internal_server_error)?;
+        let mut delta = BTreeMap::new();
+        if let (Some(current), Some(previous)) = (current.as_object(), previous.as_object()) {
+            for (key, value) in current {
+                if let (Some(now), Some(before)) =
+                    (value.as_f64(), previous.get(key).and_then(|v| v.as_f64()))
+                {
+                    let absolute = now - before;
+                    delta.insert(
+                        key.clone(),
+                        StatDelta {
+                            absolute,
+                            percentage: (before != 0.0).then(|| absolute / before.abs() * 100.0),
+                        },
+                    );
+                }
+            }
+        }
+        Ok(StatResult::Compared(StatComparison {
+            current,
+            previous,
+            delta,
+        }))
+    }
+}
+
+macro_rules! fn_select_ones {
+    ($fn_name: ident; $($T: ident),*) => {
+        async fn $fn_name<$($T: sea_orm::TryGetable),+>(
+            db: impl Borrow<DatabaseConnection>,
+            query: impl QueryTrait + QueryFilter,
+            col_name: &[&str],
+        ) -> AResult<($($T,)+)> {
+            let count_result = db
+                .borrow()
+                .query_one(
+                    query.build(db.borrow().get_database_backend()),
+                )
+                .await?
+                .ok_or_else(|| internal_server_error("Unable to get count"))?;
+            Ok(
+                count_macro::count! {
+                    ($(
+                        count_result.try_get::<$T>("", col_name[_int_])?,
+                    )+)
+                }
+            )
+        }
+    };
+}
+
+fn_select_ones!(select_one; T1);
+
+#[derive(Serialize, ToSchema)]
+pub struct StatTransaction {
+    pub total_sell_price: f32,
+    pub total_stock_paid_price: f32,
+}
+
+async fn stat_transaction_query(
+    db: impl Borrow<DatabaseConnection>,
+    typ: TicketType,
+    base_query: impl QueryTrait + QueryFilter,
+    col_name: &str,
+) -> AResult<f32> {
+    Ok(select_one::<Option<f64>>(
+        db,
+        base_query.filter(entity::order_list::Column::Typ.eq(typ)),
+        &[col_name],
+    )
+    .await?
+    .0
+    .unwrap_or(0.0) as f32)
+}
+
+async fn stat_transaction_in(
+    db: &DatabaseConnection,
+    range: &StatRange,
+    operator: Option<i32>,
+) -> AResult<StatTransaction> {
+    let query = range
+        .with_constraint(
+            entity::transaction::Entity::find(),
+            entity::transaction::Column::CreatedAt,
+        )
+        .find_also_related(entity::order_list::Entity)
+        .select_only()
+        .column_as(
+            as_double(db, entity::order_list::Column::TotalPrice.sum()),
+            TOTAL_PRICE,
+        )
+        .apply_if(operator, |q, v| {
+            q.filter(entity::order_list::Column::OperatorId.eq(v))
+        });
+
+    Ok(StatTransaction {
+        total_sell_price: stat_transaction_query(db, TicketType::Sell, query.clone(), TOTAL_PRICE)
+            .await?,
+        total_stock_paid_price: stat_transaction_query(db, TicketType::Stock, query, TOTAL_PRICE)
+            .await?,
+    })
+}
+
+#[p(
+    params(StatOption),
+    responses(
+        (status = OK, description = "Stat successful. With `compare=true` the body is a `StatComparison`", body = StatTransaction),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/transaction")]
+pub async fn stat_transaction(
+    param: Query<StatOption>,
+    db: Data<DatabaseConnection>,
+    auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<StatResult<StatTransaction>>> {
+    let (current, previous) = param.ranges()?;
+    let operator = param.operator(&auth.auth_info);
+    let previous = match previous {
+        Some(range) => Some(stat_transaction_in(db.get_ref(), &range, operator).await?),
+        None => None,
+    };
+    Ok(AJson(StatResult::new(
+        stat_transaction_in(db.get_ref(), &current, operator).await?,
+        previous,
+    )?))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StatStock {
+    pub total_stock_count: i32,
+    pub total_waiting_for_confirm_count: i32,
+}
+
+async fn stat_stock_in(
+    db: &DatabaseConnection,
+    range: &StatRange,
+    operator: Option<i32>,
+) -> AResult<StatStock> {
+    let query = range
+        .with_constraint(
+            entity::order_list::Entity::find(),
+            entity::order_list::Column::CreatedAt,
+        )
+        .apply_if(operator, |q, v| {
+            q.filter(entity::order_list::Column::OperatorId.eq(v))
+        })
+        .filter(entity::order_list::Column::Typ.eq(TicketType::Stock))
+        .select_only();
+
+    Ok(StatStock {
+        total_stock_count: select_one::<Option<i64>>(
+            db,
+            query.clone().column_as(
+                as_sint(db, entity::order_list::Column::TotalCount.sum()),
+                TOTAL_COUNT,
+            ),
+            &[TOTAL_COUNT],
+        )
+        .await?
+        .0
+        .unwrap_or(0) as i32,
+        total_waiting_for_confirm_count: select_one::<Option<i64>>(
+            db,
+            query
+                .filter(entity::order_list::Column::Status.eq(TicketStatus::StockPaid))
+                .column_as(
+                    as_sint(db, entity::order_list::Column::TotalCount.sum()),
+                    TOTAL_COUNT,
+                ),
+            &[TOTAL_COUNT],
+        )
+        .await?
+        .0
+        .unwrap_or(0) as i32,
+    })
+}
+
+#[p(
+    params(StatOption),
+    responses(
+        (status = OK, description = "Stat successful. With `compare=true` the body is a `StatComparison`", body = StatStock),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/stock")]
+pub async fn stat_stock(
+    param: Query<StatOption>,
+    db: Data<DatabaseConnection>,
+    auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<StatResult<StatStock>>> {
+    let (current, previous) = param.ranges()?;
+    let operator = param.operator(&auth.auth_info);
+    let previous = match previous {
+        Some(range) => Some(stat_stock_in(db.get_ref(), &range, operator).await?),
+        None => None,
+    };
+    Ok(AJson(StatResult::new(
+        stat_stock_in(db.get_ref(), &current, operator).await?,
+        previous,
+    )?))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StatSell {
+    pub total_sell_count: i32,
+    pub total_done_count: i32,
+}
+
+async fn stat_sell_in(
+    db: &DatabaseConnection,
+    range: &StatRange,
+    operator: Option<i32>,
+) -> AResult<StatSell> {
+    let query = range
+        .with_constraint(
+            entity::order_list::Entity::find(),
+            entity::order_list::Column::CreatedAt,
+        )
+        .apply_if(operator, |q, v| {
+            q.filter(entity::order_list::Column::OperatorId.eq(v))
+        })
+        .filter(entity::order_list::Column::Typ.eq(TicketType::Sell))
+        .select_only();
+
+    Ok(StatSell {
+        total_sell_count: select_one::<Option<i64>>(
+            db,
+            query.clone().column_as(
+                as_sint(db, entity::order_list::Column::TotalCount.sum()),
+                TOTAL_COUNT,
+            ),
+            &[TOTAL_COUNT],
+        )
+        .await?
+        .0
+        .unwrap_or(0) as i32,
+        total_done_count: select_one::<Option<i64>>(
+            db,
+            query
+                .filter(entity::order_list::Column::Status.eq(TicketStatus::Done))
+                .column_as(
+                    as_sint(db, entity::order_list::Column::TotalCount.sum()),
+                    TOTAL_COUNT,
+                ),
+            &[TOTAL_COUNT],
+        )
+        .await?
+        .0
+        .unwrap_or(0) as i32,
+    })
+}
+
+#[p(
+    params(StatOption),
+    responses(
+        (status = OK, description = "Stat successful. With `compare=true` the body is a `StatComparison`", body = StatSell),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/sell")]
+pub async fn stat_sell(
+    param: Query<StatOption>,
+    db: Data<DatabaseConnection>,
+    auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<StatResult<StatSell>>> {
+    let (current, previous) = param.ranges()?;
+    let operator = param.operator(&auth.auth_info);
+    let previous = match previous {
+        Some(range) => Some(stat_sell_in(db.get_ref(), &range, operator).await?),
+        None => None,
+    };
+    Ok(AJson(StatResult::new(
+        stat_sell_in(db.get_ref(), &current, operator).await?,
+        previous,
+    )?))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StatBook {
+    pub total_inventory_count: i32,
+    pub total_book_count: i32,
+}
+
+#[p(
+    responses(
+        (status = OK, description = "Stat successful", body = StatBook),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/book")]
+pub async fn stat_book(
+    db: Data<DatabaseConnection>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<StatBook>> {
+    let query = entity::book::Entity::find().select_only();
+
+    Ok(AJson(StatBook {
+        total_inventory_count: select_one::<Option<i64>>(
+            db.get_ref(),
+            query.clone().column_as(
+                as_sint(db.get_ref(), entity::book::Column::InventoryCount.sum()),
+                TOTAL_COUNT,
+            ),
+            &[TOTAL_COUNT],
+        )
+        .await?
+        .0
+        .unwrap_or(0) as i32,
+        total_book_count: select_one::<i64>(
+            db.get_ref(),
+            query.column_as(
+                as_sint(db.get_ref(), entity::book::Column::Isbn.count()),
+                TOTAL_COUNT,
+            ),
+            &[TOTAL_COUNT],
+        )
+        .await?
+        .0 as i32,
+    }))
+}
@@ -0,0 +1,191 @@
+// 滞销书分析。
+
+use std::collections::BTreeMap;
+
+use crate::api::PagingRequest;
+use crate::config::Config;
+use crate::utils::api_key::{Credential, OrApiKey};
+use crate::utils::errors::unprocessable_entity;
+use crate::utils::ext::SelectExt;
+use crate::utils::jwt::AllowAdmin;
+use crate::utils::permission::APermission;
+
+use crate::api::preclude::*;
+
+use actix_web::web::Data;
+use actix_web::HttpResponse;
+use actix_web::{get, web::Query};
+use chrono::{NaiveDateTime, Utc};
+use entity::{book, order_list, TicketStatus, TicketType};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use utoipa::{IntoParams, ToSchema};
+
+use super::cost::{replay, CostMethod};
+use super::StatPeriod;
+
+#[serde_as]
+#[derive(Deserialize, IntoParams)]
+pub struct SlowMoverOption {
+    /// 统计销量的周期
+    #[serde(flatten)]
+    pub period: StatPeriod,
+    /// 周期内售出少于此数量的书籍视为滞销，至少为 1。默认为 1，即没有任何销售
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub min_sales: Option<i32>,
+    /// 计算积压金额所用的成本计价方法，默认使用服务器配置
+    pub method: Option<CostMethod>,
+    #[serde(flatten)]
+    pub paging: PagingRequest,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SlowMover {
+    pub isbn: String,
+    pub title: String,
+    pub publisher: String,
+    /// 库存与架上数量之和
+    pub stock_count: i64,
+    /// 周期内售出的册数
+    pub sold_count: i64,
+    /// 最近一次售出的时间，从未售出时为空
+    pub last_sold_at: Option<NaiveDateTime>,
+    pub days_since_last_sale: Option<i64>,
+    /// 最近一次确认入库的时间，没有进货记录时为空
+    pub last_stocked_at: Option<NaiveDateTime>,
+    pub stock_age_days: Option<i64>,
+    /// 单位成本
+    pub unit_cost: f64,
+    /// 积压的资金，按成本计算
+    pub tied_up_value: f64,
+}
+
+#[p(
+    params(SlowMoverOption),
+    responses(
+        (status = OK, description = "Stat successful", body = [SlowMover]),
+        (status = BAD_REQUEST, description = "Cursor paging is not supported", body = GeneralResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid min_sales", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/slow_movers")]
+pub async fn stat_slow_movers(
+    param: Query<SlowMoverOption>,
+    db: Data<DatabaseConnection>,
+    config: Data<Config>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<HttpResponse> {
+    let param = param.into_inner();
+    // 按库存排序，不支持游标分页
+    param.paging.reject_cursor()?;
+    let (range, _) = param.period.resolve(Utc::now())?;
+    let min_sales = param.min_sales.unwrap_or(1);
+    // 没有书籍能售出少于 0 本，这样的条件没有意义
+    if min_sales < 1 {
+        return Err(unprocessable_entity("`min_sales` must be at least 1").into());
+    }
+    let method = param.method.unwrap_or(config.stats.cost_method);
+
+    let sells = || {
+        order_list::Entity::find()
+            .filter(order_list::Column::Typ.eq(TicketType::Sell))
+            .filter(order_list::Column::Status.eq(TicketStatus::Done))
+    };
+    // 周期内销量达标的书籍
+    let moving = range
+        .with_constraint(sells(), order_list::Column::UpdatedAt)
+        .select_only()
+        .column(order_list::Column::BookIsbn)
+        .group_by(order_list::Column::BookIsbn)
+        .having(Expr::expr(order_list::Column::TotalCount.sum()).gte(min_sales))
+        .into_query();
+
+    // 库存最多的排在前面
+    let page = book::Entity::find()
+        .filter(
+            Condition::any()
+                .add(book::Column::InventoryCount.gt(0))
+                .add(book::Column::OnShelfCount.gt(0)),
+        )
+        .filter(book::Column::Isbn.not_in_subquery(moving))
+        .order_by_desc(
+            Expr::col(book::Column::InventoryCount).add(Expr::col(book::Column::OnShelfCount)),
+        )
+        .order_by_asc(book::Column::Isbn)
+        .fetch_page::<DatabaseConnection, _>(param.paging, db.get_ref())
+        .await?;
+    let isbns: Vec<String> = page.items.iter().map(|book| book.isbn.clone()).collect();
+
+    let mut sold: BTreeMap<String, i64> = BTreeMap::new();
+    for (isbn, count) in range
+        .with_constraint(sells(), order_list::Column::UpdatedAt)
+        .filter(order_list::Column::BookIsbn.is_in(isbns.clone()))
+        .select_only()
+        .column(order_list::Column::BookIsbn)
+        .column(order_list::Column::TotalCount)
+        .into_tuple::<(String, i32)>()
+        .all(db.get_ref())
+        .await?
+    {
+        *sold.entry(isbn).or_default() += count as i64;
+    }
+
+    let mut last_sold = BTreeMap::new();
+    let mut last_stocked = BTreeMap::new();
+    for (isbn, typ, at) in order_list::Entity::find()
+        .filter(order_list::Column::Status.eq(TicketStatus::Done))
+        .filter(order_list::Column::BookIsbn.is_in(isbns.clone()))
+        .select_only()
+        .column(order_list::Column::BookIsbn)
+        .column(order_list::Column::Typ)
+        .column_as(order_list::Column::UpdatedAt.max(), "last_at")
+        .group_by(order_list::Column::BookIsbn)
+        .group_by(order_list::Column::Typ)
+        .into_tuple::<(String, TicketType, Option<NaiveDateTime>)>()
+        .all(db.get_ref())
+        .await?
+    {
+        match typ {
+            TicketType::Sell => last_sold.insert(isbn, at),
+            TicketType::Stock => last_stocked.insert(isbn, at),
+            _ => None,
+        };
+    }
+
+    let ledgers = replay(db.get_ref(), method, None, Some(isbns))
+        .await?
+        .ledgers;
+    let now = Utc::now().naive_utc();
+    let days_since = |at: Option<NaiveDateTime>| at.map(|at| (now - at).num_days());
+
+    Ok(page
+        .map(|book| {
+            let stock_count = book.inventory_count as i64 + book.on_shelf_count as i64;
+            let unit_cost = ledgers
+                .get(&book.isbn)
+                .map_or(0.0, |ledger| ledger.unit_cost());
+            let last_sold_at = last_sold.get(&book.isbn).copied().flatten();
+            let last_stocked_at = last_stocked.get(&book.isbn).copied().flatten();
+            SlowMover {
+                sold_count: sold.get(&book.isbn).copied().unwrap_or(0),
+                isbn: book.isbn,
+                title: book.title,
+                publisher: book.publisher,
+                stock_count,
+                last_sold_at,
+                days_since_last_sale: days_since(last_sold_at),
+                last_stocked_at,
+                stock_age_days: days_since(last_stocked_at),
+                unit_cost,
+                tied_up_value: stock_count as f64 * unit_cost,
+            }
+        })
+        .into_response())
+}
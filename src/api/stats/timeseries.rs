@@ -0,0 +1,305 @@
+// 按时间分桶的统计数据，用于绘制图表。
+
+use crate::utils::api_key::{Credential, OrApiKey};
+use crate::utils::errors::unprocessable_entity;
+use crate::utils::jwt::AllowAdmin;
+use crate::utils::permission::APermission;
+
+use crate::api::preclude::*;
+
+use actix_web::web::Data;
+use actix_web::{get, web::Query};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Months, TimeZone, Timelike, Utc};
+use entity::{order_list, transaction, TicketType};
+use sea_orm::sea_query::types::Alias;
+use sea_orm::sea_query::{Expr, Func, SimpleExpr};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, QueryFilter,
+    QuerySelect, QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{as_double, as_sint, should_filter_user, TzOffset};
+
+/// 一次请求最多返回的时间桶数量
+const MAX_BUCKETS: usize = 1000;
+
+#[derive(Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeSeriesMetric {
+    /// 售书收入，与 `/stats/transaction` 的 `total_sell_price` 口径一致
+    Revenue,
+    /// 售出册数，与 `/stats/sell` 的 `total_sell_count` 口径一致
+    SellCount,
+    /// 进货支出，与 `/stats/transaction` 的 `total_stock_paid_price` 口径一致
+    StockSpend,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeBucket {
+    Hour,
+    Day,
+    /// 以周一为一周的开始
+    Week,
+    Month,
+}
+
+impl TimeBucket {
+    /// 取 `time` 所在时间桶的起始时间，时区与 `time` 相同。
+    pub fn floor(self, time: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        let date = time.date_naive();
+        let start = match self {
+            TimeBucket::Hour => date.and_hms_opt(time.hour(), 0, 0),
+            TimeBucket::Day => date.and_hms_opt(0, 0, 0),
+            TimeBucket::Week => (date
+                - Duration::days(date.weekday().num_days_from_monday() as i64))
+            .and_hms_opt(0, 0, 0),
+            TimeBucket::Month => date.with_day(1).and_then(|date| date.and_hms_opt(0, 0, 0)),
+        }
+        .expect("valid bucket start");
+        time.timezone()
+            .from_local_datetime(&start)
+            .single()
+            .expect("fixed offset is never ambiguous")
+    }
+
+    /// 固定长度的时间桶的秒数。时区是固定偏移量，因此小时、天与周的长度不变
+    fn seconds(self) -> Option<i64> {
+        match self {
+            TimeBucket::Hour => Some(3600),
+            TimeBucket::Day => Some(86400),
+            TimeBucket::Week => Some(7 * 86400),
+            TimeBucket::Month => None,
+        }
+    }
+
+    /// 时间桶在所有月份中的编号，即 `年 * 12 + 月 - 1`。
+    fn month_index(time: DateTime<FixedOffset>) -> i64 {
+        time.year() as i64 * 12 + time.month0() as i64
+    }
+
+    /// 计算 `at`（UTC 时间）所在时间桶的编号的 SQL 表达式，编号从 `first` 所在的时间桶开始，为 0。
+    ///
+    /// 在数据库中分组，避免把区间内的全部订单读入内存。各数据库的日期函数不同。
+    fn key(
+        self,
+        db: &impl ConnectionTrait,
+        at: SimpleExpr,
+        first: DateTime<FixedOffset>,
+    ) -> SimpleExpr {
+        let backend = db.get_database_backend();
+        let call = |name: &str, args: Vec<SimpleExpr>| -> SimpleExpr {
+            Func::cust(Alias::new(name)).args(args).into()
+        };
+        let integer = |expr: SimpleExpr| expr.cast_as(Alias::new("integer"));
+        let Some(seconds) = self.seconds() else {
+            // 月的长度不固定，按本地时间的年月计算
+            let offset = first.offset().local_minus_utc() as i64;
+            let part = |name: &str, sqlite_format: &str| match backend {
+                DbBackend::MySql => call(
+                    name,
+                    vec![call(
+                        "TIMESTAMPADD",
+                        vec![Expr::cust("SECOND"), offset.into(), at.clone()],
+                    )],
+                ),
+                DbBackend::Postgres => call(
+                    "date_part",
+                    vec![
+                        name.to_lowercase().into(),
+                        at.clone().add(
+                            Expr::val(format!("{} seconds", offset))
+                                .cast_as(Alias::new("interval")),
+                        ),
+                    ],
+                ),
+                DbBackend::Sqlite => integer(call(
+                    "strftime",
+                    vec![
+                        sqlite_format.into(),
+                        at.clone(),
+                        format!("{:+} seconds", offset).into(),
+                    ],
+                )),
+            };
+            let month = part("YEAR", "%Y")
+                .mul(12)
+                .add(part("MONTH", "%m"))
+                .sub(1 + Self::month_index(first));
+            return as_sint(db, month);
+        };
+        let epoch = match backend {
+            DbBackend::MySql => call(
+                "TIMESTAMPDIFF",
+                vec![Expr::cust("SECOND"), "1970-01-01 00:00:00".into(), at],
+            ),
+            DbBackend::Postgres => call("date_part", vec!["epoch".into(), at]),
+            DbBackend::Sqlite => integer(call("strftime", vec!["%s".into(), at])),
+        };
+        let key = epoch.sub(first.timestamp()).div(seconds);
+        as_sint(
+            db,
+            match backend {
+                // SQLite 的整数除法已经向下取整，并且没有内置 FLOOR
+                DbBackend::Sqlite => key,
+                _ => call("FLOOR", vec![key]),
+            },
+        )
+    }
+
+    /// 下一个时间桶的起始时间。
+    fn next(self, start: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        match self {
+            TimeBucket::Hour => start + Duration::hours(1),
+            TimeBucket::Day => start + Duration::days(1),
+            TimeBucket::Week => start + Duration::weeks(1),
+            TimeBucket::Month => start + Months::new(1),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TimeSeriesOption {
+    pub metric: TimeSeriesMetric,
+    pub bucket: TimeBucket,
+    /// 起始时间（包含），RFC 3339 格式
+    pub from: DateTime<FixedOffset>,
+    /// 结束时间（不包含），RFC 3339 格式，默认为当前时间
+    pub to: Option<DateTime<FixedOffset>>,
+    /// 划分时间桶所用的时区，例如 `+08:00`（在 URL 中需写作 `%2B08:00`），默认为 UTC
+    #[param(value_type = Option<String>)]
+    pub tz: Option<TzOffset>,
+    /// 超级管理员可以查看所有人的数据
+    pub all: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TimeSeriesPoint {
+    /// 时间桶的起始时间
+    pub start: DateTime<FixedOffset>,
+    pub value: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TimeSeries {
+    /// 划分时间桶所用的时区
+    pub tz: String,
+    pub points: Vec<TimeSeriesPoint>,
+}
+
+#[p(
+    params(TimeSeriesOption),
+    responses(
+        (status = OK, description = "Stat successful", body = TimeSeries),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid time range", body = GeneralResponse),
+    ),
+    security(("jwt_token" = []), ("api_key" = []))
+)]
+#[get("/stats/timeseries")]
+pub async fn stat_timeseries(
+    param: Query<TimeSeriesOption>,
+    db: Data<DatabaseConnection>,
+    auth: APermission<Credential, OrApiKey<AllowAdmin>>,
+) -> AResult<AJson<TimeSeries>> {
+    let tz = param.tz.unwrap_or_default().0;
+    let from = param.from.with_timezone(&tz);
+    let to = param
+        .to
+        .unwrap_or_else(|| Utc::now().into())
+        .with_timezone(&tz);
+    if from >= to {
+        return Err(unprocessable_entity("`from` must be earlier than `to`").into());
+    }
+
+    let mut points = Vec::new();
+    let mut start = param.bucket.floor(from);
+    while start < to {
+        if points.len() >= MAX_BUCKETS {
+            return Err(unprocessable_entity(format!(
+                "Too many buckets, at most {} are allowed",
+                MAX_BUCKETS
+            ))
+            .into());
+        }
+        points.push(TimeSeriesPoint { start, value: 0.0 });
+        start = param.bucket.next(start);
+    }
+
+    let operator = should_filter_user(param.all, &auth.auth_info).then_some(auth.auth_info.id);
+    let first = points[0].start;
+    let (from, to) = (from.naive_utc(), to.naive_utc());
+    let group_by_key = Expr::col(Alias::new("bucket"));
+    let sums: Vec<(i64, f64)> = match param.metric {
+        TimeSeriesMetric::Revenue | TimeSeriesMetric::StockSpend => {
+            let typ = match param.metric {
+                TimeSeriesMetric::Revenue => TicketType::Sell,
+                _ => TicketType::Stock,
+            };
+            transaction::Entity::find()
+                .inner_join(order_list::Entity)
+                .filter(order_list::Column::Typ.eq(typ))
+                .filter(transaction::Column::CreatedAt.gte(from))
+                .filter(transaction::Column::CreatedAt.lt(to))
+                .apply_if(operator, |q, v| {
+                    q.filter(order_list::Column::OperatorId.eq(v))
+                })
+                .select_only()
+                .column_as(
+                    param.bucket.key(
+                        db.get_ref(),
+                        Expr::col((transaction::Entity, transaction::Column::CreatedAt)).into(),
+                        first,
+                    ),
+                    "bucket",
+                )
+                .column_as(
+                    as_double(db.get_ref(), order_list::Column::TotalPrice.sum()),
+                    "value",
+                )
+                .group_by(group_by_key)
+                .into_tuple::<(i64, f64)>()
+                .all(db.get_ref())
+                .await?
+        }
+        TimeSeriesMetric::SellCount => order_list::Entity::find()
+            .filter(order_list::Column::Typ.eq(TicketType::Sell))
+            .filter(order_list::Column::CreatedAt.gte(from))
+            .filter(order_list::Column::CreatedAt.lt(to))
+            .apply_if(operator, |q, v| {
+                q.filter(order_list::Column::OperatorId.eq(v))
+            })
+            .select_only()
+            .column_as(
+                param.bucket.key(
+                    db.get_ref(),
+                    Expr::col((order_list::Entity, order_list::Column::CreatedAt)).into(),
+                    first,
+                ),
+                "bucket",
+            )
+            .column_as(
+                as_sint(db.get_ref(), order_list::Column::TotalCount.sum()),
+                "value",
+            )
+            .group_by(group_by_key)
+            .into_tuple::<(i64, i64)>()
+            .all(db.get_ref())
+            .await?
+            .into_iter()
+            .map(|(key, count)| (key, count as f64))
+            .collect(),
+    };
+
+    for (key, value) in sums {
+        if let Some(point) = usize::try_from(key).ok().and_then(|i| points.get_mut(i)) {
+            point.value += value;
+        }
+    }
+
+    Ok(AJson(TimeSeries {
+        tz: tz.to_string(),
+        points,
+    }))
+}
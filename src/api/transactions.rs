@@ -1,5 +1,6 @@
+use crate::utils::api_key::{Credential, OrApiKey};
 use crate::utils::ext::SelectTwoExt;
-use crate::utils::jwt::{AllowAdmin, JwtClaims};
+use crate::utils::jwt::AllowAdmin;
 use crate::utils::permission::APermission;
 
 use super::preclude::*;
@@ -27,12 +28,12 @@ pub struct TransactionFilter {
     responses(
         (status = OK, description = "Get transaction list successfully", body = [GetTransaction]),
     ),
-    security(("jwt_token" = []))
+    security(("jwt_token" = []), ("api_key" = []))
 )]
 #[get("/transaction")]
 pub async fn get_transaction_list(
     params: Query<TransactionFilter>,
-    _auth: APermission<JwtClaims, AllowAdmin>,
+    _auth: APermission<Credential, OrApiKey<AllowAdmin>>,
     db: Data<DatabaseConnection>,
 ) -> AResult<HttpResponse> {
     let params = params.into_inner();
@@ -0,0 +1,520 @@
+// 服务器配置。
+//
+// 先从 TOML 配置文件中读取，再用环境变量覆盖，最后在启动时统一校验。未配置的项使用默认值。
+
+use std::{collections::BTreeMap, env, fs, io::ErrorKind, str::FromStr};
+
+use jsonwebtoken::Algorithm;
+use serde::Deserialize;
+
+use crate::api::stats::cost::CostMethod;
+use crate::contants::{envs, API_KEY_HEADER};
+use crate::scheduler::jobs;
+use crate::utils::cors::{validate_headers, validate_methods, OriginPattern};
+use crate::utils::db::backend_of;
+use crate::utils::jwk::JwtKeys;
+use crate::utils::password::PasswordPolicy;
+
+/// 默认的配置文件路径，可以通过环境变量 `CONFIG_FILE` 指定其他路径。
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub auth: AuthConfig,
+    pub jwt: JwtConfig,
+    pub password: PasswordPolicy,
+    pub cors: CorsConfig,
+    pub database: DatabaseConfig,
+    pub redis: RedisConfig,
+    pub scheduler: SchedulerConfig,
+    pub rate_limit: RateLimitConfig,
+    pub metrics: MetricsConfig,
+    pub stats: StatsConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// 监听地址
+    pub host: String,
+    /// 监听端口
+    pub port: u16,
+    /// 工作线程数，未设置时等于 CPU 核心数
+    pub workers: Option<usize>,
+    /// 收到停止信号后等待进行中的请求与任务结束的最长时间（秒）
+    pub shutdown_timeout: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_owned(),
+            port: 8080,
+            workers: None,
+            shutdown_timeout: 30,
+        }
+    }
+}
+
+/// 时长均以秒为单位。
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub access_token_lifetime: i64,
+    pub refresh_token_lifetime: i64,
+    /// 管理员签发的密码重置凭据的有效期
+    pub reset_token_lifetime: i64,
+    /// 用户 Secret Key 的长度
+    pub secret_key_length: usize,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            access_token_lifetime: 1800,
+            refresh_token_lifetime: 3600 * 24 * 7,
+            reset_token_lifetime: 3600,
+            secret_key_length: 32,
+        }
+    }
+}
+
+/// JWT 的签名算法与密钥，在启动时加载为 [`JwtKeys`]。
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    /// 签名算法，支持 `HS256`（默认）、`RS256` 和 `EdDSA`
+    pub algorithm: Algorithm,
+    /// `HS256` 使用的对称密钥
+    pub secret: Option<String>,
+    /// 当前签名密钥的编号，会写入 JWT Header 的 `kid` 字段，`RS256`/`EdDSA` 必须设置
+    pub key_id: Option<String>,
+    /// `RS256`/`EdDSA` 使用的 PEM 格式私钥文件路径
+    pub private_key: Option<String>,
+    /// 所有验证公钥的 PEM 文件路径，键为密钥编号，必须包含当前签名密钥的公钥。
+    /// 轮换密钥时，旧的公钥应继续保留一段时间
+    pub public_keys: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// 允许任意来源，不建议在生产环境中使用
+    pub allow_all: bool,
+    /// 允许的来源，例如 `https://example.com`，也可以使用 `https://*.example.com` 匹配所有子域名
+    pub allowed_origins: Vec<String>,
+    /// 允许的请求方法，`*` 表示任意方法
+    pub allowed_methods: Vec<String>,
+    /// 允许的请求头，`*` 表示任意请求头
+    pub allowed_headers: Vec<String>,
+    /// 是否允许携带 Cookie 等凭据
+    pub allow_credentials: bool,
+    /// 预检请求结果的缓存时间（秒）
+    pub max_age: Option<usize>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allow_all: false,
+            allowed_origins: vec![],
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+                .map(str::to_owned)
+                .to_vec(),
+            allowed_headers: ["Authorization", "Content-Type", API_KEY_HEADER]
+                .map(str::to_owned)
+                .to_vec(),
+            allow_credentials: false,
+            max_age: Some(3600),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    /// 连接池的最大连接数，未设置时使用 SeaORM 的默认值
+    pub max_connections: Option<u32>,
+    /// 连接池的最小连接数
+    pub min_connections: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisConfig {
+    /// 未设置时不使用 Redis 缓存
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// 是否在当前实例上运行定时任务，多个实例都开启时每次调度只会由其中一个执行
+    pub enabled: bool,
+    /// 待支付订单的有效期（秒），开启 `expire_pending_orders` 任务后超时的订单会被自动撤销
+    pub pending_order_lifetime: i64,
+    /// 覆盖任务默认的 cron 表达式，键为任务名，`off` 表示不执行该任务
+    pub schedules: BTreeMap<String, String>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pending_order_lifetime: 3600 * 24,
+            schedules: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// 从 `Forwarded`、`X-Forwarded-For` 请求头获取客户端 IP，只应在反向代理之后开启
+    pub trust_forwarded_headers: bool,
+    /// 按路由分组的限额，键为分组名。配置后替换全部默认分组，不属于任何分组的路由不限流
+    pub groups: BTreeMap<String, RateLimitGroup>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let group = |paths: &[&str], burst, per_minute| RateLimitGroup {
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            burst,
+            per_minute,
+        };
+        Self {
+            enabled: true,
+            trust_forwarded_headers: false,
+            groups: BTreeMap::from([
+                (
+                    "login".to_owned(),
+                    group(
+                        &["/user/login", "/user/refresh", "/user/reset_password"],
+                        5,
+                        10,
+                    ),
+                ),
+                ("stats".to_owned(), group(&["/stats/"], 20, 60)),
+            ]),
+        }
+    }
+}
+
+/// 令牌桶的参数。
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitGroup {
+    /// 路由前缀，`/stats/` 匹配其下的所有路由，`/user/login` 只匹配它本身及其子路径
+    pub paths: Vec<String>,
+    /// 桶的容量，即允许的突发请求数
+    pub burst: u32,
+    /// 每分钟补充的令牌数，即长期的平均速率
+    pub per_minute: u32,
+}
+
+/// `/metrics` 与业务接口使用同一个端口，其中包含各路由的流量、订单与销量计数以及数据库连接池的状态。
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    /// 设置后抓取时需要携带 `Authorization: Bearer <token>`，未设置时任何人都可以访问
+    pub token: Option<String>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            token: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsConfig {
+    /// 请求中没有指定时使用的成本计价方法
+    pub cost_method: CostMethod,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            cost_method: CostMethod::Average,
+        }
+    }
+}
+
+impl Config {
+    /// 读取配置文件与环境变量，并校验配置。
+    ///
+    /// 配置文件由 `CONFIG_FILE` 指定；未指定且默认的配置文件不存在时，只使用环境变量和默认值。
+    pub fn load() -> Result<Self, String> {
+        let (path, required) = match env::var(envs::CONFIG_FILE) {
+            Ok(path) => (path, true),
+            Err(_) => (DEFAULT_CONFIG_FILE.to_owned(), false),
+        };
+        let mut config = match fs::read_to_string(&path) {
+            Ok(content) => {
+                Self::from_toml(&content).map_err(|err| format!("Invalid {}: {}", path, err))?
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound && !required => Self::default(),
+            Err(err) => return Err(format!("Unable to read {}: {}", path, err)),
+        };
+        config.apply_env(|key| env::var(key).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|err| err.to_string())
+    }
+
+    /// 用环境变量覆盖配置，`lookup` 根据变量名返回变量的值。
+    pub fn apply_env(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        fn parse<T: FromStr>(key: &str, value: String) -> Result<T, String> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid value for {}: {}", key, value))
+        }
+        let read = |key: &str| lookup(key).filter(|v| !v.is_empty());
+        // 以逗号分隔的列表
+        let list = |v: String| -> Vec<String> {
+            v.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_owned)
+                .collect()
+        };
+
+        if let Some(v) = read(envs::LISTEN_HOST) {
+            self.server.host = v;
+        }
+        if let Some(v) = read(envs::LISTEN_PORT) {
+            self.server.port = parse(envs::LISTEN_PORT, v)?;
+        }
+        if let Some(v) = read(envs::WORKERS) {
+            self.server.workers = Some(parse(envs::WORKERS, v)?);
+        }
+        if let Some(v) = read(envs::SHUTDOWN_TIMEOUT) {
+            self.server.shutdown_timeout = parse(envs::SHUTDOWN_TIMEOUT, v)?;
+        }
+        if let Some(v) = read(envs::ACCESS_TOKEN_LIFETIME) {
+            self.auth.access_token_lifetime = parse(envs::ACCESS_TOKEN_LIFETIME, v)?;
+        }
+        if let Some(v) = read(envs::REFRESH_TOKEN_LIFETIME) {
+            self.auth.refresh_token_lifetime = parse(envs::REFRESH_TOKEN_LIFETIME, v)?;
+        }
+        if let Some(v) = read(envs::RESET_TOKEN_LIFETIME) {
+            self.auth.reset_token_lifetime = parse(envs::RESET_TOKEN_LIFETIME, v)?;
+        }
+        if let Some(v) = read(envs::SECRET_KEY_LENGTH) {
+            self.auth.secret_key_length = parse(envs::SECRET_KEY_LENGTH, v)?;
+        }
+        if let Some(v) = read(envs::JWT_ALGORITHM) {
+            self.jwt.algorithm = parse(envs::JWT_ALGORITHM, v)?;
+        }
+        if let Some(v) = read(envs::JWT_SECRET) {
+            self.jwt.secret = Some(v);
+        }
+        if let Some(v) = read(envs::JWT_KEY_ID) {
+            self.jwt.key_id = Some(v);
+        }
+        if let Some(v) = read(envs::JWT_PRIVATE_KEY) {
+            self.jwt.private_key = Some(v);
+        }
+        if let Some(v) = read(envs::JWT_PUBLIC_KEYS) {
+            // 格式为 kid=path
+            self.jwt.public_keys = list(v)
+                .into_iter()
+                .map(|entry| match entry.split_once('=') {
+                    Some((kid, path)) => Ok((kid.trim().to_owned(), path.trim().to_owned())),
+                    None => Err(format!(
+                        "Invalid value for {}: {}, expect kid=path",
+                        envs::JWT_PUBLIC_KEYS,
+                        entry
+                    )),
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(v) = read(envs::PASSWORD_MIN_LENGTH) {
+            self.password.min_length = parse(envs::PASSWORD_MIN_LENGTH, v)?;
+        }
+        if let Some(v) = read(envs::PASSWORD_MIN_CLASSES) {
+            self.password.min_classes = parse(envs::PASSWORD_MIN_CLASSES, v)?;
+        }
+        if let Some(v) = read(envs::PASSWORD_HISTORY) {
+            self.password.history = parse(envs::PASSWORD_HISTORY, v)?;
+        }
+        if let Some(v) = read(envs::ALLOW_ALL_CORS) {
+            self.cors.allow_all = parse(envs::ALLOW_ALL_CORS, v)?;
+        }
+        if let Some(v) = read(envs::CORS_ALLOWED_ORIGINS) {
+            self.cors.allowed_origins = list(v);
+        }
+        if let Some(v) = read(envs::CORS_ALLOWED_METHODS) {
+            self.cors.allowed_methods = list(v);
+        }
+        if let Some(v) = read(envs::CORS_ALLOWED_HEADERS) {
+            self.cors.allowed_headers = list(v);
+        }
+        if let Some(v) = read(envs::CORS_ALLOW_CREDENTIALS) {
+            self.cors.allow_credentials = parse(envs::CORS_ALLOW_CREDENTIALS, v)?;
+        }
+        if let Some(v) = read(envs::CORS_MAX_AGE) {
+            self.cors.max_age = Some(parse(envs::CORS_MAX_AGE, v)?);
+        }
+        if let Some(v) = read(envs::DB_URL) {
+            self.database.url = v;
+        }
+        if let Some(v) = read(envs::DB_MAX_CONNECTIONS) {
+            self.database.max_connections = Some(parse(envs::DB_MAX_CONNECTIONS, v)?);
+        }
+        if let Some(v) = read(envs::DB_MIN_CONNECTIONS) {
+            self.database.min_connections = Some(parse(envs::DB_MIN_CONNECTIONS, v)?);
+        }
+        if let Some(v) = read(envs::REDIS_URL) {
+            self.redis.url = Some(v);
+        }
+        if let Some(v) = read(envs::SCHEDULER_ENABLED) {
+            self.scheduler.enabled = parse(envs::SCHEDULER_ENABLED, v)?;
+        }
+        if let Some(v) = read(envs::PENDING_ORDER_LIFETIME) {
+            self.scheduler.pending_order_lifetime = parse(envs::PENDING_ORDER_LIFETIME, v)?;
+        }
+        if let Some(v) = read(envs::RATE_LIMIT_ENABLED) {
+            self.rate_limit.enabled = parse(envs::RATE_LIMIT_ENABLED, v)?;
+        }
+        if let Some(v) = read(envs::TRUST_FORWARDED_HEADERS) {
+            self.rate_limit.trust_forwarded_headers = parse(envs::TRUST_FORWARDED_HEADERS, v)?;
+        }
+        if let Some(v) = read(envs::METRICS_ENABLED) {
+            self.metrics.enabled = parse(envs::METRICS_ENABLED, v)?;
+        }
+        if let Some(v) = read(envs::METRICS_TOKEN) {
+            self.metrics.token = Some(v);
+        }
+        if let Some(v) = read(envs::COST_METHOD) {
+            self.stats.cost_method = parse(envs::COST_METHOD, v)?;
+        }
+        Ok(())
+    }
+
+    /// 校验配置，返回第一个发现的问题。
+    pub fn validate(&self) -> Result<(), String> {
+        if self.server.host.is_empty() {
+            return Err("server.host must not be empty".to_owned());
+        }
+        if self.server.workers == Some(0) {
+            return Err("server.workers must be positive".to_owned());
+        }
+
+        let auth = &self.auth;
+        for (name, value) in [
+            ("auth.access_token_lifetime", auth.access_token_lifetime),
+            ("auth.refresh_token_lifetime", auth.refresh_token_lifetime),
+            ("auth.reset_token_lifetime", auth.reset_token_lifetime),
+        ] {
+            if value <= 0 {
+                return Err(format!("{} must be positive", name));
+            }
+        }
+        if auth.refresh_token_lifetime < auth.access_token_lifetime {
+            return Err(
+                "auth.refresh_token_lifetime must not be shorter than auth.access_token_lifetime"
+                    .to_owned(),
+            );
+        }
+        // user.secret_key 在 MySQL 中为 VARCHAR(255)
+        if !(16..=255).contains(&auth.secret_key_length) {
+            return Err("auth.secret_key_length must be between 16 and 255".to_owned());
+        }
+
+        if self.jwt.algorithm == Algorithm::HS256
+            && self.jwt.secret.as_deref().unwrap_or_default().is_empty()
+        {
+            return Err(format!(
+                "jwt.secret is required for HS256, set it in the config file or {}",
+                envs::JWT_SECRET
+            ));
+        }
+        // 读取并解析密钥文件
+        JwtKeys::from_config(&self.jwt)?;
+
+        let password = &self.password;
+        if password.min_length == 0 {
+            return Err("password.min_length must be positive".to_owned());
+        }
+        // 只有小写字母、大写字母、数字与其他符号四类
+        if !(1..=4).contains(&password.min_classes) {
+            return Err("password.min_classes must be between 1 and 4".to_owned());
+        }
+
+        let cors = &self.cors;
+        for origin in &cors.allowed_origins {
+            OriginPattern::parse(origin)?;
+        }
+        validate_methods(&cors.allowed_methods)?;
+        validate_headers(&cors.allowed_headers)?;
+        if cors.allow_all && cors.allow_credentials {
+            // 任意来源都可以携带凭据访问，等同于关闭了同源策略
+            return Err("cors.allow_credentials cannot be combined with cors.allow_all".to_owned());
+        }
+
+        let database = &self.database;
+        if database.url.is_empty() {
+            return Err(format!(
+                "database.url is required, set it in the config file or {}",
+                envs::DB_URL
+            ));
+        }
+        backend_of(&database.url)?;
+        if database.max_connections == Some(0) {
+            return Err("database.max_connections must be positive".to_owned());
+        }
+        if let (Some(min), Some(max)) = (database.min_connections, database.max_connections) {
+            if min > max {
+                return Err(
+                    "database.min_connections must not exceed database.max_connections".to_owned(),
+                );
+            }
+        }
+
+        if let Some(ref url) = self.redis.url {
+            // 只解析 URL，不建立连接
+            redis::Client::open(url.as_str())
+                .map_err(|err| format!("Invalid redis.url: {}", err))?;
+        }
+
+        if self.scheduler.pending_order_lifetime <= 0 {
+            return Err("scheduler.pending_order_lifetime must be positive".to_owned());
+        }
+        jobs::build(&self.scheduler)?;
+
+        for (name, group) in &self.rate_limit.groups {
+            if group.burst == 0 || group.per_minute == 0 {
+                return Err(format!(
+                    "rate_limit.groups.{}: burst and per_minute must be positive",
+                    name
+                ));
+            }
+            if group.paths.is_empty() || group.paths.iter().any(|path| !path.starts_with('/')) {
+                return Err(format!(
+                    "rate_limit.groups.{}: paths must be non-empty and start with /",
+                    name
+                ));
+            }
+        }
+
+        if self.metrics.token.as_deref() == Some("") {
+            return Err("metrics.token must not be empty".to_owned());
+        }
+        Ok(())
+    }
+}
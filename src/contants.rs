@@ -7,11 +7,48 @@ pub mod envs {
     pub const DB_URL: &str = "DB_URL";
     pub const REDIS_URL: &str = "REDIS_URL";
     pub const JWT_SECRET: &str = "JWT_SECRET";
+    pub const JWT_ALGORITHM: &str = "JWT_ALGORITHM";
+    pub const JWT_PRIVATE_KEY: &str = "JWT_PRIVATE_KEY";
+    pub const JWT_KEY_ID: &str = "JWT_KEY_ID";
+    pub const JWT_PUBLIC_KEYS: &str = "JWT_PUBLIC_KEYS";
     pub const ALLOW_ALL_CORS: &str = "ALLOW_ALL_CORS";
+    pub const PASSWORD_MIN_LENGTH: &str = "PASSWORD_MIN_LENGTH";
+    pub const PASSWORD_MIN_CLASSES: &str = "PASSWORD_MIN_CLASSES";
+    pub const PASSWORD_HISTORY: &str = "PASSWORD_HISTORY";
+    pub const COST_METHOD: &str = "COST_METHOD";
+    pub const CONFIG_FILE: &str = "CONFIG_FILE";
+    pub const LISTEN_HOST: &str = "LISTEN_HOST";
+    pub const LISTEN_PORT: &str = "LISTEN_PORT";
+    pub const WORKERS: &str = "WORKERS";
+    pub const SHUTDOWN_TIMEOUT: &str = "SHUTDOWN_TIMEOUT";
+    pub const ACCESS_TOKEN_LIFETIME: &str = "ACCESS_TOKEN_LIFETIME";
+    pub const REFRESH_TOKEN_LIFETIME: &str = "REFRESH_TOKEN_LIFETIME";
+    pub const RESET_TOKEN_LIFETIME: &str = "RESET_TOKEN_LIFETIME";
+    pub const SECRET_KEY_LENGTH: &str = "SECRET_KEY_LENGTH";
+    pub const CORS_ALLOWED_ORIGINS: &str = "CORS_ALLOWED_ORIGINS";
+    pub const CORS_ALLOWED_METHODS: &str = "CORS_ALLOWED_METHODS";
+    pub const CORS_ALLOWED_HEADERS: &str = "CORS_ALLOWED_HEADERS";
+    pub const CORS_ALLOW_CREDENTIALS: &str = "CORS_ALLOW_CREDENTIALS";
+    pub const CORS_MAX_AGE: &str = "CORS_MAX_AGE";
+    pub const DB_MAX_CONNECTIONS: &str = "DB_MAX_CONNECTIONS";
+    pub const DB_MIN_CONNECTIONS: &str = "DB_MIN_CONNECTIONS";
+    pub const SCHEDULER_ENABLED: &str = "SCHEDULER_ENABLED";
+    pub const PENDING_ORDER_LIFETIME: &str = "PENDING_ORDER_LIFETIME";
+    pub const RATE_LIMIT_ENABLED: &str = "RATE_LIMIT_ENABLED";
+    pub const TRUST_FORWARDED_HEADERS: &str = "TRUST_FORWARDED_HEADERS";
+    pub const METRICS_ENABLED: &str = "METRICS_ENABLED";
+    pub const METRICS_TOKEN: &str = "METRICS_TOKEN";
 }
 
-pub const SECRET_KEY_LENGTH: usize = 32;
+pub const TOKEN_ID_LENGTH: usize = 32;
 pub const ISSUER: &str = "mid";
-pub const ACCESS_TOKEN_EXPIRE_SECONDS: i64 = 1800;
-pub const REFRESH_TOKEN_EXPIRE_SECONDS: i64 = 3600 * 24 * 7;
 pub const ITEM_COUNT_HEADER: &str = "X-Item-Count";
+pub const DEFAULT_PAGE_SIZE: u64 = 20;
+pub const MAX_PAGE_SIZE: u64 = 100;
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+pub const RATE_LIMIT_LIMIT_HEADER: &str = "X-RateLimit-Limit";
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "X-RateLimit-Remaining";
+pub const RATE_LIMIT_RESET_HEADER: &str = "X-RateLimit-Reset";
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+pub const API_KEY_PREFIX: &str = "mid_";
+pub const API_KEY_DISPLAY_LENGTH: usize = 12;
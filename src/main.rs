@@ -1,18 +1,20 @@
 #![feature(min_specialization, ready_into_inner)]
-use actix_cors::Cors;
 use actix_web::{web, App, HttpServer};
 use log::{error, info, warn};
 use migration::{Migrator, MigratorTrait};
 use mimalloc::MiMalloc;
-use sea_orm::Database;
-use std::env;
 use tokio::sync::Mutex;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::contants::envs;
+use crate::config::Config;
+use crate::utils::jwk::JwtKeys;
 mod api;
+mod config;
 mod contants;
+mod scheduler;
+#[cfg(test)]
+mod tests;
 mod utils;
 
 #[global_allocator]
@@ -23,18 +25,23 @@ async fn main() -> std::io::Result<()> {
     if let Some(err) = dotenv::dotenv().err() {
         eprintln!("Error loading .env file: {}. You may safely ignore this error if you are not using .env file.", err);
     }
-    let _ = env_logger::try_init();
+    utils::logging::init();
 
-    let db = Database::connect(
-        env::var(envs::DB_URL).expect("Unable to obtain DB_URL from the environment variables"),
-    )
-    .await
-    .expect("Unable to connect to the database");
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Invalid configuration, exiting: {}", err);
+            return Ok(());
+        }
+    };
+
+    let db = utils::db::connect(&config.database)
+        .await
+        .expect("Unable to connect to the database");
 
-    let redis_url = env::var(envs::REDIS_URL);
     let mut redis_conn = None;
-    if let Ok(redis_url) = redis_url {
-        let redis = redis::Client::open(redis_url).expect("Unable to open redis client");
+    if let Some(ref redis_url) = config.redis.url {
+        let redis = redis::Client::open(redis_url.as_str()).expect("Unable to open redis client");
         redis_conn = Some(
             redis
                 .get_multiplexed_async_connection()
@@ -52,27 +59,38 @@ async fn main() -> std::io::Result<()> {
         info!("Migration completed");
     }
 
-    let allow_cors: bool = env::var(envs::ALLOW_ALL_CORS)
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(false);
-
-    if allow_cors {
+    if config.cors.allow_all {
         warn!("CORS is enabled for all origins, this is not recommended for production!")
     }
+    info!("CORS policy: {}", utils::cors::describe(&config.cors));
 
-    HttpServer::new(move || {
-        let mut cors = Cors::default();
-        cors = cors.expose_headers([crate::contants::ITEM_COUNT_HEADER]);
-        if allow_cors {
-            cors = cors
-                .allow_any_origin()
-                .allow_any_method()
-                .allow_any_header()
-                .max_age(None);
-        }
+    let bind = (config.server.host.clone(), config.server.port);
+    let workers = config.server.workers;
+    let shutdown_timeout = config.server.shutdown_timeout;
+    let jwt_keys =
+        web::Data::new(JwtKeys::from_config(&config.jwt).expect("validated in Config::load"));
+    let config = web::Data::new(config);
+
+    let scheduler = if config.scheduler.enabled {
+        let jobs = scheduler::jobs::build(&config.scheduler).expect("validated in Config::load");
+        info!("Scheduling {} background jobs", jobs.len());
+        let context = scheduler::JobContext {
+            db: db.clone(),
+            redis: web::Data::new(redis_conn.clone().map(Mutex::new)),
+            config: config.clone(),
+        };
+        Some(scheduler::Scheduler::new(context, jobs).start())
+    } else {
+        None
+    };
+
+    let rate_limiter = utils::rate_limit::RateLimiter::new(&config.rate_limit);
+    let server = HttpServer::new(move || {
         App::new()
-            .wrap(cors)
+            .wrap(rate_limiter.clone())
+            .wrap(utils::cors::build(&config.cors))
+            .wrap(utils::metrics::RequestMetrics)
+            .wrap(utils::logging::RequestLogger)
             .configure(api::configure())
             .service(
                 SwaggerUi::new("/docs/{_:.*}")
@@ -80,8 +98,22 @@ async fn main() -> std::io::Result<()> {
             )
             .app_data(web::Data::new(db.clone()))
             .app_data(web::Data::new(redis_conn.clone().map(Mutex::new)))
-    })
-    .bind(("0.0.0.0", 8080))?
-    .run()
-    .await
+            .app_data(config.clone())
+            .app_data(jwt_keys.clone())
+    });
+    let server = match workers {
+        Some(workers) => server.workers(workers),
+        None => server,
+    };
+    // 收到 SIGTERM 后不再接受新连接，并等待进行中的请求结束
+    let result = server
+        .shutdown_timeout(shutdown_timeout)
+        .bind(bind)?
+        .run()
+        .await;
+    if let Some(scheduler) = scheduler {
+        info!("Waiting for background jobs to finish");
+        scheduler.shutdown().await;
+    }
+    result
 }
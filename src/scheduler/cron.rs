@@ -0,0 +1,129 @@
+// 简化的 cron 表达式。
+//
+// 支持五个字段（分 时 日 月 星期），每个字段可以是 `*`、数字、范围 `a-b` 以及步长 `*/n`、`a-b/n`，
+// 多项之间以逗号分隔；另支持 `@hourly`、`@daily`、`@weekly`、`@monthly` 四个别名。时间均为 UTC。
+
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// 日与星期都不以 `*` 开头时，按照 cron 的习惯满足其一即可
+    day_or_weekday: bool,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            expr => expr,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("Cron expression must have 5 fields: {}", expr));
+        };
+        let mut weekdays = field(weekday, 0, 7)?;
+        // 0 和 7 都表示星期日
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: field(minute, 0, 59)?,
+            hours: field(hour, 0, 23)?,
+            days: field(day, 1, 31)?,
+            months: field(month, 1, 12)?,
+            weekdays,
+            day_or_weekday: !day.starts_with('*') && !weekday.starts_with('*'),
+        })
+    }
+}
+
+/// 将一个字段解析为位集合，第 `n` 位表示取值 `n`。
+fn field(text: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("Invalid cron field: {}", text);
+    let number = |s: &str| -> Result<u32, String> {
+        s.parse()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(invalid)
+    };
+    let mut bits = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse().ok().filter(|s| *s > 0).ok_or_else(invalid)?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // `a/n` 表示从 a 开始到最大值
+            None if step > 1 => (number(range)?, max),
+            None => {
+                let n = number(range)?;
+                (n, n)
+            }
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        for n in (start..=end).step_by(step) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+fn has(bits: u64, n: u32) -> bool {
+    bits & (1 << n) != 0
+}
+
+impl Schedule {
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        if self.day_or_weekday {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// 严格晚于 `after` 的下一个执行时间，五年内都不会执行（例如 2 月 30 日）时返回 `None`。
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = time + Duration::days(366 * 5);
+        while time < limit {
+            let date = time.date();
+            if !has(self.months, date.month()) {
+                let (year, month) = match date.month() {
+                    12 => (date.year() + 1, 1),
+                    month => (date.year(), month + 1),
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.matches_day(date) {
+                time = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !has(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
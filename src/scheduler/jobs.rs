@@ -0,0 +1,100 @@
+// 内置的定时任务。
+
+use chrono::{Duration, Utc};
+use entity::{job_run, order_list, password_reset, refresh_token, TicketStatus, TicketType};
+use sea_orm::{ActiveEnum, ColumnTrait, EntityTrait, QueryFilter, Set};
+
+use crate::config::SchedulerConfig;
+use crate::utils::metrics::METRICS;
+
+use super::{Job, JobContext, JobFn, JobFuture};
+
+/// 任务执行历史的保留天数
+const JOB_RUN_RETENTION_DAYS: i64 = 30;
+
+/// 所有任务及其默认的调度表达式，`off` 表示默认不执行。
+///
+/// 自动撤销待支付订单会改变业务规则，需要在 `scheduler.schedules` 中显式开启。
+const JOBS: [(&str, &str, JobFn); 2] = [
+    ("expire_pending_orders", "off", expire_pending_orders),
+    ("purge_expired_records", "@hourly", purge_expired_records),
+];
+
+/// 根据配置生成要调度的任务，`schedules` 中可以覆盖默认的调度表达式，`off` 表示不执行该任务。
+pub fn build(config: &SchedulerConfig) -> Result<Vec<Job>, String> {
+    if let Some(name) = config
+        .schedules
+        .keys()
+        .find(|name| JOBS.iter().all(|(job, ..)| job != name))
+    {
+        return Err(format!("Unknown job in scheduler.schedules: {}", name));
+    }
+    let mut jobs = vec![];
+    for (name, default, run) in JOBS {
+        let expr = config.schedules.get(name).map_or(default, String::as_str);
+        if expr == "off" {
+            continue;
+        }
+        let schedule = expr
+            .parse()
+            .map_err(|err| format!("Invalid schedule for job {}: {}", name, err))?;
+        jobs.push(Job {
+            name,
+            schedule,
+            run,
+        });
+    }
+    Ok(jobs)
+}
+
+/// 撤销超过 `scheduler.pending_order_lifetime` 仍未支付的订单。默认不执行。
+fn expire_pending_orders(context: JobContext) -> JobFuture {
+    Box::pin(async move {
+        let now = Utc::now().naive_utc();
+        let cutoff = now - Duration::seconds(context.config.scheduler.pending_order_lifetime);
+        let mut counts = vec![];
+        for typ in [TicketType::Sell, TicketType::Stock] {
+            let result = order_list::Entity::update_many()
+                .set(order_list::ActiveModel {
+                    status: Set(TicketStatus::Revoked),
+                    updated_at: Set(now),
+                    ..Default::default()
+                })
+                .filter(order_list::Column::Status.eq(TicketStatus::Pending))
+                .filter(order_list::Column::Typ.eq(typ.clone()))
+                .filter(order_list::Column::CreatedAt.lt(cutoff))
+                .exec(&context.db)
+                .await?;
+            let typ = typ.to_value().to_lowercase();
+            METRICS
+                .orders
+                .with_label_values(&[&typ, "expired"])
+                .inc_by(result.rows_affected);
+            counts.push(format!("{} {}", result.rows_affected, typ));
+        }
+        Ok(format!("Revoked {} orders", counts.join(" and ")))
+    })
+}
+
+/// 删除过期的 Refresh Token、密码重置凭据以及较早的任务执行历史。
+fn purge_expired_records(context: JobContext) -> JobFuture {
+    Box::pin(async move {
+        let now = Utc::now().naive_utc();
+        let tokens = refresh_token::Entity::delete_many()
+            .filter(refresh_token::Column::ExpiresAt.lt(now))
+            .exec(&context.db)
+            .await?;
+        let resets = password_reset::Entity::delete_many()
+            .filter(password_reset::Column::ExpiresAt.lt(now))
+            .exec(&context.db)
+            .await?;
+        let runs = job_run::Entity::delete_many()
+            .filter(job_run::Column::FinishedAt.lt(now - Duration::days(JOB_RUN_RETENTION_DAYS)))
+            .exec(&context.db)
+            .await?;
+        Ok(format!(
+            "Deleted {} refresh tokens, {} reset tokens and {} job runs",
+            tokens.rows_affected, resets.rows_affected, runs.rows_affected
+        ))
+    })
+}
@@ -0,0 +1,245 @@
+// 后台定时任务。
+//
+// 每个任务按照 cron 表达式调度。多个实例同时运行时，同一任务的同一次调度只会由一个实例执行：
+// 配置了 Redis 时先抢占 Redis 中的锁，之后插入以任务名和计划时间为主键的 `job_run` 记录，
+// 插入成功的实例才会执行任务，该记录同时作为执行历史。
+
+pub mod cron;
+pub mod jobs;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::web::Data;
+use chrono::{NaiveDateTime, Utc};
+use entity::job_run::{self, JobStatus};
+use log::{error, info, warn};
+use redis::aio::MultiplexedConnection;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveEnum, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    Set,
+};
+use tokio::sync::{watch, Mutex};
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::config::Config;
+use crate::utils::jwt::gen_token_id;
+use crate::utils::metrics::METRICS;
+
+use self::cron::Schedule;
+
+/// Redis 锁的有效期，只需覆盖各实例之间的时钟误差与调度延迟
+const LOCK_TTL_MS: u64 = 10 * 60 * 1000;
+
+/// 任务成功时返回执行结果的说明。
+pub type JobFuture = Pin<Box<dyn Future<Output = Result<String, DbErr>> + Send>>;
+pub type JobFn = fn(JobContext) -> JobFuture;
+
+/// 任务可以使用的资源。
+#[derive(Clone)]
+pub struct JobContext {
+    pub db: DatabaseConnection,
+    pub redis: Data<Option<Mutex<MultiplexedConnection>>>,
+    pub config: Data<Config>,
+}
+
+pub struct Job {
+    pub name: &'static str,
+    pub schedule: Schedule,
+    pub run: JobFn,
+}
+
+pub struct Scheduler {
+    context: JobContext,
+    jobs: Vec<Job>,
+    /// 当前实例的标识，记录在执行历史中
+    instance: String,
+}
+
+impl Scheduler {
+    pub fn new(context: JobContext, jobs: Vec<Job>) -> Self {
+        Self {
+            context,
+            jobs,
+            instance: format!("{}-{}", process::id(), &gen_token_id()[..8]),
+        }
+    }
+
+    /// 在后台开始调度。
+    pub fn start(self) -> SchedulerHandle {
+        let (stop, stopped) = watch::channel(false);
+        let task = tokio::spawn(Arc::new(self).run_loop(stopped));
+        SchedulerHandle { stop, task }
+    }
+
+    async fn run_loop(self: Arc<Self>, mut stopped: watch::Receiver<bool>) -> Arc<Self> {
+        let now = Utc::now().naive_utc();
+        let mut next: Vec<_> = self
+            .jobs
+            .iter()
+            .map(|job| job.schedule.next_after(now))
+            .collect();
+        let mut running = JoinSet::new();
+        loop {
+            let sleep = match next.iter().flatten().min() {
+                Some(due) => (*due - Utc::now().naive_utc()).to_std().unwrap_or_default(),
+                None => Duration::MAX,
+            };
+            tokio::select! {
+                _ = stopped.changed() => break,
+                // 回收已经结束的任务
+                Some(_) = running.join_next(), if !running.is_empty() => continue,
+                _ = tokio::time::sleep(sleep) => {}
+            }
+            let now = Utc::now().naive_utc();
+            for (index, job) in self.jobs.iter().enumerate() {
+                let Some(due) = next[index].filter(|due| *due <= now) else {
+                    continue;
+                };
+                // 停机期间错过的调度不再补执行
+                next[index] = job.schedule.next_after(now);
+                let scheduler = self.clone();
+                running.spawn(async move {
+                    if let Err(err) = scheduler.run(index, due).await {
+                        error!(job = scheduler.jobs[index].name; "Unable to record job run: {}", err);
+                    }
+                });
+            }
+        }
+
+        let timeout = Duration::from_secs(self.context.config.server.shutdown_timeout);
+        let drain = async { while running.join_next().await.is_some() {} };
+        if tokio::time::timeout(timeout, drain).await.is_err() {
+            warn!(
+                "{} jobs still running after {:?}, aborting",
+                running.len(),
+                timeout
+            );
+            running.shutdown().await;
+            if let Err(err) = self.mark_interrupted().await {
+                error!("Unable to mark interrupted jobs: {}", err);
+            }
+        }
+        self
+    }
+
+    /// 执行第 `index` 个任务计划在 `scheduled_at` 的一次调度。
+    ///
+    /// 其他实例已经执行了这次调度时返回 `None`。
+    pub async fn run(
+        &self,
+        index: usize,
+        scheduled_at: NaiveDateTime,
+    ) -> Result<Option<JobStatus>, DbErr> {
+        let job = &self.jobs[index];
+        if !self.lock(job.name, scheduled_at).await {
+            return Ok(None);
+        }
+
+        let db = &self.context.db;
+        let record = job_run::ActiveModel {
+            job: Set(job.name.to_owned()),
+            scheduled_at: Set(scheduled_at),
+            instance: Set(self.instance.clone()),
+            status: Set(JobStatus::Running),
+            detail: Set(None),
+            started_at: Set(Utc::now().naive_utc()),
+            finished_at: Set(None),
+        };
+        if let Err(err) = record.insert(db).await {
+            // 主键冲突说明其他实例抢先插入了记录，不同数据库的错误不同，只能再查询一次
+            return match job_run::Entity::find_by_id((job.name.to_owned(), scheduled_at))
+                .one(db)
+                .await?
+            {
+                Some(_) => Ok(None),
+                None => Err(err),
+            };
+        }
+
+        info!(job = job.name; "Job started");
+        let (status, detail) = match (job.run)(self.context.clone()).await {
+            Ok(detail) => {
+                info!(job = job.name; "Job succeeded: {}", detail);
+                (JobStatus::Succeeded, detail)
+            }
+            Err(err) => {
+                error!(job = job.name; "Job failed: {}", err);
+                (JobStatus::Failed, err.to_string())
+            }
+        };
+        job_run::Entity::update_many()
+            .col_expr(job_run::Column::Status, Expr::value(status))
+            .col_expr(job_run::Column::Detail, Expr::value(detail))
+            .col_expr(
+                job_run::Column::FinishedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(job_run::Column::Job.eq(job.name))
+            .filter(job_run::Column::ScheduledAt.eq(scheduled_at))
+            .exec(db)
+            .await?;
+        METRICS
+            .job_runs
+            .with_label_values(&[job.name, &status.to_value()])
+            .inc();
+        Ok(Some(status))
+    }
+
+    /// 抢占 Redis 中的锁。没有配置 Redis 或 Redis 不可用时交由数据库判断。
+    async fn lock(&self, job: &str, scheduled_at: NaiveDateTime) -> bool {
+        let Some(redis) = self.context.redis.as_ref() else {
+            return true;
+        };
+        let key = format!("job_lock:{}:{}", job, scheduled_at.timestamp());
+        let result: redis::RedisResult<Option<String>> = redis::cmd("SET")
+            .arg(key)
+            .arg(&self.instance)
+            .arg("NX")
+            .arg("PX")
+            .arg(LOCK_TTL_MS)
+            .query_async(&mut *redis.lock().await)
+            .await;
+        match result {
+            Ok(reply) => reply.is_some(),
+            Err(err) => {
+                warn!(job = job; "Unable to acquire job lock from redis: {}", err);
+                true
+            }
+        }
+    }
+
+    /// 将当前实例仍在执行的任务记为已中止。
+    async fn mark_interrupted(&self) -> Result<(), DbErr> {
+        job_run::Entity::update_many()
+            .col_expr(job_run::Column::Status, Expr::value(JobStatus::Interrupted))
+            .col_expr(
+                job_run::Column::FinishedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(job_run::Column::Instance.eq(self.instance.as_str()))
+            .filter(job_run::Column::Status.eq(JobStatus::Running))
+            .exec(&self.context.db)
+            .await?;
+        Ok(())
+    }
+}
+
+pub struct SchedulerHandle {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<Arc<Scheduler>>,
+}
+
+impl SchedulerHandle {
+    /// 停止调度新的任务，并等待正在执行的任务结束，最多等待 `server.shutdown_timeout` 秒。
+    pub async fn shutdown(self) {
+        let _ = self.stop.send(true);
+        if let Err(err) = self.task.await {
+            error!("Scheduler panicked: {}", err);
+        }
+    }
+}
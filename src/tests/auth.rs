@@ -0,0 +1,421 @@
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{call_service, TestRequest};
+use entity::user;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use serde_json::json;
+
+use crate::contants::{user_type, API_KEY_HEADER};
+
+use super::{spawn_app, PASSWORD};
+
+#[actix_web::test]
+async fn only_one_super_admin_can_register_freely() {
+    let app = spawn_app(false).await;
+    app.super_admin().await;
+
+    let (status, _) = app
+        .call(
+            Method::POST,
+            "/user/register",
+            None,
+            Some(json!({
+                "password": PASSWORD,
+                "role": user_type::SUPER_ADMIN,
+                "real_name": "second",
+                "sex": "Female",
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn registering_admins_requires_super_admin() {
+    let app = spawn_app(false).await;
+    let (_, super_token) = app.super_admin().await;
+    let (_, admin_token) = app.admin(&super_token).await;
+
+    let new_admin = json!({
+        "password": PASSWORD,
+        "role": user_type::ADMIN,
+        "real_name": "admin",
+        "sex": "Male",
+    });
+    let (status, _) = app
+        .call(
+            Method::POST,
+            "/user/register",
+            None,
+            Some(new_admin.clone()),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.post("/user/register", &admin_token, new_admin).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn weak_passwords_and_unknown_roles_are_rejected() {
+    let app = spawn_app(false).await;
+    let (status, _) = app
+        .call(
+            Method::POST,
+            "/user/register",
+            None,
+            Some(json!({
+                "password": "short",
+                "role": user_type::SUPER_ADMIN,
+                "real_name": "weak",
+                "sex": "Male",
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, _) = app
+        .call(
+            Method::POST,
+            "/user/register",
+            None,
+            Some(json!({
+                "password": PASSWORD,
+                "role": "cashier",
+                "real_name": "unknown",
+                "sex": "Male",
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn login_checks_credentials() {
+    let app = spawn_app(false).await;
+    let (id, _) = app.super_admin().await;
+
+    let (status, _) = app
+        .call(
+            Method::POST,
+            "/user/login",
+            None,
+            Some(json!({ "id": id, "password": "wrong password" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::IM_A_TEAPOT);
+    let (status, _) = app
+        .call(
+            Method::POST,
+            "/user/login",
+            None,
+            Some(json!({ "id": id + 1, "password": PASSWORD })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.call(Method::GET, "/user/me", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn admins_only_see_themselves() {
+    let app = spawn_app(false).await;
+    let (super_id, super_token) = app.super_admin().await;
+    let (admin_id, admin_token) = app.admin(&super_token).await;
+
+    let (status, body) = app.get("/user/me", &admin_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["id"], admin_id);
+    let (status, _) = app.get(&format!("/user/{}", admin_id), &admin_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.get(&format!("/user/{}", super_id), &admin_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.get("/user?page=0&page_size=10", &admin_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app.get(&format!("/user/{}", admin_id), &super_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.get("/user?page=0&page_size=10", &super_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn admins_cannot_promote_themselves() {
+    let app = spawn_app(false).await;
+    let (_, super_token) = app.super_admin().await;
+    let (admin_id, admin_token) = app.admin(&super_token).await;
+
+    let (status, _) = app
+        .call(
+            Method::PATCH,
+            &format!("/user/{}", admin_id),
+            Some(&admin_token),
+            Some(json!({ "role": user_type::SUPER_ADMIN })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = app
+        .call(
+            Method::PATCH,
+            &format!("/user/{}", admin_id),
+            Some(&admin_token),
+            Some(json!({ "real_name": "renamed" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["real_name"], "renamed");
+}
+
+#[actix_web::test]
+async fn the_last_super_admin_cannot_be_deleted() {
+    let app = spawn_app(false).await;
+    let (super_id, super_token) = app.super_admin().await;
+    let (admin_id, _) = app.admin(&super_token).await;
+
+    let (status, _) = app
+        .call(
+            Method::DELETE,
+            &format!("/user/{}", super_id),
+            Some(&super_token),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = app
+        .call(
+            Method::DELETE,
+            &format!("/user/{}", admin_id),
+            Some(&super_token),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app
+        .call(
+            Method::POST,
+            "/user/login",
+            None,
+            Some(json!({ "id": admin_id, "password": PASSWORD })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn the_last_super_admin_cannot_be_demoted() {
+    let app = spawn_app(false).await;
+    let (super_id, super_token) = app.super_admin().await;
+    let (admin_id, _) = app.admin(&super_token).await;
+    // 有另一个超级管理员后才可以降级
+    for (id, role, expected) in [
+        (super_id, user_type::ADMIN, StatusCode::CONFLICT),
+        (admin_id, user_type::SUPER_ADMIN, StatusCode::OK),
+        (super_id, user_type::ADMIN, StatusCode::OK),
+    ] {
+        let (status, body) = app
+            .call(
+                Method::PATCH,
+                &format!("/user/{}", id),
+                Some(&super_token),
+                Some(json!({ "role": role })),
+            )
+            .await;
+        assert_eq!(status, expected, "{}", body);
+    }
+}
+
+#[actix_web::test]
+async fn super_admins_demoting_each_other_keep_one() {
+    let app = spawn_app(false).await;
+    let (first_id, first_token) = app.super_admin().await;
+    let (second_id, _) = app.admin(&first_token).await;
+    let (status, _) = app
+        .call(
+            Method::PATCH,
+            &format!("/user/{}", second_id),
+            Some(&first_token),
+            Some(json!({ "role": user_type::SUPER_ADMIN })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (second_token, _) = app.login(second_id).await;
+
+    // 同时降级对方，只有一个请求能成功
+    let demote = |id: i32, token: String| {
+        let app = &app;
+        async move {
+            let path = format!("/user/{}", id);
+            let body = json!({ "role": user_type::ADMIN });
+            app.call(Method::PATCH, &path, Some(&token), Some(body))
+                .await
+                .0
+        }
+    };
+    let (first, second) = tokio::join!(
+        demote(second_id, first_token),
+        demote(first_id, second_token)
+    );
+    // 后执行的请求可能因为自己已被降级而被拒绝，也可能因为对方是最后一个超级管理员而冲突
+    assert_eq!(
+        [first, second]
+            .iter()
+            .filter(|status| **status == StatusCode::OK)
+            .count(),
+        1
+    );
+    let super_admins = user::Entity::find()
+        .filter(user::Column::Role.eq(user_type::SUPER_ADMIN))
+        .count(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(super_admins, 1);
+}
+
+#[actix_web::test]
+async fn logout_revokes_issued_tokens() {
+    for with_redis in [false, true] {
+        let app = spawn_app(with_redis).await;
+        let (id, token) = app.super_admin().await;
+
+        // 第一次请求后用户信息被缓存到 Redis 中
+        let (status, _) = app.get("/user/me", &token).await;
+        assert_eq!(status, StatusCode::OK);
+        if let Some(ref redis) = app.redis {
+            assert!(redis.contains(id));
+        }
+
+        let (status, _) = app.post("/user/logout", &token, json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        if let Some(ref redis) = app.redis {
+            assert!(!redis.contains(id));
+        }
+        let (status, _) = app.get("/user/me", &token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[actix_web::test]
+async fn refresh_tokens_rotate_and_detect_reuse() {
+    let app = spawn_app(false).await;
+    let (id, _) = app.super_admin().await;
+    let (access, refresh) = app.login(id).await;
+
+    // Access Token 不能用于刷新
+    let (status, _) = app.post("/user/refresh", &access, json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = app.post("/user/refresh", &refresh, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let rotated = body["refresh_token"].as_str().unwrap().to_owned();
+
+    // 重复使用旧的 Refresh Token 会吊销整个家族
+    let (status, _) = app.post("/user/refresh", &refresh, json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.post("/user/refresh", &rotated, json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.get("/user/me", &access).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn changing_password_requires_the_old_one() {
+    let app = spawn_app(false).await;
+    let (_, token) = app.super_admin().await;
+
+    let (status, _) = app
+        .post(
+            "/user/me/password",
+            &token,
+            json!({ "old_password": "wrong password", "new_password": "N3w passw0rd" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::IM_A_TEAPOT);
+    // 不能重复使用最近的密码
+    let (status, _) = app
+        .post(
+            "/user/me/password",
+            &token,
+            json!({ "old_password": PASSWORD, "new_password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, body) = app
+        .post(
+            "/user/me/password",
+            &token,
+            json!({ "old_password": PASSWORD, "new_password": "N3w passw0rd" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.get("/user/me", &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app
+        .get("/user/me", body["access_token"].as_str().unwrap())
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn changing_or_resetting_a_password_revokes_api_keys() {
+    let app = spawn_app(false).await;
+    let (_, super_token) = app.super_admin().await;
+    let (admin_id, admin_token) = app.admin(&super_token).await;
+    let create_key = |token: String| {
+        let app = &app;
+        async move {
+            let (status, body) = app
+                .post(
+                    "/user/me/api_keys",
+                    &token,
+                    json!({ "name": "ci", "scopes": ["read"] }),
+                )
+                .await;
+            assert_eq!(status, StatusCode::OK);
+            body["key"].as_str().unwrap().to_owned()
+        }
+    };
+    let use_key = |key: String| {
+        let req = TestRequest::get()
+            .uri("/stats/book")
+            .insert_header((API_KEY_HEADER, key))
+            .to_request();
+        call_service(&app.service, req)
+    };
+
+    let own_key = create_key(super_token.clone()).await;
+    let admin_key = create_key(admin_token).await;
+    assert_eq!(use_key(own_key.clone()).await.status(), StatusCode::OK);
+    assert_eq!(use_key(admin_key.clone()).await.status(), StatusCode::OK);
+
+    let (status, body) = app
+        .post(
+            "/user/me/password",
+            &super_token,
+            json!({ "old_password": PASSWORD, "new_password": "N3w passw0rd" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let super_token = body["access_token"].as_str().unwrap().to_owned();
+    assert_eq!(use_key(own_key).await.status(), StatusCode::UNAUTHORIZED);
+    // 其他用户的 API Key 不受影响
+    assert_eq!(use_key(admin_key.clone()).await.status(), StatusCode::OK);
+
+    let (_, body) = app
+        .post(
+            &format!("/user/{}/reset_password", admin_id),
+            &super_token,
+            json!({}),
+        )
+        .await;
+    let (status, _) = app
+        .call(
+            Method::POST,
+            "/user/reset_password",
+            None,
+            Some(json!({ "token": body["token"], "new_password": "R3set passw0rd" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(use_key(admin_key).await.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use jsonwebtoken::Algorithm;
+
+use crate::api::stats::cost::CostMethod;
+use crate::config::Config;
+
+/// 一个可以通过校验的数据库 URL，取决于启用的数据库后端。
+fn db_url() -> &'static str {
+    if cfg!(feature = "mysql") {
+        "mysql://localhost/midpj"
+    } else if cfg!(feature = "postgres") {
+        "postgres://localhost/midpj"
+    } else {
+        "sqlite::memory:"
+    }
+}
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    move |key| vars.get(key).cloned()
+}
+
+#[test]
+fn file_values_and_defaults() {
+    let config = Config::from_toml(
+        r#"
+        [server]
+        port = 9090
+        workers = 2
+
+        [database]
+        max_connections = 5
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.server.host, "0.0.0.0");
+    assert_eq!(config.server.port, 9090);
+    assert_eq!(config.server.workers, Some(2));
+    assert_eq!(config.auth.access_token_lifetime, 1800);
+    assert_eq!(config.database.max_connections, Some(5));
+    assert!(config.redis.url.is_none());
+
+    assert!(Config::from_toml("[server]\nprot = 9090").is_err());
+    // 示例配置可以被正确解析
+    Config::from_toml(include_str!("../../config.example.toml")).unwrap();
+}
+
+#[test]
+fn environment_overrides_the_file() {
+    let mut config = Config::from_toml("[server]\nport = 9090\n[cors]\nallow_all = true").unwrap();
+    config
+        .apply_env(env(&[
+            ("LISTEN_PORT", "8081"),
+            ("ALLOW_ALL_CORS", "false"),
+            (
+                "CORS_ALLOWED_ORIGINS",
+                "https://a.example.com, https://b.example.com",
+            ),
+            ("DB_URL", db_url()),
+            ("REDIS_URL", ""),
+        ]))
+        .unwrap();
+    assert_eq!(config.server.port, 8081);
+    assert!(!config.cors.allow_all);
+    assert_eq!(
+        config.cors.allowed_origins,
+        ["https://a.example.com", "https://b.example.com"]
+    );
+    assert_eq!(config.database.url, db_url());
+    // 空的环境变量视为未设置
+    assert!(config.redis.url.is_none());
+
+    assert!(config.apply_env(env(&[("LISTEN_PORT", "http")])).is_err());
+}
+
+#[test]
+fn jwt_password_and_cost_settings_come_from_the_config() {
+    let mut config = Config::from_toml(
+        r#"
+        [jwt]
+        secret = "from-file"
+
+        [password]
+        min_length = 12
+
+        [stats]
+        cost_method = "fifo"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.jwt.algorithm, Algorithm::HS256);
+    assert_eq!(config.password.min_length, 12);
+    assert_eq!(config.password.min_classes, 2);
+    assert_eq!(config.stats.cost_method, CostMethod::Fifo);
+
+    config
+        .apply_env(env(&[
+            ("JWT_ALGORITHM", "RS256"),
+            ("JWT_KEY_ID", "k1"),
+            ("JWT_PUBLIC_KEYS", "k1=/keys/k1.pem, k0=/keys/k0.pem"),
+            ("PASSWORD_HISTORY", "0"),
+            ("COST_METHOD", "average"),
+        ]))
+        .unwrap();
+    assert_eq!(config.jwt.algorithm, Algorithm::RS256);
+    assert_eq!(config.jwt.key_id.as_deref(), Some("k1"));
+    assert_eq!(config.jwt.public_keys["k0"], "/keys/k0.pem");
+    assert_eq!(config.password.history, 0);
+    assert_eq!(config.stats.cost_method, CostMethod::Average);
+
+    // 无法识别的值不再被静默忽略
+    for vars in [
+        [("JWT_ALGORITHM", "HS999")],
+        [("JWT_PUBLIC_KEYS", "/keys/k1.pem")],
+        [("PASSWORD_MIN_LENGTH", "eight")],
+        [("COST_METHOD", "lifo")],
+    ] {
+        assert!(config.apply_env(env(&vars)).is_err(), "{:?}", vars);
+    }
+    assert!(Config::from_toml("[stats]\ncost_method = \"lifo\"").is_err());
+}
+
+#[test]
+fn invalid_configs_are_rejected() {
+    let valid = || {
+        let mut config = Config::default();
+        config.database.url = db_url().to_owned();
+        config.jwt.secret = Some("secret".to_owned());
+        config
+    };
+    valid().validate().unwrap();
+
+    assert!(Config::default().validate().is_err());
+    let cases: [fn(&mut Config); 16] = [
+        |c| c.server.workers = Some(0),
+        |c| c.auth.access_token_lifetime = 0,
+        |c| c.auth.refresh_token_lifetime = 60,
+        |c| c.auth.secret_key_length = 8,
+        |c| c.auth.secret_key_length = 256,
+        |c| c.jwt.secret = None,
+        |c| c.jwt.secret = Some(String::new()),
+        // 非对称算法需要私钥文件
+        |c| c.jwt.algorithm = Algorithm::RS256,
+        |c| {
+            c.jwt.algorithm = Algorithm::EdDSA;
+            c.jwt.key_id = Some("ed".to_owned());
+            c.jwt.private_key = Some("/nonexistent/ed25519.pem".to_owned());
+        },
+        |c| c.password.min_length = 0,
+        |c| c.password.min_classes = 5,
+        |c| c.cors.allowed_origins = vec!["https://example.com/".to_owned()],
+        |c| c.cors.allowed_methods = vec!["GET POST".to_owned()],
+        |c| {
+            c.cors.allow_all = true;
+            c.cors.allow_credentials = true;
+        },
+        |c| c.database.url = "oracle://localhost".to_owned(),
+        |c| {
+            c.database.min_connections = Some(5);
+            c.database.max_connections = Some(2);
+        },
+    ];
+    for case in cases {
+        let mut config = valid();
+        case(&mut config);
+        assert!(config.validate().is_err(), "{:?}", config);
+    }
+}
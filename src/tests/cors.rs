@@ -0,0 +1,111 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{web, App, HttpResponse};
+
+use crate::config::CorsConfig;
+use crate::utils::cors::{build, OriginPattern};
+
+#[test]
+fn origin_patterns() {
+    let exact = OriginPattern::parse("https://Example.com").unwrap();
+    assert!(exact.matches("https://example.com"));
+    assert!(!exact.matches("http://example.com"));
+    assert!(!exact.matches("https://a.example.com"));
+
+    let wildcard = OriginPattern::parse("https://*.example.com").unwrap();
+    assert!(wildcard.matches("https://a.example.com"));
+    assert!(wildcard.matches("https://a.b.example.com"));
+    assert!(!wildcard.matches("https://example.com"));
+    assert!(!wildcard.matches("https://evilexample.com"));
+    assert!(!wildcard.matches("https://a.example.com.evil.com"));
+    assert!(!wildcard.matches("https://a.example.com:8443"));
+    assert!(!wildcard.matches("http://a.example.com"));
+
+    let with_port = OriginPattern::parse("http://*.localhost:3000").unwrap();
+    assert!(with_port.matches("http://app.localhost:3000"));
+    assert!(!with_port.matches("http://app.localhost"));
+
+    for invalid in [
+        "example.com",
+        "ftp://example.com",
+        "https://example.com/",
+        "https://*",
+        "https://a.*.example.com",
+        "*",
+    ] {
+        assert!(OriginPattern::parse(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[actix_web::test]
+async fn preflight_follows_the_policy() {
+    let config = CorsConfig {
+        allowed_origins: vec![
+            "https://admin.example.com".to_owned(),
+            "https://*.staging.example.com".to_owned(),
+        ],
+        allowed_methods: vec!["GET".to_owned(), "POST".to_owned()],
+        allow_credentials: true,
+        max_age: Some(600),
+        ..Default::default()
+    };
+    let app = init_service(
+        App::new()
+            .wrap(build(&config))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    let preflight = |origin: &str, method: &str| {
+        TestRequest::default()
+            .method(actix_web::http::Method::OPTIONS)
+            .uri("/")
+            .insert_header((header::ORIGIN, origin))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, method))
+            .to_request()
+    };
+
+    for origin in [
+        "https://admin.example.com",
+        "https://pr-42.staging.example.com",
+    ] {
+        let resp = call_service(&app, preflight(origin, "POST")).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{}", origin);
+        let headers = resp.headers();
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            origin
+        );
+        assert_eq!(
+            headers
+                .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+                .unwrap(),
+            "true"
+        );
+        assert_eq!(headers.get(header::ACCESS_CONTROL_MAX_AGE).unwrap(), "600");
+    }
+
+    let resp = call_service(&app, preflight("https://evil.com", "POST")).await;
+    assert!(!resp
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    let resp = call_service(&app, preflight("https://admin.example.com", "DELETE")).await;
+    assert!(!resp
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+
+    // 实际请求暴露分页的总数请求头
+    let req = TestRequest::get()
+        .uri("/")
+        .insert_header((header::ORIGIN, "https://admin.example.com"))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let exposed = resp
+        .headers()
+        .get(header::ACCESS_CONTROL_EXPOSE_HEADERS)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_ascii_lowercase();
+    assert!(exposed.contains("x-item-count"));
+}
@@ -0,0 +1,221 @@
+// 只实现了服务器用到的少数命令的 Redis 替身，通过 RESP 协议与真正的客户端通信。
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::utils::rate_limit::TOKEN_BUCKET_SCRIPT;
+
+#[derive(Default)]
+struct Entry {
+    value: Vec<u8>,
+    expires_at: Option<Instant>,
+}
+
+type Store = Arc<Mutex<HashMap<Vec<u8>, Entry>>>;
+/// 通过 SCRIPT LOAD 加载的脚本的 SHA1
+type Scripts = Arc<Mutex<HashSet<Vec<u8>>>>;
+
+pub struct FakeRedis {
+    pub url: String,
+    store: Store,
+}
+
+impl FakeRedis {
+    /// 在随机端口上启动服务器。
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        let store = Store::default();
+        let shared = store.clone();
+        let scripts = Scripts::default();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, shared.clone(), scripts.clone()));
+            }
+        });
+        Self { url, store }
+    }
+
+    /// 连接到服务器，返回与 `main` 中相同类型的连接。
+    pub async fn connect(&self) -> redis::aio::MultiplexedConnection {
+        redis::Client::open(self.url.as_str())
+            .unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap()
+    }
+
+    pub fn contains(&self, key: impl ToString) -> bool {
+        let store = self.store.lock().unwrap();
+        store
+            .get(key.to_string().as_bytes())
+            .map_or(false, |entry| !expired(entry))
+    }
+}
+
+fn expired(entry: &Entry) -> bool {
+    entry.expires_at.map_or(false, |at| at <= Instant::now())
+}
+
+async fn serve(stream: TcpStream, store: Store, scripts: Scripts) {
+    let mut stream = BufReader::new(stream);
+    while let Some(args) = read_command(&mut stream).await {
+        let reply = execute(&args, &store, &scripts);
+        if stream.get_mut().write_all(&reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// 读取一条以 RESP 数组表示的命令，连接关闭时返回 `None`。
+async fn read_command(stream: &mut BufReader<TcpStream>) -> Option<Vec<Vec<u8>>> {
+    let count = read_header(stream, b'*').await?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len = read_header(stream, b'$').await?;
+        let mut arg = vec![0; len + 2];
+        stream.read_exact(&mut arg).await.ok()?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Some(args)
+}
+
+async fn read_header(stream: &mut BufReader<TcpStream>, prefix: u8) -> Option<usize> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await.ok()? == 0 {
+        return None;
+    }
+    let line = line.trim_end();
+    if line.as_bytes().first() != Some(&prefix) {
+        return None;
+    }
+    line[1..].parse().ok()
+}
+
+fn bulk(value: Option<&[u8]>) -> Vec<u8> {
+    match value {
+        Some(value) => {
+            let mut reply = format!("${}\r\n", value.len()).into_bytes();
+            reply.extend_from_slice(value);
+            reply.extend_from_slice(b"\r\n");
+            reply
+        }
+        None => b"$-1\r\n".to_vec(),
+    }
+}
+
+fn integer(value: i64) -> Vec<u8> {
+    format!(":{}\r\n", value).into_bytes()
+}
+
+fn error(message: &str) -> Vec<u8> {
+    format!("-ERR {}\r\n", message).into_bytes()
+}
+
+fn parse<T: std::str::FromStr>(arg: &[u8]) -> Option<T> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+fn execute(args: &[Vec<u8>], store: &Store, scripts: &Scripts) -> Vec<u8> {
+    let mut store = store.lock().unwrap();
+    store.retain(|_, entry| !expired(entry));
+    let command = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+    match (command.as_str(), &args[1..]) {
+        ("PING", _) => b"+PONG\r\n".to_vec(),
+        ("GET", [key]) => bulk(store.get(key).map(|entry| entry.value.as_slice())),
+        ("SET", [key, value, options @ ..]) => {
+            let mut expires_at = None;
+            let mut only_new = false;
+            let mut options = options.iter();
+            while let Some(option) = options.next() {
+                let option = String::from_utf8_lossy(option).to_ascii_uppercase();
+                let unit = match option.as_str() {
+                    "NX" => {
+                        only_new = true;
+                        continue;
+                    }
+                    "EX" => 1000,
+                    "PX" => 1,
+                    _ => return error("syntax error"),
+                };
+                match options.next().and_then(|arg| parse::<u64>(arg)) {
+                    Some(ttl) => {
+                        expires_at = Some(Instant::now() + Duration::from_millis(ttl * unit))
+                    }
+                    None => return error("value is not an integer or out of range"),
+                }
+            }
+            if only_new && store.contains_key(key) {
+                return bulk(None);
+            }
+            store.insert(
+                key.clone(),
+                Entry {
+                    value: value.clone(),
+                    expires_at,
+                },
+            );
+            b"+OK\r\n".to_vec()
+        }
+        // 只支持限流使用的令牌桶脚本，用 Rust 实现脚本的逻辑
+        ("SCRIPT", [sub, body]) if sub.eq_ignore_ascii_case(b"LOAD") => {
+            if body != TOKEN_BUCKET_SCRIPT.as_bytes() {
+                return error("unsupported script");
+            }
+            let sha = redis::Script::new(TOKEN_BUCKET_SCRIPT)
+                .get_hash()
+                .to_owned();
+            scripts.lock().unwrap().insert(sha.clone().into_bytes());
+            bulk(Some(sha.as_bytes()))
+        }
+        ("EVALSHA", [sha, ..]) if !scripts.lock().unwrap().contains(sha) => {
+            b"-NOSCRIPT No matching script. Please use EVAL.\r\n".to_vec()
+        }
+        ("EVALSHA", [_, num_keys, key, capacity, per_ms, now]) if num_keys == b"1" => {
+            let (Some(capacity), Some(per_ms), Some(now)) = (
+                parse::<f64>(capacity),
+                parse::<f64>(per_ms),
+                parse::<i64>(now),
+            ) else {
+                return error("value is not a number");
+            };
+            let state = store.get(key).and_then(|entry| {
+                let (tokens, last) = std::str::from_utf8(&entry.value).ok()?.split_once(':')?;
+                Some((tokens.parse::<f64>().ok()?, last.parse::<i64>().ok()?))
+            });
+            let mut tokens = match state {
+                Some((tokens, last)) => {
+                    (tokens + (now - last).max(0) as f64 * per_ms).min(capacity)
+                }
+                None => capacity,
+            };
+            let allowed = tokens >= 1.0;
+            if allowed {
+                tokens -= 1.0;
+            }
+            let ttl = ((capacity - tokens) / per_ms).ceil() as u64 + 1;
+            store.insert(
+                key.clone(),
+                Entry {
+                    value: format!("{}:{}", tokens, now).into_bytes(),
+                    expires_at: Some(Instant::now() + Duration::from_millis(ttl)),
+                },
+            );
+            let mut reply = b"*2\r\n".to_vec();
+            reply.extend(integer(allowed as i64));
+            reply.extend(bulk(Some(tokens.to_string().as_bytes())));
+            reply
+        }
+        ("DEL", keys) => integer(
+            keys.iter()
+                .filter(|key| store.remove(*key).is_some())
+                .count() as i64,
+        ),
+        _ => error(&format!("unknown command '{}'", command)),
+    }
+}
@@ -0,0 +1,48 @@
+use actix_web::http::{Method, StatusCode};
+use migration::{Migrator, MigratorTrait};
+
+use super::spawn_app;
+
+#[actix_web::test]
+async fn liveness_and_version() {
+    let app = spawn_app(false).await;
+    let (status, _) = app.call(Method::GET, "/healthz", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = app.call(Method::GET, "/version", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert!(!body["git_commit"].as_str().unwrap().is_empty());
+    assert!(!body["build_time"].as_str().unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn readiness_reports_each_dependency() {
+    for with_redis in [false, true] {
+        let app = spawn_app(with_redis).await;
+        let (status, body) = app.call(Method::GET, "/readyz", None, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["ready"], true);
+        assert_eq!(body["database"]["status"], "ok");
+        assert!(body["database"]["latency_ms"].is_number());
+        assert_eq!(body["migrations"]["status"], "ok");
+        let redis = if with_redis { "ok" } else { "disabled" };
+        assert_eq!(body["redis"]["status"], redis);
+    }
+}
+
+#[actix_web::test]
+async fn pending_migrations_are_not_ready() {
+    let app = spawn_app(false).await;
+    Migrator::down(&app.db, Some(1)).await.unwrap();
+
+    let (status, body) = app.call(Method::GET, "/readyz", None, None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["database"]["status"], "ok");
+    assert_eq!(body["migrations"]["status"], "error");
+    assert!(body["migrations"]["detail"]
+        .as_str()
+        .unwrap()
+        .starts_with("1 pending"));
+}
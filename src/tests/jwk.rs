@@ -0,0 +1,102 @@
+use jsonwebtoken::jwk::AlgorithmParameters;
+use jsonwebtoken::Algorithm;
+use serde_json::{json, Value};
+
+use crate::utils::jwk::JwtKeys;
+
+const RSA_OLD: &[u8] = include_bytes!("fixtures/rsa_old.pem");
+// SPKI 格式
+const RSA_OLD_PUB: &[u8] = include_bytes!("fixtures/rsa_old.pub.pem");
+const RSA_NEW: &[u8] = include_bytes!("fixtures/rsa_new.pem");
+// PKCS#1 格式
+const RSA_NEW_PUB: &[u8] = include_bytes!("fixtures/rsa_new.pub.pem");
+const ED25519: &[u8] = include_bytes!("fixtures/ed25519.pem");
+const ED25519_PUB: &[u8] = include_bytes!("fixtures/ed25519.pub.pem");
+
+/// `openssl rsa -noout -modulus` 得到的模数
+const RSA_OLD_N: &str = "s1Wvow1DbALfiTusrNUx2y4kyW1PCLluv72nEYFU1CGpwC3bHWHxbf3-hWr9WGoWBjy06NTOXkVPnEGsMYSmT27OUoZ5qPc6akDLHx1Ai2_GphlMEpyc3O8NljGs0TD-XFVdkgtISE8WrwsHdhInltbVcSpWjfipNJM_kx9SgiArrSdbvIxXR6rsgvy9EMclRs8jH4gjwUvgByovu5ZTUlWlRh8_b9HiHt_trThDidgUUaRtCoc06lq0CBfG6kfKxYCH3RIPSR0ovVpZkikJPuFXkekPfIHgp_uuc4WzcXLyy8DP9gemb8ntoXII6Ze3Sw0evvO4_qtoGn-QaTcxGQ";
+const ED25519_X: &str = "Z3tqL_AjGGAcEuZk9zWhVh_GqWBgdaDrF3235HBg0EA";
+
+fn claims() -> Value {
+    json!({ "sub": "1", "exp": 4_102_444_800u64 })
+}
+
+fn keys(private_key: &[u8], kid: &str, public_keys: &[(&str, &[u8])]) -> JwtKeys {
+    let public_keys: Vec<_> = public_keys
+        .iter()
+        .map(|(kid, pem)| (kid.to_string(), pem.to_vec()))
+        .collect();
+    JwtKeys::asymmetric(Algorithm::RS256, private_key, kid.to_owned(), &public_keys).unwrap()
+}
+
+#[test]
+fn rotated_rsa_keys_still_verify_old_tokens() {
+    let before = keys(RSA_OLD, "old", &[("old", RSA_OLD_PUB)]);
+    let old_token = before.encode(&claims()).unwrap();
+
+    // 轮换后用新密钥签名，旧公钥继续保留
+    let after = keys(
+        RSA_NEW,
+        "new",
+        &[("old", RSA_OLD_PUB), ("new", RSA_NEW_PUB)],
+    );
+    let new_token = after.encode(&claims()).unwrap();
+    assert_eq!(after.decode::<Value>(&old_token).unwrap(), claims());
+    assert_eq!(after.decode::<Value>(&new_token).unwrap(), claims());
+
+    // 旧公钥被移除后，旧 Token 的 kid 不再被接受
+    let retired = keys(RSA_NEW, "new", &[("new", RSA_NEW_PUB)]);
+    assert!(retired.decode::<Value>(&old_token).is_err());
+    assert!(before.decode::<Value>(&new_token).is_err());
+
+    let jwks = serde_json::to_value(after.jwks()).unwrap();
+    let kids: Vec<_> = jwks["keys"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|key| key["kid"].as_str().unwrap())
+        .collect();
+    assert_eq!(kids, ["old", "new"]);
+    match &after.jwks().keys[0].algorithm {
+        AlgorithmParameters::RSA(rsa) => {
+            assert_eq!(rsa.n, RSA_OLD_N);
+            assert_eq!(rsa.e, "AQAB");
+        }
+        params => panic!("unexpected parameters {:?}", params),
+    }
+    assert_eq!(jwks["keys"][0]["alg"], "RS256");
+    assert_eq!(jwks["keys"][0]["use"], "sig");
+}
+
+#[test]
+fn ed25519_keys_are_published_in_jwks() {
+    let public_keys = [("ed".to_owned(), ED25519_PUB.to_vec())];
+    let keys =
+        JwtKeys::asymmetric(Algorithm::EdDSA, ED25519, "ed".to_owned(), &public_keys).unwrap();
+    let token = keys.encode(&claims()).unwrap();
+    assert_eq!(keys.decode::<Value>(&token).unwrap(), claims());
+
+    let jwks = serde_json::to_value(keys.jwks()).unwrap();
+    assert_eq!(jwks["keys"][0]["kty"], "OKP");
+    assert_eq!(jwks["keys"][0]["crv"], "Ed25519");
+    assert_eq!(jwks["keys"][0]["x"], ED25519_X);
+}
+
+#[test]
+fn invalid_key_configurations_are_rejected() {
+    let rsa_pub = [("old".to_owned(), RSA_OLD_PUB.to_vec())];
+    // 签名密钥的公钥缺失
+    assert!(JwtKeys::asymmetric(Algorithm::RS256, RSA_OLD, "new".to_owned(), &rsa_pub).is_err());
+    // 公钥与算法不匹配
+    let ed_pub = [("ed".to_owned(), ED25519_PUB.to_vec())];
+    assert!(JwtKeys::asymmetric(Algorithm::RS256, RSA_OLD, "ed".to_owned(), &ed_pub).is_err());
+    let pkcs1 = [("new".to_owned(), RSA_NEW_PUB.to_vec())];
+    assert!(JwtKeys::asymmetric(Algorithm::EdDSA, ED25519, "new".to_owned(), &pkcs1).is_err());
+    assert!(JwtKeys::asymmetric(Algorithm::HS256, RSA_OLD, "old".to_owned(), &rsa_pub).is_err());
+
+    // 对称密钥不会公开
+    let symmetric = JwtKeys::symmetric(b"secret", Some("hs".to_owned()));
+    assert!(symmetric.jwks().keys.is_empty());
+    let token = symmetric.encode(&claims()).unwrap();
+    assert_eq!(symmetric.decode::<Value>(&token).unwrap(), claims());
+}
@@ -0,0 +1,84 @@
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, read_body, TestRequest};
+use log::{Level, Record};
+use serde_json::Value;
+
+use crate::contants::{REQUEST_ID_HEADER, TOKEN_ID_LENGTH};
+use crate::utils::logging::{render, request_id, set_user_id, RequestContext};
+
+use super::spawn_app;
+
+#[actix_web::test]
+async fn request_id_is_echoed_or_generated() {
+    let app = spawn_app(false).await;
+    let req = TestRequest::get()
+        .uri("/healthz")
+        .insert_header((REQUEST_ID_HEADER, "client-id.1"))
+        .to_request();
+    let resp = call_service(&app.service, req).await;
+    assert_eq!(
+        resp.headers().get(REQUEST_ID_HEADER).unwrap(),
+        "client-id.1"
+    );
+
+    // 不合法的编号会被替换
+    for header in [None, Some("bad id!")] {
+        let mut req = TestRequest::get().uri("/healthz");
+        if let Some(header) = header {
+            req = req.insert_header((REQUEST_ID_HEADER, header));
+        }
+        let resp = call_service(&app.service, req.to_request()).await;
+        let id = resp.headers().get(REQUEST_ID_HEADER).unwrap();
+        assert_eq!(id.len(), TOKEN_ID_LENGTH);
+    }
+}
+
+#[actix_web::test]
+async fn error_body_contains_request_id() {
+    let app = spawn_app(false).await;
+    let req = TestRequest::get().uri("/user/me").to_request();
+    let resp = call_service(&app.service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let id = resp.headers().get(REQUEST_ID_HEADER).unwrap().clone();
+    let body: Value = serde_json::from_slice(&read_body(resp).await).unwrap();
+    assert_eq!(body["request_id"], id.to_str().unwrap());
+
+    // 成功的响应不带请求编号
+    let (_, body) = app
+        .call(actix_web::http::Method::GET, "/healthz", None, None)
+        .await;
+    assert!(body.get("request_id").is_none());
+}
+
+#[actix_web::test]
+async fn lines_include_request_context() {
+    let kvs: &[(&str, i32)] = &[("count", 3)];
+    let line = || {
+        render(
+            &Record::builder()
+                .args(format_args!("hello"))
+                .level(Level::Warn)
+                .target("mid")
+                .key_values(&kvs)
+                .build(),
+        )
+    };
+
+    let line = RequestContext::new("req-1".to_owned())
+        .scope(async {
+            assert_eq!(request_id().as_deref(), Some("req-1"));
+            set_user_id(7);
+            line()
+        })
+        .await;
+    let line: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(line["level"], "WARN");
+    assert_eq!(line["target"], "mid");
+    assert_eq!(line["message"], "hello");
+    assert_eq!(line["request_id"], "req-1");
+    assert_eq!(line["user_id"], 7);
+    assert_eq!(line["count"], 3);
+    assert!(line["timestamp"].as_str().unwrap().ends_with('Z'));
+
+    assert!(request_id().is_none());
+}
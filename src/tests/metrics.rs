@@ -0,0 +1,74 @@
+use actix_web::http::{Method, StatusCode};
+
+use super::{spawn_app, spawn_app_with, test_config};
+
+/// 在 Prometheus 文本中查找带有全部给定标签的样本值。其他测试并行运行，指标只能按下限断言。
+fn sample(text: &str, name: &str, labels: &[&str]) -> f64 {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.rsplit_once(' '))
+        .find(|(series, _)| {
+            let series_name = series.split('{').next().unwrap();
+            series_name == name && labels.iter().all(|label| series.contains(label))
+        })
+        .map_or(0.0, |(_, value)| value.parse().unwrap())
+}
+
+#[actix_web::test]
+async fn requests_cache_and_orders_are_counted() {
+    let app = spawn_app(true).await;
+    let (_, token) = app.super_admin().await;
+    app.stock(&token, "9787000000100", 5, 50.0).await;
+    app.sell(&token, "9787000000100", 2, 40.0).await;
+    for _ in 0..2 {
+        app.get("/user/me", &token).await;
+    }
+    let (status, _) = app.get("/no/such/route", &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = app.call(Method::GET, "/metrics", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let text = body.as_str().unwrap();
+
+    let pay = [
+        r#"method="POST""#,
+        r#"route="/sell/{id}/pay""#,
+        r#"status="200""#,
+    ];
+    assert!(sample(text, "http_requests_total", &pay) >= 1.0);
+    let me = [r#"method="GET""#, r#"route="/user/me""#];
+    assert!(sample(text, "http_request_duration_seconds_count", &me) >= 2.0);
+    let unmatched = [r#"route="unmatched""#, r#"status="404""#];
+    assert!(sample(text, "http_requests_total", &unmatched) >= 1.0);
+
+    // 第一次鉴权未命中缓存，之后的请求命中
+    assert!(sample(text, "user_cache_lookups_total", &[r#"result="miss""#]) >= 1.0);
+    assert!(sample(text, "user_cache_lookups_total", &[r#"result="hit""#]) >= 1.0);
+
+    let sell_paid = [r#"type="sell""#, r#"action="paid""#];
+    assert!(sample(text, "orders_total", &sell_paid) >= 1.0);
+    let stock_confirmed = [r#"type="stock""#, r#"action="confirmed""#];
+    assert!(sample(text, "orders_total", &stock_confirmed) >= 1.0);
+    assert!(sample(text, "books_sold_total", &[]) >= 2.0);
+}
+
+#[actix_web::test]
+async fn metrics_can_require_a_token_or_be_disabled() {
+    let mut config = test_config();
+    config.metrics.token = Some("scrape".to_owned());
+    let app = spawn_app_with(false, config).await;
+    let (status, _) = app.call(Method::GET, "/metrics", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.call(Method::GET, "/metrics", Some("wrong"), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app
+        .call(Method::GET, "/metrics", Some("scrape"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut config = test_config();
+    config.metrics.enabled = false;
+    let app = spawn_app_with(false, config).await;
+    let (status, _) = app.call(Method::GET, "/metrics", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
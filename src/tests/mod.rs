@@ -0,0 +1,246 @@
+// 集成测试：在进程内启动完整的 App，每个测试使用一个全新的 SQLite 内存数据库。
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{web, App};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{ConnectOptions, Database, DatabaseConnection};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use crate::api;
+use crate::config::Config;
+use crate::contants::user_type;
+use crate::utils::jwk::JwtKeys;
+use crate::utils::logging::RequestLogger;
+use crate::utils::metrics::RequestMetrics;
+use crate::utils::rate_limit::RateLimiter;
+
+use self::fake_redis::FakeRedis;
+
+mod auth;
+mod config;
+mod cors;
+mod fake_redis;
+mod health;
+mod jwk;
+mod logging;
+mod metrics;
+mod orders;
+mod paging;
+mod rate_limit;
+mod scheduler;
+mod stats;
+
+/// 测试用户统一使用的密码，满足默认的密码策略。
+pub const PASSWORD: &str = "Passw0rd!";
+
+pub struct TestApp<S> {
+    service: S,
+    pub db: DatabaseConnection,
+    pub redis: Option<FakeRedis>,
+}
+
+/// 测试使用的配置：默认配置加上固定的 JWT 密钥。
+pub fn test_config() -> Config {
+    let mut config = Config::default();
+    config.jwt.secret = Some("test_secret".to_owned());
+    config
+}
+
+/// 在进程内启动服务器。`with_redis` 为 `true` 时同时启动一个 Redis 替身。
+///
+/// 测试会频繁登录，因此关闭了限流。
+pub async fn spawn_app(
+    with_redis: bool,
+) -> TestApp<impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>> {
+    let mut config = test_config();
+    config.rate_limit.enabled = false;
+    spawn_app_with(with_redis, config).await
+}
+
+/// 使用指定的配置启动服务器。
+pub async fn spawn_app_with(
+    with_redis: bool,
+    config: Config,
+) -> TestApp<impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>> {
+    let keys = JwtKeys::from_config(&config.jwt).unwrap();
+
+    // 内存数据库只能使用一个连接，否则每个连接各自拥有一个独立的数据库
+    let mut options = ConnectOptions::new("sqlite::memory:".to_owned());
+    options
+        .max_connections(1)
+        .min_connections(1)
+        .sqlx_logging(false);
+    let db = Database::connect(options).await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    let redis = match with_redis {
+        true => Some(FakeRedis::start().await),
+        false => None,
+    };
+    let redis_conn = match redis {
+        Some(ref redis) => Some(Mutex::new(redis.connect().await)),
+        None => None,
+    };
+
+    let service = init_service(
+        App::new()
+            .wrap(RateLimiter::new(&config.rate_limit))
+            .wrap(RequestMetrics)
+            .wrap(RequestLogger)
+            .configure(api::configure())
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(redis_conn))
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(keys)),
+    )
+    .await;
+    TestApp { service, db, redis }
+}
+
+impl<S> TestApp<S>
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+{
+    /// 发送请求，返回状态码和解析为 JSON 的响应体。响应体不是 JSON 时返回字符串。
+    pub async fn call(
+        &self,
+        method: Method,
+        path: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut req = TestRequest::default().method(method).uri(path);
+        if let Some(token) = token {
+            req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+        }
+        if let Some(body) = body {
+            req = req.set_json(body);
+        }
+        let resp = call_service(&self.service, req.to_request()).await;
+        let status = resp.status();
+        let body = read_body(resp).await;
+        let body = serde_json::from_slice(&body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+        (status, body)
+    }
+
+    pub async fn get(&self, path: &str, token: &str) -> (StatusCode, Value) {
+        self.call(Method::GET, path, Some(token), None).await
+    }
+
+    pub async fn post(&self, path: &str, token: &str, body: Value) -> (StatusCode, Value) {
+        self.call(Method::POST, path, Some(token), Some(body)).await
+    }
+
+    /// 注册用户，返回用户编号。注册超级管理员时 `token` 为空。
+    pub async fn register(&self, role: &str, token: Option<&str>) -> i32 {
+        let (status, body) = self
+            .call(
+                Method::POST,
+                "/user/register",
+                token,
+                Some(json!({
+                    "password": PASSWORD,
+                    "role": role,
+                    "real_name": format!("{} user", role),
+                    "sex": "NonBinary",
+                })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "register failed: {}", body);
+        body["id"].as_i64().unwrap() as i32
+    }
+
+    /// 登录，返回 Access Token 和 Refresh Token。
+    pub async fn login(&self, id: i32) -> (String, String) {
+        let (status, body) = self
+            .call(
+                Method::POST,
+                "/user/login",
+                None,
+                Some(json!({ "id": id, "password": PASSWORD })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "login failed: {}", body);
+        (
+            body["access_token"].as_str().unwrap().to_owned(),
+            body["refresh_token"].as_str().unwrap().to_owned(),
+        )
+    }
+
+    /// 注册并登录第一个超级管理员，返回用户编号与 Access Token。
+    pub async fn super_admin(&self) -> (i32, String) {
+        let id = self.register(user_type::SUPER_ADMIN, None).await;
+        (id, self.login(id).await.0)
+    }
+
+    /// 由超级管理员注册并登录一个管理员，返回用户编号与 Access Token。
+    pub async fn admin(&self, super_token: &str) -> (i32, String) {
+        let id = self.register(user_type::ADMIN, Some(super_token)).await;
+        (id, self.login(id).await.0)
+    }
+
+    /// 创建一张待付款的进货订单，返回订单编号。书籍不存在时会被创建。
+    pub async fn create_stock(&self, token: &str, isbn: &str, count: i32, price: f32) -> i32 {
+        let (status, order) = self
+            .post(
+                "/stock",
+                token,
+                json!({
+                    "book_isbn": isbn,
+                    "total_price": price,
+                    "total_count": count,
+                    "title": format!("Book {}", isbn),
+                    "author": "Author",
+                    "publisher": "Publisher",
+                    "out_price": 20.0,
+                }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "stock failed: {}", order);
+        order["id"].as_i64().unwrap() as i32
+    }
+
+    /// 进货并完成付款与入库，返回订单编号。书籍不存在时会被创建。
+    pub async fn stock(&self, token: &str, isbn: &str, count: i32, price: f32) -> i32 {
+        let id = self.create_stock(token, isbn, count, price).await;
+        for action in ["pay", "confirm"] {
+            let (status, body) = self
+                .post(&format!("/stock/{}/{}", id, action), token, json!({}))
+                .await;
+            assert_eq!(status, StatusCode::OK, "{} stock failed: {}", action, body);
+        }
+        id
+    }
+
+    /// 将书籍从库存上架，再售出并付款，返回订单编号。
+    pub async fn sell(&self, token: &str, isbn: &str, count: i32, price: f32) -> i32 {
+        let (status, body) = self
+            .post(
+                &format!("/book/{}/put_on_shelf", isbn),
+                token,
+                json!({ "put_count": count }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "put on shelf failed: {}", body);
+        let (status, order) = self
+            .post(
+                "/sell",
+                token,
+                json!({ "book_isbn": isbn, "total_price": price, "total_count": count }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "sell failed: {}", order);
+        let id = order["id"].as_i64().unwrap() as i32;
+        let (status, body) = self
+            .post(&format!("/sell/{}/pay", id), token, json!({}))
+            .await;
+        assert_eq!(status, StatusCode::OK, "pay sell failed: {}", body);
+        id
+    }
+}
@@ -0,0 +1,235 @@
+use actix_web::http::StatusCode;
+use serde_json::{json, Value};
+
+use super::spawn_app;
+
+const ISBN: &str = "9787111111111";
+
+fn new_stock(count: i32) -> Value {
+    json!({
+        "book_isbn": ISBN,
+        "total_price": 50.0,
+        "total_count": count,
+        "title": "Rust",
+        "author": "Ferris",
+        "publisher": "Crab Press",
+        "out_price": 20.0,
+    })
+}
+
+#[actix_web::test]
+async fn stock_lifecycle() {
+    let app = spawn_app(false).await;
+    let (_, token) = app.super_admin().await;
+
+    let (status, _) = app.post("/stock", &token, new_stock(0)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    // 书籍不存在时必须提供书籍信息
+    let (status, _) = app
+        .post(
+            "/stock",
+            &token,
+            json!({ "book_isbn": ISBN, "total_price": 50.0, "total_count": 5 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, order) = app.post("/stock", &token, new_stock(5)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(order["status"], "Pending");
+    let id = order["id"].as_i64().unwrap();
+
+    // 付款前不能入库，付款后不能撤销
+    let (status, _) = app
+        .post(&format!("/stock/{}/confirm", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, order) = app
+        .post(&format!("/stock/{}/pay", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(order["status"], "StockPaid");
+    let (status, _) = app
+        .post(&format!("/stock/{}/pay", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = app
+        .post(&format!("/stock/{}/revoke", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, order) = app
+        .post(&format!("/stock/{}/confirm", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(order["status"], "Done");
+
+    let (status, books) = app
+        .get(&format!("/book?isbn={}&page=0&page_size=10", ISBN), &token)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(books[0]["inventory_count"], 5);
+    assert_eq!(books[0]["on_shelf_count"], 0);
+
+    let (status, transactions) = app.get("/transaction?page=0&page_size=10", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(transactions.as_array().unwrap().len(), 1);
+
+    // 已有的书籍再次进货时不需要书籍信息
+    let (status, order) = app
+        .post(
+            "/stock",
+            &token,
+            json!({ "book_isbn": ISBN, "total_price": 30.0, "total_count": 3 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let id = order["id"].as_i64().unwrap();
+    let (status, order) = app
+        .post(&format!("/stock/{}/revoke", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(order["status"], "Revoked");
+    let (status, _) = app
+        .post(&format!("/stock/{}/pay", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn sell_lifecycle() {
+    let app = spawn_app(false).await;
+    let (_, token) = app.super_admin().await;
+    app.stock(&token, ISBN, 5, 50.0).await;
+
+    let (status, _) = app
+        .post(
+            "/sell",
+            &token,
+            json!({ "book_isbn": "unknown", "total_price": 20.0, "total_count": 1 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, book) = app
+        .post(
+            &format!("/book/{}/put_on_shelf", ISBN),
+            &token,
+            json!({ "put_count": 3 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(book["inventory_count"], 2);
+    assert_eq!(book["on_shelf_count"], 3);
+    let (status, _) = app
+        .post(
+            &format!("/book/{}/put_on_shelf", ISBN),
+            &token,
+            json!({ "put_count": 3 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // 架上数量不足时付款失败，订单保持待付款状态
+    let (status, order) = app
+        .post(
+            "/sell",
+            &token,
+            json!({ "book_isbn": ISBN, "total_price": 80.0, "total_count": 4 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let too_many = order["id"].as_i64().unwrap();
+    let (status, _) = app
+        .post(&format!("/sell/{}/pay", too_many), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, orders) = app
+        .get(
+            &format!("/sell?id={}&page=0&page_size=10", too_many),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(orders[0]["status"], "Pending");
+
+    let (status, order) = app
+        .post(&format!("/sell/{}/revoke", too_many), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(order["status"], "Revoked");
+    let (status, _) = app
+        .post(&format!("/sell/{}/pay", too_many), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, order) = app
+        .post(
+            "/sell",
+            &token,
+            json!({ "book_isbn": ISBN, "total_price": 40.0, "total_count": 2 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let id = order["id"].as_i64().unwrap();
+    let (status, order) = app
+        .post(&format!("/sell/{}/pay", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(order["status"], "Done");
+    let (status, _) = app
+        .post(&format!("/sell/{}/revoke", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (_, books) = app
+        .get(&format!("/book?isbn={}&page=0&page_size=10", ISBN), &token)
+        .await;
+    assert_eq!(books[0]["inventory_count"], 2);
+    assert_eq!(books[0]["on_shelf_count"], 1);
+}
+
+#[actix_web::test]
+async fn orders_cannot_be_processed_as_the_other_type() {
+    let app = spawn_app(false).await;
+    let (_, token) = app.super_admin().await;
+
+    let (_, order) = app.post("/stock", &token, new_stock(5)).await;
+    let id = order["id"].as_i64().unwrap();
+    let (status, _) = app
+        .post(&format!("/sell/{}/pay", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = app
+        .post(&format!("/sell/{}/revoke", id), &token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = app.post("/stock/12345/pay", &token, json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn order_lists_are_filtered_by_type_and_operator() {
+    let app = spawn_app(false).await;
+    let (super_id, super_token) = app.super_admin().await;
+    let (admin_id, admin_token) = app.admin(&super_token).await;
+    app.stock(&super_token, ISBN, 5, 50.0).await;
+    app.sell(&super_token, ISBN, 1, 20.0).await;
+    app.sell(&admin_token, ISBN, 2, 40.0).await;
+
+    let (status, stocks) = app.get("/stock?page=0&page_size=10", &admin_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(stocks.as_array().unwrap().len(), 1);
+    let (_, sells) = app.get("/sell?page=0&page_size=10", &admin_token).await;
+    assert_eq!(sells.as_array().unwrap().len(), 2);
+    for (operator, count) in [(super_id, 1), (admin_id, 2)] {
+        let (_, sells) = app
+            .get(
+                &format!("/sell?operator={}&page=0&page_size=10", operator),
+                &admin_token,
+            )
+            .await;
+        assert_eq!(sells.as_array().unwrap().len(), 1);
+        assert_eq!(sells[0]["total_count"], count);
+    }
+}
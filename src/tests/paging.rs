@@ -0,0 +1,158 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::test::{call_service, TestRequest};
+use chrono::{Duration, Utc};
+use entity::order_list;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::Value;
+
+use crate::contants::ITEM_COUNT_HEADER;
+
+use super::spawn_app;
+
+#[actix_web::test]
+async fn cursor_pages_are_stable_while_rows_are_added() {
+    let app = spawn_app(false).await;
+    let (_, token) = app.super_admin().await;
+    for n in 0..5 {
+        app.create_stock(&token, &format!("97870000003{:02}", n), 1, 10.0)
+            .await;
+    }
+
+    let mut ids = vec![];
+    let mut cursor = String::new();
+    for page in 0.. {
+        let (status, body) = app
+            .get(&format!("/stock?cursor={}&limit=2", cursor), &token)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let items = body["items"].as_array().unwrap();
+        ids.extend(items.iter().map(|item| item["id"].as_i64().unwrap()));
+        // 翻页期间新增的订单不会出现在后续的页中
+        if page == 0 {
+            app.create_stock(&token, "9787000000305", 1, 10.0).await;
+        }
+        match body["next_cursor"].as_str() {
+            Some(next) => cursor = next.to_owned(),
+            None => break,
+        }
+    }
+    assert_eq!(ids, [5, 4, 3, 2, 1]);
+
+    let (status, _) = app.get("/stock?cursor=bad&limit=2", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn order_cursor_pages_follow_the_update_time() {
+    let app = spawn_app(false).await;
+    let (_, token) = app.super_admin().await;
+    for n in 0..3 {
+        app.create_stock(&token, &format!("97870000006{:02}", n), 1, 10.0)
+            .await;
+    }
+    // 最早的订单最近被修改过，与最新的订单更新时间相同
+    let now = Utc::now().naive_utc();
+    for (ids, updated_at) in [(vec![1, 3], now), (vec![2], now - Duration::hours(1))] {
+        order_list::Entity::update_many()
+            .col_expr(order_list::Column::UpdatedAt, Expr::value(updated_at))
+            .filter(order_list::Column::Id.is_in(ids))
+            .exec(&app.db)
+            .await
+            .unwrap();
+    }
+
+    let (status, body) = app.get("/stock?cursor=&limit=2", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let ids = |body: &Value| -> Vec<i64> {
+        let items = body["items"].as_array().unwrap();
+        items
+            .iter()
+            .map(|item| item["id"].as_i64().unwrap())
+            .collect()
+    };
+    // 更新时间相同的按编号倒序排列
+    assert_eq!(ids(&body), [3, 1]);
+    let next = body["next_cursor"].as_str().unwrap();
+    let (status, body) = app
+        .get(&format!("/stock?cursor={}&limit=2", next), &token)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(ids(&body), [2]);
+    assert_eq!(body["next_cursor"], Value::Null);
+
+    // 页码分页的顺序与游标分页相同
+    let (_, body) = app.get("/stock?page=0", &token).await;
+    let ids: Vec<_> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|o| o["id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(ids, [3, 1, 2]);
+}
+
+#[actix_web::test]
+async fn count_is_optional_and_page_size_is_bounded() {
+    let app = spawn_app(false).await;
+    let (_, token) = app.super_admin().await;
+    app.stock(&token, "9787000000400", 1, 10.0).await;
+    app.stock(&token, "9787000000401", 1, 10.0).await;
+
+    let get = |uri: &str| {
+        TestRequest::get()
+            .uri(uri)
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_request()
+    };
+    // 页码分页默认统计总数，参数都可以省略
+    let resp = call_service(&app.service, get("/stock")).await;
+    assert_eq!(resp.headers().get(ITEM_COUNT_HEADER).unwrap(), "2");
+    let resp = call_service(&app.service, get("/stock?page=0&count=false")).await;
+    assert!(resp.headers().get(ITEM_COUNT_HEADER).is_none());
+
+    // 游标分页只在要求时统计
+    let resp = call_service(&app.service, get("/transaction?cursor=&limit=1")).await;
+    assert!(resp.headers().get(ITEM_COUNT_HEADER).is_none());
+    let resp = call_service(&app.service, get("/transaction?cursor=&limit=1&count=true")).await;
+    assert_eq!(resp.headers().get(ITEM_COUNT_HEADER).unwrap(), "2");
+
+    let (_, body) = app.get("/transaction?cursor=&limit=1", &token).await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+    let next = body["next_cursor"].as_str().unwrap();
+    let (_, body) = app
+        .get(&format!("/transaction?cursor={}&limit=1", next), &token)
+        .await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+    assert_eq!(body["next_cursor"], Value::Null);
+
+    for size in ["0", "101"] {
+        let (status, _) = app
+            .get(&format!("/stock?page=0&page_size={}", size), &token)
+            .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", size);
+    }
+    let (_, body) = app.get("/stock?page=0&page_size=100", &token).await;
+    assert_eq!(body.as_array().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn cursors_are_rejected_with_a_custom_sort() {
+    let app = spawn_app(false).await;
+    let (_, token) = app.super_admin().await;
+    app.stock(&token, "9787000000500", 1, 10.0).await;
+
+    for path in [
+        "/book?cursor=&sort_by=_inventory",
+        "/stats/slow_movers?cursor=",
+    ] {
+        let (status, _) = app.get(path, &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", path);
+    }
+    let (status, body) = app.get("/book?cursor=", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+    let (status, body) = app.get("/book?page=0&sort_by=_inventory", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+}
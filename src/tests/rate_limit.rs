@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::test::{call_service, read_body, TestRequest};
+use serde_json::{json, Value};
+
+use crate::config::{Config, RateLimitGroup};
+use crate::contants::{
+    API_KEY_HEADER, RATE_LIMIT_LIMIT_HEADER, RATE_LIMIT_REMAINING_HEADER, REQUEST_ID_HEADER,
+};
+use crate::utils::rate_limit::{MAX_MEMORY_BUCKETS, TOKEN_BUCKET_SCRIPT};
+
+use super::{spawn_app_with, test_config};
+
+fn config(name: &str, path: &str, burst: u32) -> Config {
+    let mut config = test_config();
+    config.rate_limit.groups = BTreeMap::from([(
+        name.to_owned(),
+        RateLimitGroup {
+            paths: vec![path.to_owned()],
+            burst,
+            per_minute: 1,
+        },
+    )]);
+    config
+}
+
+fn request(method: Method, path: &str, ip: &str) -> TestRequest {
+    TestRequest::default()
+        .method(method)
+        .uri(path)
+        .peer_addr(format!("{}:40000", ip).parse().unwrap())
+}
+
+#[actix_web::test]
+async fn anonymous_requests_are_limited_per_ip() {
+    for with_redis in [false, true] {
+        let app = spawn_app_with(with_redis, config("login", "/user/login", 2)).await;
+        let login = |ip| {
+            request(Method::POST, "/user/login", ip)
+                .set_json(json!({ "id": 1, "password": "wrong" }))
+                .to_request()
+        };
+
+        for remaining in ["1", "0"] {
+            let resp = call_service(&app.service, login("10.0.0.1")).await;
+            assert_ne!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(resp.headers().get(RATE_LIMIT_LIMIT_HEADER).unwrap(), "2");
+            let header = resp.headers().get(RATE_LIMIT_REMAINING_HEADER).unwrap();
+            assert_eq!(header, remaining);
+        }
+
+        let resp = call_service(&app.service, login("10.0.0.1")).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        let headers = resp.headers().clone();
+        assert_eq!(headers.get(RATE_LIMIT_REMAINING_HEADER).unwrap(), "0");
+        let retry: u64 = headers
+            .get(header::RETRY_AFTER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry));
+        let body: Value = serde_json::from_slice(&read_body(resp).await).unwrap();
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Too many requests"));
+        assert_eq!(
+            body["request_id"],
+            headers.get(REQUEST_ID_HEADER).unwrap().to_str().unwrap()
+        );
+
+        // 配置了 Redis 时桶保存在 Redis 中
+        if let Some(redis) = &app.redis {
+            assert!(redis.contains("rate_limit:login:ip:10.0.0.1"));
+        }
+
+        // 其他 IP 与其他路由不受影响
+        let resp = call_service(&app.service, login("10.0.0.2")).await;
+        assert_ne!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        for path in ["/user/login_history", "/healthz"] {
+            let req = request(Method::GET, path, "10.0.0.1").to_request();
+            let resp = call_service(&app.service, req).await;
+            assert_ne!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert!(resp.headers().get(RATE_LIMIT_LIMIT_HEADER).is_none());
+        }
+    }
+}
+
+#[actix_web::test]
+async fn authenticated_users_have_their_own_buckets() {
+    // 登录不属于限流的分组
+    let app = spawn_app_with(false, config("stats", "/stats/", 1)).await;
+    let (_, super_token) = app.super_admin().await;
+    let (_, admin_token) = app.admin(&super_token).await;
+    let get = |token: &str| {
+        request(Method::GET, "/stats/kpi", "10.0.0.1")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_request()
+    };
+    let resp = call_service(&app.service, get(&super_token)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = call_service(&app.service, get(&super_token)).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 同一 IP 上的另一个用户
+    let resp = call_service(&app.service, get(&admin_token)).await;
+    assert_ne!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    // 未登录的请求按 IP 计数
+    let req = request(Method::GET, "/stats/kpi", "10.0.0.1").to_request();
+    let resp = call_service(&app.service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn api_key_requests_count_against_the_key_owner() {
+    let app = spawn_app_with(false, config("stats", "/stats/", 1)).await;
+    let (_, super_token) = app.super_admin().await;
+    let (_, admin_token) = app.admin(&super_token).await;
+    let (status, body) = app
+        .post(
+            "/user/me/api_keys",
+            &admin_token,
+            json!({ "name": "ci", "scopes": ["read"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let key = body["key"].as_str().unwrap().to_owned();
+    let get = |ip: &str, key: &str| {
+        request(Method::GET, "/stats/book", ip)
+            .insert_header((API_KEY_HEADER, key))
+            .to_request()
+    };
+
+    let resp = call_service(&app.service, get("10.0.0.1", &key)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    // 换一个 IP 仍然计入同一个用户
+    let resp = call_service(&app.service, get("10.0.0.2", &key)).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    // 密钥所属用户的 JWT 请求共用同一个桶
+    let req = request(Method::GET, "/stats/book", "10.0.0.3")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = call_service(&app.service, req).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 无效的密钥按 IP 计数，更换密钥不能绕过限流
+    let resp = call_service(&app.service, get("10.0.0.4", "bogus-1")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = call_service(&app.service, get("10.0.0.4", "bogus-2")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_web::test]
+async fn memory_buckets_evict_the_least_recently_used() {
+    let app = spawn_app_with(false, config("flood", "/flood", 1)).await;
+    let flood = |ip: String| request(Method::GET, "/flood", &ip).to_request();
+    call_service(&app.service, flood("10.0.0.1".to_owned())).await;
+    let resp = call_service(&app.service, flood("10.0.0.1".to_owned())).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 不断更换 IP 的请求使桶的数量达到上限，最久没有使用的桶被淘汰
+    let ip = |n: usize| format!("10.1.{}.{}", n / 256, n % 256);
+    for n in 0..MAX_MEMORY_BUCKETS {
+        call_service(&app.service, flood(ip(n))).await;
+    }
+    let resp = call_service(&app.service, flood("10.0.0.1".to_owned())).await;
+    assert_ne!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    // 最近使用的桶仍然保留
+    let resp = call_service(&app.service, flood(ip(MAX_MEMORY_BUCKETS - 1))).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn token_bucket_script_is_unchanged() {
+    // 测试中的 Redis 替身用 Rust 重新实现了这段脚本。修改脚本时需要同步修改
+    // refill 与 fake_redis.rs 中的实现，然后更新这里的摘要
+    assert_eq!(
+        redis::Script::new(TOKEN_BUCKET_SCRIPT).get_hash(),
+        "fe6cb83574280a24fdc918e144fc5a6286443799"
+    );
+}
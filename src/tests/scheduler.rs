@@ -0,0 +1,165 @@
+use actix_web::web::Data;
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use entity::job_run::{self, JobStatus};
+use entity::{order_list, TicketStatus};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use tokio::sync::Mutex;
+
+use crate::config::{Config, SchedulerConfig};
+use crate::scheduler::cron::Schedule;
+use crate::scheduler::{jobs, JobContext, Scheduler};
+
+use super::spawn_app;
+
+fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(y, m, d)
+        .unwrap()
+        .and_hms_opt(h, min, 0)
+        .unwrap()
+}
+
+fn next(expr: &str, after: NaiveDateTime) -> Option<NaiveDateTime> {
+    expr.parse::<Schedule>().unwrap().next_after(after)
+}
+
+#[test]
+fn cron_schedules() {
+    // 2023-07-14 是星期五
+    let friday = at(2023, 7, 14, 17, 50);
+    assert_eq!(
+        next("*/15 9-17 * * 1-5", friday),
+        Some(at(2023, 7, 17, 9, 0))
+    );
+    assert_eq!(next("*/5 * * * *", friday), Some(at(2023, 7, 14, 17, 55)));
+    assert_eq!(next("5/20 * * * *", friday), Some(at(2023, 7, 14, 18, 5)));
+    assert_eq!(
+        next("0 0 1,15 * *", at(2023, 7, 1, 0, 0)),
+        Some(at(2023, 7, 15, 0, 0))
+    );
+    assert_eq!(
+        next("@daily", at(2023, 12, 31, 23, 59)),
+        Some(at(2024, 1, 1, 0, 0))
+    );
+    // 日与星期同时指定时满足其一即可
+    assert_eq!(
+        next("0 12 13 * 5", at(2023, 7, 1, 0, 0)),
+        Some(at(2023, 7, 7, 12, 0))
+    );
+    assert_eq!(next("0 0 * * 7", friday), next("0 0 * * 0", friday));
+    assert_eq!(next("0 0 30 2 *", friday), None);
+
+    for invalid in [
+        "* * * *",
+        "60 * * * *",
+        "*/0 * * * *",
+        "5-1 * * * *",
+        "a * * * *",
+    ] {
+        assert!(invalid.parse::<Schedule>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn job_schedules_can_be_overridden() {
+    // 撤销待支付订单的任务默认不执行
+    let mut config = SchedulerConfig::default();
+    let built = jobs::build(&config).unwrap();
+    assert_eq!(built.len(), 1);
+    assert_eq!(built[0].name, "purge_expired_records");
+
+    config
+        .schedules
+        .insert("expire_pending_orders".to_owned(), "*/5 * * * *".to_owned());
+    assert_eq!(jobs::build(&config).unwrap().len(), 2);
+    config
+        .schedules
+        .insert("purge_expired_records".to_owned(), "off".to_owned());
+    let built = jobs::build(&config).unwrap();
+    assert_eq!(built.len(), 1);
+    assert_eq!(built[0].name, "expire_pending_orders");
+
+    config
+        .schedules
+        .insert("expire_pending_orders".to_owned(), "* *".to_owned());
+    assert!(jobs::build(&config).is_err());
+    config.schedules.clear();
+    config
+        .schedules
+        .insert("no_such_job".to_owned(), "@daily".to_owned());
+    assert!(jobs::build(&config).is_err());
+}
+
+#[actix_web::test]
+async fn each_run_executes_once_and_is_recorded() {
+    let app = spawn_app(true).await;
+    let (_, token) = app.super_admin().await;
+    let mut orders = vec![];
+    for isbn in ["9787000000200", "9787000000201"] {
+        orders.push(app.create_stock(&token, isbn, 1, 10.0).await);
+    }
+    // 只有第一个订单超过了待支付的有效期
+    order_list::Entity::update_many()
+        .col_expr(
+            order_list::Column::CreatedAt,
+            Expr::value(Utc::now().naive_utc() - Duration::days(2)),
+        )
+        .filter(order_list::Column::Id.eq(orders[0]))
+        .exec(&app.db)
+        .await
+        .unwrap();
+
+    let redis = Some(Mutex::new(app.redis.as_ref().unwrap().connect().await));
+    let scheduler = |redis| {
+        let mut config = Config::default();
+        config
+            .scheduler
+            .schedules
+            .insert("expire_pending_orders".to_owned(), "*/5 * * * *".to_owned());
+        Scheduler::new(
+            JobContext {
+                db: app.db.clone(),
+                redis: Data::new(redis),
+                config: Data::new(config.clone()),
+            },
+            jobs::build(&config.scheduler).unwrap(),
+        )
+    };
+    let first = scheduler(redis);
+    let scheduled_at = at(2023, 7, 14, 12, 0);
+    assert_eq!(
+        first.run(0, scheduled_at).await.unwrap(),
+        Some(JobStatus::Succeeded)
+    );
+
+    let statuses: Vec<_> = order_list::Entity::find()
+        .filter(order_list::Column::Id.is_in(orders))
+        .all(&app.db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|order| order.status)
+        .collect();
+    assert_eq!(statuses, [TicketStatus::Revoked, TicketStatus::Pending]);
+
+    // 同一次调度不会再次执行：同一实例被 Redis 锁挡住，没有 Redis 的实例被数据库记录挡住
+    assert_eq!(first.run(0, scheduled_at).await.unwrap(), None);
+    assert_eq!(scheduler(None).run(0, scheduled_at).await.unwrap(), None);
+
+    let runs = job_run::Entity::find().all(&app.db).await.unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].job, "expire_pending_orders");
+    assert_eq!(runs[0].status, JobStatus::Succeeded);
+    assert!(runs[0].finished_at.is_some());
+    assert!(runs[0].detail.as_ref().unwrap().contains("1 stock"));
+
+    // 下一次调度照常执行
+    let later = scheduled_at + Duration::minutes(5);
+    assert_eq!(
+        scheduler(None).run(0, later).await.unwrap(),
+        Some(JobStatus::Succeeded)
+    );
+
+    // 没有进行中的任务时立即停止
+    scheduler(None).start().shutdown().await;
+}
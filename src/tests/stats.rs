@@ -0,0 +1,400 @@
+use actix_web::http::StatusCode;
+use chrono::{Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use entity::{order_list, transaction};
+use sea_orm::sea_query::Expr;
+use sea_orm::EntityTrait;
+use serde_json::json;
+
+use crate::api::stats::{StatPeriod, StatSpan, TzOffset};
+
+use super::spawn_app;
+
+const BOOK_A: &str = "9787000000001";
+const BOOK_B: &str = "9787000000002";
+
+/// 超级管理员与管理员的一组订单：
+///
+/// - 超级管理员进货 A 10 本共 100 元并入库；进货 B 4 本共 40 元，已付款未入库；
+/// - 超级管理员售出 A 3 本共 60 元，管理员售出 A 2 本共 40 元；
+/// - 管理员撤销了一张 A 1 本的售书订单。
+macro_rules! seeded_app {
+    ($app:ident, $super_id:ident, $super_token:ident, $admin_id:ident, $admin_token:ident) => {
+        let $app = spawn_app(false).await;
+        let ($super_id, $super_token) = $app.super_admin().await;
+        let ($admin_id, $admin_token) = $app.admin(&$super_token).await;
+        $app.stock(&$super_token, BOOK_A, 10, 100.0).await;
+        let order = $app.create_stock(&$super_token, BOOK_B, 4, 40.0).await;
+        $app.post(
+            &format!("/stock/{}/pay", order),
+            &$super_token,
+            json!({}),
+        )
+        .await;
+        $app.sell(&$super_token, BOOK_A, 3, 60.0).await;
+        $app.sell(&$admin_token, BOOK_A, 2, 40.0).await;
+        let (_, order) = $app
+            .post(
+                "/sell",
+                &$admin_token,
+                json!({ "book_isbn": BOOK_A, "total_price": 20.0, "total_count": 1 }),
+            )
+            .await;
+        $app.post(
+            &format!("/sell/{}/revoke", order["id"]),
+            &$admin_token,
+            json!({}),
+        )
+        .await;
+    };
+}
+
+#[actix_web::test]
+async fn order_totals() {
+    seeded_app!(app, super_id, super_token, admin_id, admin_token);
+    let _ = (super_id, admin_id);
+
+    let (status, body) = app.get("/stats/sell?all=true", &super_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({ "total_sell_count": 6, "total_done_count": 5 })
+    );
+    let (_, body) = app.get("/stats/stock?all=true", &super_token).await;
+    assert_eq!(
+        body,
+        json!({ "total_stock_count": 14, "total_waiting_for_confirm_count": 4 })
+    );
+    let (_, body) = app.get("/stats/transaction?all=true", &super_token).await;
+    assert_eq!(
+        body,
+        json!({ "total_sell_price": 100.0, "total_stock_paid_price": 140.0 })
+    );
+    let (_, body) = app.get("/stats/book", &super_token).await;
+    assert_eq!(
+        body,
+        json!({ "total_inventory_count": 5, "total_book_count": 2 })
+    );
+}
+
+#[actix_web::test]
+async fn admins_only_see_their_own_figures() {
+    seeded_app!(app, super_id, super_token, admin_id, admin_token);
+    let _ = (super_id, admin_id);
+
+    for path in ["/stats/sell", "/stats/sell?all=true"] {
+        let (status, body) = app.get(path, &admin_token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({ "total_sell_count": 3, "total_done_count": 2 })
+        );
+    }
+    let (_, body) = app.get("/stats/sell", &super_token).await;
+    assert_eq!(
+        body,
+        json!({ "total_sell_count": 3, "total_done_count": 3 })
+    );
+
+    let (status, _) = app.get("/stats/kpi", &admin_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn rankings() {
+    seeded_app!(app, super_id, super_token, admin_id, admin_token);
+    let _ = admin_token;
+
+    let (status, body) = app.get("/stats/bestsell?all=true", &super_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["isbn"], BOOK_A);
+    assert_eq!(body[0]["total_sell_count"], 5);
+    assert_eq!(body[0]["total_revenue"], 100.0);
+    assert_eq!(body[0]["share"], 100.0);
+
+    // 按完成时间统计，无论按什么排名，前一天创建、今天完成的订单都算在今天
+    order_list::Entity::update_many()
+        .col_expr(
+            order_list::Column::CreatedAt,
+            Expr::value(Utc::now().naive_utc() - Duration::days(2)),
+        )
+        .exec(&app.db)
+        .await
+        .unwrap();
+    for rank_by in ["copies", "revenue", "profit"] {
+        let (_, body) = app
+            .get(
+                &format!("/stats/bestsell?all=true&span=day&rank_by={}", rank_by),
+                &super_token,
+            )
+            .await;
+        assert_eq!(body[0]["total_sell_count"], 5, "{}", rank_by);
+    }
+
+    // 绩效同样按完成时间统计
+    let (status, body) = app.get("/stats/kpi?span=day", &super_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["operator_id"], super_id);
+    assert_eq!(body[0]["revenue"], 60.0);
+    assert_eq!(body[0]["stock_orders_confirmed"], 1);
+    assert_eq!(body[1]["operator_id"], admin_id);
+    assert_eq!(body[1]["copies_sold"], 2);
+    assert_eq!(body[1]["sell_tickets_revoked"], 1);
+}
+
+#[actix_web::test]
+async fn margin_and_inventory_value() {
+    seeded_app!(app, super_id, super_token, admin_id, admin_token);
+    let _ = (super_id, admin_id, admin_token);
+
+    let (status, body) = app
+        .get("/stats/margin?all=true&method=average", &super_token)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["overall"]["revenue"], 100.0);
+    assert_eq!(body["overall"]["cogs"], 50.0);
+    assert_eq!(body["overall"]["gross_profit"], 50.0);
+
+    let (status, body) = app
+        .get("/stats/inventory_value?method=fifo", &super_token)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["total"],
+        json!({ "count": 5, "cost_value": 50.0, "retail_value": 100.0 })
+    );
+
+    // 售出 5 本的 A 只在要求至少售出 6 本时视为滞销
+    let (_, body) = app.get("/stats/slow_movers", &super_token).await;
+    assert_eq!(body, json!([]));
+    let (_, body) = app
+        .get("/stats/slow_movers?min_sales=6", &super_token)
+        .await;
+    assert_eq!(body[0]["isbn"], BOOK_A);
+    assert_eq!(body[0]["sold_count"], 5);
+    for min_sales in ["0", "-1"] {
+        let (status, _) = app
+            .get(
+                &format!("/stats/slow_movers?min_sales={}", min_sales),
+                &super_token,
+            )
+            .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", min_sales);
+    }
+}
+
+#[actix_web::test]
+async fn forecasts() {
+    seeded_app!(app, super_id, super_token, admin_id, admin_token);
+    let _ = (super_id, admin_id, admin_token);
+
+    // 今天的销售不在历史窗口内，A 仍有库存
+    let (status, body) = app.get("/stats/forecast", &super_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["isbn"], BOOK_A);
+    assert_eq!(body[0]["stock_count"], 5);
+
+    let isbns: Vec<_> = (0..101).map(|n| format!("97870000{:05}", n)).collect();
+    let (status, _) = app
+        .get(
+            &format!("/stats/forecast?isbns={}", isbns.join(",")),
+            &super_token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_web::test]
+async fn timeseries_are_grouped_by_local_buckets() {
+    seeded_app!(app, super_id, super_token, admin_id, admin_token);
+    let _ = (super_id, admin_id, admin_token);
+
+    // 所有订单发生在 UTC 3 月 31 日 20:00，即东八区 4 月 1 日 04:00
+    let at = NaiveDate::from_ymd_opt(2023, 3, 31)
+        .unwrap()
+        .and_hms_opt(20, 0, 0)
+        .unwrap();
+    order_list::Entity::update_many()
+        .col_expr(order_list::Column::CreatedAt, Expr::value(at))
+        .exec(&app.db)
+        .await
+        .unwrap();
+    transaction::Entity::update_many()
+        .col_expr(transaction::Column::CreatedAt, Expr::value(at))
+        .exec(&app.db)
+        .await
+        .unwrap();
+
+    let series = |query: String| {
+        let app = &app;
+        let token = &super_token;
+        async move {
+            let (status, body) = app
+                .get(&format!("/stats/timeseries?all=true&{}", query), token)
+                .await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            body["points"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|point| point["value"].as_f64().unwrap())
+                .collect::<Vec<_>>()
+        }
+    };
+    let range = |from: &str, to: &str, tz: &str| {
+        format!("from={}{}&to={}{}&tz={}", from, tz, to, tz, tz).replace('+', "%2B")
+    };
+
+    for (bucket, from, to, tz, expected) in [
+        (
+            "month",
+            "2023-03-01T00:00:00",
+            "2023-05-01T00:00:00",
+            "+08:00",
+            [0.0, 100.0],
+        ),
+        (
+            "month",
+            "2023-03-01T00:00:00",
+            "2023-05-01T00:00:00",
+            "+00:00",
+            [100.0, 0.0],
+        ),
+        (
+            "week",
+            "2023-03-20T00:00:00",
+            "2023-04-03T00:00:00",
+            "+08:00",
+            [0.0, 100.0],
+        ),
+        (
+            "day",
+            "2023-03-31T00:00:00",
+            "2023-04-02T00:00:00",
+            "+08:00",
+            [0.0, 100.0],
+        ),
+        (
+            "hour",
+            "2023-04-01T03:00:00",
+            "2023-04-01T05:00:00",
+            "+08:00",
+            [0.0, 100.0],
+        ),
+        (
+            "day",
+            "2023-03-31T00:00:00",
+            "2023-04-02T00:00:00",
+            "-05:00",
+            [100.0, 0.0],
+        ),
+    ] {
+        let query = format!("metric=revenue&bucket={}&{}", bucket, range(from, to, tz));
+        assert_eq!(series(query).await, expected, "{} {}", bucket, tz);
+    }
+    let query = format!(
+        "metric=sell_count&bucket=month&{}",
+        range("2023-03-01T00:00:00", "2023-05-01T00:00:00", "+08:00")
+    );
+    assert_eq!(series(query).await, [0.0, 6.0]);
+}
+
+#[actix_web::test]
+async fn daily_close() {
+    seeded_app!(app, super_id, super_token, admin_id, admin_token);
+
+    let (status, body) = app.get("/stats/daily_close", &admin_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["sell_count"], 2);
+    assert_eq!(body["copies_sold"], 5);
+    assert_eq!(body["revenue"], 100.0);
+    assert_eq!(body["revoked_count"], 1);
+    assert_eq!(body["stock_payments"], 140.0);
+    assert_eq!(body["operators"][0]["operator_id"], super_id);
+    assert_eq!(body["operators"][1]["operator_id"], admin_id);
+    assert_eq!(body["closed"], json!(null));
+
+    let (status, _) = app
+        .post("/stats/daily_close", &admin_token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app
+        .post("/stats/daily_close", &super_token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app
+        .post("/stats/daily_close", &super_token, json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // 日结后的订单被标记为调整
+    app.sell(&admin_token, BOOK_A, 1, 20.0).await;
+    let (_, body) = app.get("/stats/daily_close", &admin_token).await;
+    assert_eq!(body["closed"]["closed_by"], super_id);
+    assert_eq!(body["closed"]["snapshot"]["sell_count"], 2);
+    assert_eq!(body["sell_count"], 3);
+    assert_eq!(body["adjustments"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn time_zone_offsets() {
+    let offset =
+        |value: &str| TzOffset::try_from(value.to_owned()).map(|tz| tz.0.local_minus_utc());
+    assert_eq!(offset("+08:00"), Ok(8 * 3600));
+    assert_eq!(offset("-05:30"), Ok(-(5 * 3600 + 30 * 60)));
+    assert_eq!(offset("+8"), Ok(8 * 3600));
+    assert_eq!(offset("Z"), Ok(0));
+    for invalid in [
+        "",
+        "08:00",
+        "é1",
+        "+é",
+        "+24:00",
+        "+99999999",
+        "+08:60",
+        "+-1",
+        "+08:-1",
+    ] {
+        assert!(offset(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn calendar_periods_follow_the_local_calendar() {
+    let utc = |m: u32, d: u32, h: u32| -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, m, d)
+            .unwrap()
+            .and_hms_opt(h, 0, 0)
+            .unwrap()
+    };
+    let period = |span| StatPeriod {
+        span: Some(span),
+        from: None,
+        to: None,
+        tz: Some(TzOffset::try_from("+08:00".to_owned()).unwrap()),
+    };
+    let resolve = |span, now| {
+        let (current, previous) = period(span)
+            .resolve(Utc.from_utc_datetime(&now))
+            .ok()
+            .unwrap();
+        let previous = previous.unwrap();
+        (
+            current.from.unwrap(),
+            previous.from.unwrap(),
+            previous.to.unwrap(),
+        )
+    };
+
+    // 本地时间 3 月 10 日 08:00，本月从 3 月 1 日开始，上个月对应 2 月 1 日至 2 月 10 日 08:00
+    assert_eq!(
+        resolve(StatSpan::ThisMonth, utc(3, 10, 0)),
+        (utc(2, 28, 16), utc(1, 31, 16), utc(2, 10, 0))
+    );
+    // 本地时间已经是 7 月 1 日，属于第三季度
+    assert_eq!(
+        resolve(StatSpan::ThisQuarter, utc(6, 30, 20)),
+        (utc(6, 30, 16), utc(3, 31, 16), utc(3, 31, 20))
+    );
+}
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+use std::{future::Future, marker::PhantomData, pin::Pin};
+
+use actix_web::{http::Method, web::Data, FromRequest};
+use chrono::Utc;
+use entity::api_key::{self, ApiKeyScope};
+use entity::user;
+use redis::aio::MultiplexedConnection;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QuerySelect,
+};
+use tokio::sync::Mutex;
+
+use crate::contants::{API_KEY_DISPLAY_LENGTH, API_KEY_HEADER, API_KEY_PREFIX};
+
+use super::{
+    errors::{internal_server_error, unauthorized},
+    jwt::{gen_token_id, hash_secret_token, JwtClaims, RoleValidator},
+    logging,
+    permission::CheckPermission,
+};
+
+/// 生成一个新的 API Key，返回完整密钥和用于展示的前缀。
+pub fn gen_api_key() -> (String, String) {
+    let key = format!("{}{}", API_KEY_PREFIX, gen_token_id());
+    let prefix = key.chars().take(API_KEY_DISPLAY_LENGTH).collect();
+    (key, prefix)
+}
+
+/// 未吊销且未过期的 API Key 所属用户的 ID，限流时据此按用户计数。
+pub(crate) async fn owner_id(db: &DatabaseConnection, key: &str) -> Result<Option<i32>, DbErr> {
+    let now = Utc::now().naive_utc();
+    api_key::Entity::find()
+        .select_only()
+        .column(api_key::Column::UserId)
+        .filter(api_key::Column::KeyHash.eq(hash_secret_token(key)))
+        .filter(api_key::Column::Revoked.eq(false))
+        .filter(
+            Condition::any()
+                .add(api_key::Column::ExpiresAt.is_null())
+                .add(api_key::Column::ExpiresAt.gte(now)),
+        )
+        .into_tuple::<i32>()
+        .one(db)
+        .await
+}
+
+/// 从 `X-Api-Key` 请求头中提取的 API Key。
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub key: String,
+    /// 当前请求所需的权限范围
+    pub required_scope: ApiKeyScope,
+}
+
+impl FromRequest for ApiKey {
+    type Error = actix_web::error::Error;
+    type Future = std::future::Ready<Result<Self, Self::Error>>;
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|header| header.to_str().ok())
+            .map(str::to_owned);
+        let required_scope = match *req.method() {
+            Method::GET | Method::HEAD => ApiKeyScope::Read,
+            _ => ApiKeyScope::Write,
+        };
+        std::future::ready(match key {
+            Some(key) => Ok(ApiKey {
+                key,
+                required_scope,
+            }),
+            None => Err(unauthorized("Missing API key")),
+        })
+    }
+}
+
+/// JWT 或 API Key。请求带有 `X-Api-Key` 请求头时使用 API Key，否则使用 JWT。
+#[derive(Debug, Clone)]
+pub enum Credential {
+    Jwt(JwtClaims),
+    ApiKey(ApiKey),
+}
+
+impl FromRequest for Credential {
+    type Error = actix_web::error::Error;
+    type Future = std::future::Ready<Result<Self, Self::Error>>;
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        std::future::ready(if req.headers().contains_key(API_KEY_HEADER) {
+            ApiKey::from_request(req, payload)
+                .into_inner()
+                .map(Credential::ApiKey)
+        } else {
+            JwtClaims::from_request(req, payload)
+                .into_inner()
+                .map(Credential::Jwt)
+        })
+    }
+}
+
+type AuthData = (
+    Arc<DatabaseConnection>,
+    Arc<Option<Mutex<MultiplexedConnection>>>,
+);
+
+/// 在 `T` 的基础上，额外允许使用 API Key 访问。
+///
+/// 使用 API Key 时，以密钥所属用户的身份校验 `T` 要求的角色，并检查密钥的权限范围。
+/// 用户修改或重置密码时，其全部 API Key 会被吊销。
+pub struct OrApiKey<T>(PhantomData<T>);
+
+impl<T> CheckPermission for OrApiKey<T>
+where
+    T: CheckPermission<Authentication = JwtClaims, Output = user::Model, AppData = AuthData>
+        + RoleValidator
+        + 'static,
+    T::Future: 'static,
+{
+    type Authentication = Credential;
+    type Output = user::Model;
+    type Future = Pin<Box<dyn Future<Output = Result<Option<user::Model>, actix_web::Error>>>>;
+    type AppData = AuthData;
+    fn check_permission(data: Data<AuthData>, permission: &Self::Authentication) -> Self::Future {
+        let key = match permission {
+            Credential::Jwt(claims) => return Box::pin(T::check_permission(data, claims)),
+            Credential::ApiKey(key) => key.clone(),
+        };
+        Box::pin(async move {
+            let db = data.get_ref().0.as_ref();
+            let (record, owner) = api_key::Entity::find()
+                .filter(api_key::Column::KeyHash.eq(hash_secret_token(&key.key)))
+                .filter(api_key::Column::Revoked.eq(false))
+                .find_also_related(user::Entity)
+                .one(db)
+                .await
+                .map_err(internal_server_error)?
+                .ok_or_else(|| unauthorized("Invalid API key"))?;
+            let owner = owner
+                .filter(|owner| !owner.is_deleted)
+                .ok_or_else(|| unauthorized("The user does not exist"))?;
+
+            let now = Utc::now().naive_utc();
+            if record
+                .expires_at
+                .map_or(false, |expires_at| expires_at < now)
+            {
+                return Err(unauthorized("API key expired"));
+            }
+            logging::set_user_id(owner.id);
+
+            // 写权限包含读权限
+            let scopes = ApiKeyScope::parse_list(&record.scopes);
+            if !scopes.contains(&key.required_scope) && !scopes.contains(&ApiKeyScope::Write) {
+                return Ok(None);
+            }
+
+            api_key::Entity::update_many()
+                .col_expr(api_key::Column::LastUsedAt, Expr::value(now))
+                .filter(api_key::Column::Id.eq(record.id))
+                .exec(db)
+                .await
+                .map_err(internal_server_error)?;
+
+            Ok(T::validate_role(&owner).then_some(owner))
+        })
+    }
+}
@@ -0,0 +1,163 @@
+// 根据配置构造 CORS 中间件。
+
+use std::str::FromStr;
+
+use actix_cors::Cors;
+use actix_web::http::{header::HeaderName, Method};
+
+use crate::config::CorsConfig;
+use crate::contants::{
+    ITEM_COUNT_HEADER, RATE_LIMIT_LIMIT_HEADER, RATE_LIMIT_REMAINING_HEADER,
+    RATE_LIMIT_RESET_HEADER, REQUEST_ID_HEADER,
+};
+
+/// 允许跨域请求读取的响应头
+const EXPOSED_HEADERS: [&str; 6] = [
+    ITEM_COUNT_HEADER,
+    REQUEST_ID_HEADER,
+    RATE_LIMIT_LIMIT_HEADER,
+    RATE_LIMIT_REMAINING_HEADER,
+    RATE_LIMIT_RESET_HEADER,
+    "Retry-After",
+];
+
+/// 允许的来源。`https://*.example.com` 匹配 `example.com` 的任意子域名，但不匹配 `example.com` 本身。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginPattern {
+    Exact(String),
+    /// `scheme` 包含 `://`，`suffix` 以 `.` 开头，包含端口
+    Wildcard {
+        scheme: String,
+        suffix: String,
+    },
+}
+
+impl OriginPattern {
+    pub fn parse(origin: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid CORS origin {}, expected a scheme and host without a path, such as https://example.com or https://*.example.com",
+                origin
+            )
+        };
+        let scheme_end = origin.find("://").ok_or_else(invalid)? + 3;
+        let (scheme, host) = origin.split_at(scheme_end);
+        if !matches!(scheme, "http://" | "https://")
+            || host.is_empty()
+            || host.contains('/')
+            || host.contains('?')
+        {
+            return Err(invalid());
+        }
+        let origin = origin.to_ascii_lowercase();
+        match host.strip_prefix('*') {
+            Some(suffix)
+                if suffix.starts_with('.') && suffix.len() > 1 && !suffix.contains('*') =>
+            {
+                Ok(Self::Wildcard {
+                    scheme: scheme.to_owned(),
+                    suffix: suffix.to_ascii_lowercase(),
+                })
+            }
+            None if !host.contains('*') => Ok(Self::Exact(origin)),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn matches(&self, origin: &str) -> bool {
+        match self {
+            Self::Exact(expected) => expected.eq_ignore_ascii_case(origin),
+            Self::Wildcard { scheme, suffix } => {
+                let origin = origin.to_ascii_lowercase();
+                origin
+                    .strip_prefix(scheme.as_str())
+                    .and_then(|host| host.strip_suffix(suffix.as_str()))
+                    .map_or(false, |sub| {
+                        !sub.is_empty()
+                            && !sub.starts_with('.')
+                            && sub
+                                .chars()
+                                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+                    })
+            }
+        }
+    }
+}
+
+pub fn validate_methods(methods: &[String]) -> Result<(), String> {
+    for method in methods.iter().filter(|m| *m != "*") {
+        Method::from_str(method).map_err(|_| format!("Invalid CORS method {}", method))?;
+    }
+    Ok(())
+}
+
+pub fn validate_headers(headers: &[String]) -> Result<(), String> {
+    for header in headers.iter().filter(|h| *h != "*") {
+        HeaderName::from_str(header).map_err(|_| format!("Invalid CORS header {}", header))?;
+    }
+    Ok(())
+}
+
+/// 构造 CORS 中间件。配置应当已经通过校验。
+pub fn build(config: &CorsConfig) -> Cors {
+    let mut cors = Cors::default().expose_headers(EXPOSED_HEADERS);
+    if config.allow_all {
+        cors = cors.allow_any_origin();
+    } else {
+        let mut wildcards = vec![];
+        for pattern in config
+            .allowed_origins
+            .iter()
+            .filter_map(|origin| OriginPattern::parse(origin).ok())
+        {
+            match pattern {
+                OriginPattern::Exact(origin) => cors = cors.allowed_origin(&origin),
+                wildcard => wildcards.push(wildcard),
+            }
+        }
+        if !wildcards.is_empty() {
+            cors = cors.allowed_origin_fn(move |origin, _| {
+                origin
+                    .to_str()
+                    .map_or(false, |origin| wildcards.iter().any(|p| p.matches(origin)))
+            });
+        }
+    }
+
+    if config.allowed_methods.iter().any(|m| m == "*") {
+        cors = cors.allow_any_method();
+    } else {
+        cors = cors.allowed_methods(config.allowed_methods.iter().map(String::as_str));
+    }
+    if config.allowed_headers.iter().any(|h| h == "*") {
+        cors = cors.allow_any_header();
+    } else if !config.allowed_headers.is_empty() {
+        cors = cors.allowed_headers(config.allowed_headers.iter().map(String::as_str));
+    }
+    if config.allow_credentials {
+        cors = cors.supports_credentials();
+    }
+    cors.max_age(config.max_age)
+}
+
+/// 用于启动日志的策略描述。
+pub fn describe(config: &CorsConfig) -> String {
+    let origins = if config.allow_all {
+        "any".to_owned()
+    } else if config.allowed_origins.is_empty() {
+        "none (cross-origin requests are rejected)".to_owned()
+    } else {
+        config.allowed_origins.join(", ")
+    };
+    format!(
+        "origins: {}; methods: {}; headers: {}; credentials: {}; max-age: {}; exposed: {}",
+        origins,
+        config.allowed_methods.join(", "),
+        config.allowed_headers.join(", "),
+        config.allow_credentials,
+        config
+            .max_age
+            .map_or_else(|| "unset".to_owned(), |age| format!("{}s", age)),
+        EXPOSED_HEADERS.join(", "),
+    )
+}
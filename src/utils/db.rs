@@ -0,0 +1,56 @@
+// 数据库后端的选择与连接。
+
+use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbBackend, DbErr};
+
+use crate::config::DatabaseConfig;
+
+#[cfg(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")))]
+compile_error!("At least one database backend feature must be enabled: mysql, postgres or sqlite");
+
+/// 根据 URL 的 scheme 判断数据库后端，并检查编译时是否启用了对应的 feature。
+pub fn backend_of(url: &str) -> Result<DbBackend, String> {
+    let scheme = url.split(':').next().unwrap_or_default();
+    let (backend, enabled) = match scheme {
+        "mysql" | "mariadb" => (DbBackend::MySql, cfg!(feature = "mysql")),
+        "postgres" | "postgresql" => (DbBackend::Postgres, cfg!(feature = "postgres")),
+        "sqlite" => (DbBackend::Sqlite, cfg!(feature = "sqlite")),
+        _ => return Err(format!("Unsupported database URL scheme: {}", scheme)),
+    };
+    if !enabled {
+        return Err(format!(
+            "The server was built without support for {} databases, enable the `{}` feature",
+            scheme,
+            match backend {
+                DbBackend::MySql => "mysql",
+                DbBackend::Postgres => "postgres",
+                DbBackend::Sqlite => "sqlite",
+            }
+        ));
+    }
+    Ok(backend)
+}
+
+/// 连接数据库。
+///
+/// SQLite 数据库文件不存在时自动创建；内存数据库只使用一个连接，否则每个连接各自拥有一个独立的数据库。
+pub async fn connect(config: &DatabaseConfig) -> Result<DatabaseConnection, DbErr> {
+    let url = config.url.as_str();
+    let backend = backend_of(url).map_err(DbErr::Custom)?;
+    let in_memory = url.contains(":memory:") || url.contains("mode=memory");
+    let mut url = url.to_owned();
+    if backend == DbBackend::Sqlite && !in_memory && !url.contains("mode=") {
+        url.push_str(if url.contains('?') { "&" } else { "?" });
+        url.push_str("mode=rwc");
+    }
+    let mut options = ConnectOptions::new(url);
+    if let Some(max) = config.max_connections {
+        options.max_connections(max);
+    }
+    if let Some(min) = config.min_connections {
+        options.min_connections(min);
+    }
+    if backend == DbBackend::Sqlite && in_memory {
+        options.max_connections(1).min_connections(1);
+    }
+    Database::connect(options).await
+}
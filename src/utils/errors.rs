@@ -10,6 +10,8 @@ use redis::RedisError;
 
 use crate::api::GeneralResponse;
 
+use super::logging;
+
 pub type AResult<T> = Result<T, AError>;
 
 #[derive(Debug)]
@@ -113,6 +115,7 @@ impl ResponseError for Error {
         }
         builder.json(GeneralResponse {
             message: self.message.clone(),
+            request_id: logging::request_id(),
         })
     }
 }
@@ -136,4 +139,5 @@ error!(unauthorized, UNAUTHORIZED);
 error!(unprocessable_entity, UNPROCESSABLE_ENTITY);
 error!(forbidden, FORBIDDEN);
 error!(not_found, NOT_FOUND);
+error!(too_many_requests, TOO_MANY_REQUESTS);
 error!(internal_server_error, INTERNAL_SERVER_ERROR);
@@ -2,14 +2,22 @@ use std::borrow::Borrow;
 
 use actix_web::HttpResponse;
 use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::NaiveDateTime;
 use sea_orm::{
-    ConnectionTrait, EntityTrait, QuerySelect, QueryTrait, Select, SelectTwo, TransactionTrait,
+    sea_query::{Expr, OrderedStatement, SelectStatement},
+    ColumnTrait, ColumnType, Condition, ConnectionTrait, EntityTrait, Iterable, ModelTrait,
+    PrimaryKeyToColumn, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Select, SelectTwo,
+    TransactionTrait, Value,
 };
 use serde::Serialize;
 
-use crate::{api::PagingRequest, contants};
+use crate::{
+    api::{CursorPage, PagingRequest},
+    contants,
+};
 
-use super::errors::{internal_server_error, AResult};
+use super::errors::{bad_request, internal_server_error, unprocessable_entity, AResult};
 
 pub trait OptionExt<T>
 where
@@ -49,6 +57,217 @@ where
         request: PagingRequest,
         db: D,
     ) -> AResult<HttpResponse>;
+
+    /// 与 [`paged`](SelectExt::paged) 相同，但结果按 `column` 倒序排列，
+    /// 游标分页时以 `column` 与主键共同作为排序键。
+    async fn paged_by<
+        C: ConnectionTrait + TransactionTrait,
+        D: Borrow<C> + Send + Sync,
+        R: From<T::Model> + Serialize,
+    >(
+        self,
+        column: T::Column,
+        request: PagingRequest,
+        db: D,
+    ) -> AResult<HttpResponse>;
+
+    /// 查询一页数据，用于需要在返回前补充信息的场合。
+    async fn fetch_page<C: ConnectionTrait + TransactionTrait, D: Borrow<C> + Send + Sync>(
+        self,
+        request: PagingRequest,
+        db: D,
+    ) -> AResult<Page<T::Model>>;
+}
+
+/// 一页查询结果。
+pub struct Page<M> {
+    pub items: Vec<M>,
+    /// 总数，未要求统计时为空
+    pub count: Option<i32>,
+    /// 游标分页时下一页的游标
+    pub next_cursor: Option<String>,
+    cursor: bool,
+}
+
+impl<M> Page<M> {
+    pub fn map<R>(self, f: impl FnMut(M) -> R) -> Page<R> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            count: self.count,
+            next_cursor: self.next_cursor,
+            cursor: self.cursor,
+        }
+    }
+}
+
+impl<M: Serialize> Page<M> {
+    /// 总数放在 `X-Item-Count` 响应头中。页码分页的响应体为数组，游标分页的响应体为 [`CursorPage`]。
+    pub fn into_response(self) -> HttpResponse {
+        let mut builder = HttpResponse::Ok();
+        if let Some(count) = self.count {
+            builder.append_header((contants::ITEM_COUNT_HEADER, count));
+        }
+        if self.cursor {
+            builder.json(CursorPage {
+                items: self.items,
+                next_cursor: self.next_cursor,
+            })
+        } else {
+            builder.json(self.items)
+        }
+    }
+}
+
+/// 游标分页使用的排序键，即实体的第一个主键列。
+fn key_column<T: EntityTrait>() -> T::Column {
+    T::PrimaryKey::iter().next().unwrap().into_column()
+}
+
+/// 游标中时间的格式，保留全部精度，保证解码后与数据库中的值相等。
+const CURSOR_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+fn key_to_json(key: Value) -> AResult<serde_json::Value> {
+    Ok(match key {
+        Value::Int(Some(v)) => v.into(),
+        Value::BigInt(Some(v)) => v.into(),
+        Value::String(Some(v)) => (*v).into(),
+        Value::ChronoDateTime(Some(v)) => v.format(CURSOR_TIME_FORMAT).to_string().into(),
+        _ => return Err(internal_server_error("Unsupported key type for cursor").into()),
+    })
+}
+
+fn key_from_json<C: ColumnTrait>(column: C, key: serde_json::Value) -> AResult<Value> {
+    let invalid = || bad_request("Invalid cursor");
+    match (column.def().get_column_type(), key) {
+        (ColumnType::DateTime | ColumnType::Timestamp, serde_json::Value::String(v)) => {
+            let v = NaiveDateTime::parse_from_str(&v, CURSOR_TIME_FORMAT).map_err(|_| invalid())?;
+            Ok(v.into())
+        }
+        (_, serde_json::Value::Number(v)) => Ok(v.as_i64().ok_or_else(invalid)?.into()),
+        (_, serde_json::Value::String(v)) => Ok(v.into()),
+        _ => Err(invalid().into()),
+    }
+}
+
+/// 将排序键编码为游标。游标对客户端不透明，只是键值的 JSON 表示，
+/// 按其他列排序时为该列与主键组成的数组。
+fn encode_cursor<M: ModelTrait>(
+    model: &M,
+    sort: Option<<M::Entity as EntityTrait>::Column>,
+) -> AResult<String> {
+    let key = key_to_json(model.get(key_column::<M::Entity>()))?;
+    let key = match sort {
+        Some(sort) => serde_json::Value::Array(vec![key_to_json(model.get(sort))?, key]),
+        None => key,
+    };
+    Ok(URL_SAFE_NO_PAD.encode(key.to_string()))
+}
+
+fn decode_cursor(cursor: &str) -> AResult<serde_json::Value> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| bad_request("Invalid cursor").into())
+}
+
+/// 统计查询结果的总数。
+async fn count<Q, C>(query: Q, db: &C) -> AResult<i32>
+where
+    Q: QuerySelect + QueryTrait,
+    C: ConnectionTrait,
+{
+    let count_statement = query
+        .select_only()
+        .column_as(Expr::count(Expr::val(1)), "count")
+        .build(db.get_database_backend());
+    let count_result = db
+        .query_one(count_statement)
+        .await?
+        .ok_or_else(|| internal_server_error("Unable to count"))?;
+
+    // PostgreSQL 的 COUNT 结果是 64 位整数
+    Ok(count_result.try_get::<i64>("", "count")? as i32)
+}
+
+/// 为查询添加分页条件。游标分页时多查询一条，用于判断是否还有下一页。
+///
+/// 结果按 `sort` 与主键倒序排列，没有 `sort` 时游标分页只按主键排列，
+/// 使用其他自定义排序的接口需要先调用 [`PagingRequest::reject_cursor`]。
+fn paginate<T, Q>(
+    mut query: Q,
+    request: &PagingRequest,
+    sort: Option<T::Column>,
+) -> AResult<(Q, u64)>
+where
+    T: EntityTrait,
+    Q: QuerySelect + QueryFilter + QueryOrder + QueryTrait<QueryStatement = SelectStatement>,
+{
+    let size = request.page_size.unwrap_or(contants::DEFAULT_PAGE_SIZE);
+    if !(1..=contants::MAX_PAGE_SIZE).contains(&size) {
+        return Err(unprocessable_entity(format!(
+            "Page size must be between 1 and {}",
+            contants::MAX_PAGE_SIZE
+        ))
+        .into());
+    }
+    let column = key_column::<T>();
+    let Some(ref cursor) = request.cursor else {
+        if let Some(sort) = sort {
+            query = query.order_by_desc(sort).order_by_desc(column);
+        }
+        let page = request.page.unwrap_or(0);
+        return Ok((query.limit(size).offset(page * size), size));
+    };
+    if !cursor.is_empty() {
+        let key = decode_cursor(cursor)?;
+        query = match (sort, key) {
+            (None, key) => query.filter(column.lt(key_from_json(column, key)?)),
+            (Some(sort), serde_json::Value::Array(keys)) if keys.len() == 2 => {
+                let [value, key] = <[_; 2]>::try_from(keys).unwrap();
+                let value = key_from_json(sort, value)?;
+                query.filter(
+                    Condition::any().add(sort.lt(value.clone())).add(
+                        Condition::all()
+                            .add(sort.eq(value))
+                            .add(column.lt(key_from_json(column, key)?)),
+                    ),
+                )
+            }
+            _ => return Err(bad_request("Invalid cursor").into()),
+        };
+    }
+    QueryTrait::query(&mut query).clear_order_by();
+    if let Some(sort) = sort {
+        query = query.order_by_desc(sort);
+    }
+    Ok((query.order_by_desc(column).limit(size + 1), size))
+}
+
+/// 截去多查询的一条，并生成下一页的游标。
+fn finish<M>(
+    mut items: Vec<M>,
+    size: u64,
+    count: Option<i32>,
+    request: &PagingRequest,
+    cursor: impl Fn(&M) -> AResult<String>,
+) -> AResult<Page<M>> {
+    let mut next_cursor = None;
+    if request.cursor.is_some() && items.len() as u64 > size {
+        items.truncate(size as usize);
+        next_cursor = items.last().map(cursor).transpose()?;
+    }
+    Ok(Page {
+        items,
+        count,
+        next_cursor,
+        cursor: request.cursor.is_some(),
+    })
+}
+
+/// 是否需要统计总数。游标分页默认不统计，避免在大表上执行 `COUNT(*)`。
+fn wants_count(request: &PagingRequest) -> bool {
+    request.count.unwrap_or(request.cursor.is_none())
 }
 
 #[async_trait]
@@ -65,34 +284,48 @@ where
         request: PagingRequest,
         db: D,
     ) -> AResult<HttpResponse> {
-        let query = self.clone();
-        let count_statement = self
-            .select_only()
-            .column_as(
-                sea_orm::sea_query::Expr::count(sea_orm::sea_query::Expr::val(1)),
-                "count",
-            )
-            .build(db.borrow().get_database_backend());
-        let count_result = db
-            .borrow()
-            .query_one(count_statement)
-            .await?
-            .ok_or_else(|| internal_server_error("Unable to count"))?;
-
-        let count: i32 = count_result.try_get("", "count")?;
-        let query_result: Vec<R> = query
-            .limit(request.page_size)
-            .offset(request.page * request.page_size)
-            .all(db.borrow())
-            .await?
-            .into_iter()
-            .map(Into::into)
-            .collect();
-
-        Ok(HttpResponse::Ok()
-            .append_header((contants::ITEM_COUNT_HEADER, count))
-            .json(query_result))
+        let page = fetch_page::<T, C>(self, None, request, db.borrow()).await?;
+        Ok(page.map(R::from).into_response())
+    }
+
+    async fn paged_by<
+        C: ConnectionTrait + TransactionTrait,
+        D: Borrow<C> + Send + Sync,
+        R: From<T::Model> + Serialize,
+    >(
+        self,
+        column: T::Column,
+        request: PagingRequest,
+        db: D,
+    ) -> AResult<HttpResponse> {
+        let page = fetch_page::<T, C>(self, Some(column), request, db.borrow()).await?;
+        Ok(page.map(R::from).into_response())
     }
+
+    async fn fetch_page<C: ConnectionTrait + TransactionTrait, D: Borrow<C> + Send + Sync>(
+        self,
+        request: PagingRequest,
+        db: D,
+    ) -> AResult<Page<T::Model>> {
+        fetch_page::<T, C>(self, None, request, db.borrow()).await
+    }
+}
+
+async fn fetch_page<T: EntityTrait, C: ConnectionTrait>(
+    query: Select<T>,
+    sort: Option<T::Column>,
+    request: PagingRequest,
+    db: &C,
+) -> AResult<Page<T::Model>> {
+    let total = match wants_count(&request) {
+        true => Some(count(query.clone(), db).await?),
+        false => None,
+    };
+    let (query, size) = paginate::<T, _>(query, &request, sort)?;
+    let models = query.all(db).await?;
+    finish(models, size, total, &request, |model| {
+        encode_cursor(model, sort)
+    })
 }
 
 #[async_trait]
@@ -127,32 +360,15 @@ where
         request: PagingRequest,
         db: D,
     ) -> AResult<HttpResponse> {
-        let query = self.clone();
-        let count_statement = self
-            .select_only()
-            .column_as(
-                sea_orm::sea_query::Expr::count(sea_orm::sea_query::Expr::val(1)),
-                "count",
-            )
-            .build(db.borrow().get_database_backend());
-        let count_result = db
-            .borrow()
-            .query_one(count_statement)
-            .await?
-            .ok_or_else(|| internal_server_error("Unable to count"))?;
-
-        let count: i32 = count_result.try_get("", "count")?;
-        let query_result: Vec<K> = query
-            .limit(request.page_size)
-            .offset(request.page * request.page_size)
-            .all(db.borrow())
-            .await?
-            .into_iter()
-            .map(Into::into)
-            .collect();
-
-        Ok(HttpResponse::Ok()
-            .append_header((contants::ITEM_COUNT_HEADER, count))
-            .json(query_result))
+        let total = match wants_count(&request) {
+            true => Some(count(self.clone(), db.borrow()).await?),
+            false => None,
+        };
+        let (query, size) = paginate::<T, _>(self, &request, None)?;
+        let models = query.all(db.borrow()).await?;
+        let page = finish(models, size, total, &request, |(model, _)| {
+            encode_cursor(model, None)
+        })?;
+        Ok(page.map(K::from).into_response())
     }
 }
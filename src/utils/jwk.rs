@@ -0,0 +1,219 @@
+// JWT 签名与验证密钥的管理。
+
+use std::{collections::HashMap, fs};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, OctetKeyPairParameters,
+        OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use simple_asn1::{from_der, ASN1Block};
+
+use crate::config::JwtConfig;
+
+/// JWT 密钥，在启动时根据配置加载一次，以 `web::Data<JwtKeys>` 的形式共享。
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    /// 签名密钥的编号，会写入 JWT Header 的 `kid` 字段
+    signing_kid: Option<String>,
+    encoding_key: EncodingKey,
+    /// 所有仍然有效的验证密钥。轮换密钥时，旧的公钥应继续保留一段时间
+    decoding_keys: HashMap<String, DecodingKey>,
+    /// 没有 `kid` 的 Token 所使用的验证密钥
+    default_decoding_key: DecodingKey,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    /// 根据配置加载密钥，`RS256`/`EdDSA` 会读取配置中的私钥与公钥文件。
+    pub fn from_config(config: &JwtConfig) -> Result<Self, String> {
+        let algorithm = config.algorithm;
+        let signing_kid = config.key_id.clone();
+
+        match algorithm {
+            Algorithm::HS256 => {
+                let secret = config
+                    .secret
+                    .as_deref()
+                    .ok_or("jwt.secret is required for HS256")?;
+                Ok(Self::symmetric(secret.as_bytes(), signing_kid))
+            }
+            Algorithm::RS256 | Algorithm::EdDSA => {
+                let private_key_path = config
+                    .private_key
+                    .as_deref()
+                    .ok_or_else(|| format!("jwt.private_key is required for {:?}", algorithm))?;
+                let signing_kid = signing_kid
+                    .ok_or_else(|| format!("jwt.key_id is required for {:?}", algorithm))?;
+                let private_key = fs::read(private_key_path)
+                    .map_err(|err| format!("Unable to read {}: {}", private_key_path, err))?;
+
+                let mut public_keys = Vec::new();
+                for (kid, path) in &config.public_keys {
+                    let pem = fs::read(path)
+                        .map_err(|err| format!("Unable to read {}: {}", path, err))?;
+                    public_keys.push((kid.clone(), pem));
+                }
+
+                Self::asymmetric(algorithm, &private_key, signing_kid, &public_keys)
+            }
+            _ => Err(format!("Unsupported JWT algorithm {:?}", algorithm)),
+        }
+    }
+
+    /// 使用对称密钥（`HS256`）。对称密钥不会出现在 JWKS 中。
+    pub fn symmetric(secret: &[u8], signing_kid: Option<String>) -> Self {
+        let mut decoding_keys = HashMap::new();
+        if let Some(ref kid) = signing_kid {
+            decoding_keys.insert(kid.clone(), DecodingKey::from_secret(secret));
+        }
+        Self {
+            algorithm: Algorithm::HS256,
+            signing_kid,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_keys,
+            default_decoding_key: DecodingKey::from_secret(secret),
+            jwks: JwkSet { keys: vec![] },
+        }
+    }
+
+    /// 使用非对称密钥（`RS256` 或 `EdDSA`）。
+    ///
+    /// `public_keys` 为 `(kid, PEM)` 列表，必须包含签名私钥对应的公钥。
+    pub fn asymmetric(
+        algorithm: Algorithm,
+        private_key: &[u8],
+        signing_kid: String,
+        public_keys: &[(String, Vec<u8>)],
+    ) -> Result<Self, String> {
+        let encoding_key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(private_key),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(private_key),
+            _ => return Err(format!("{:?} is not an asymmetric algorithm", algorithm)),
+        }
+        .map_err(|err| format!("Invalid private key: {}", err))?;
+
+        let mut decoding_keys = HashMap::new();
+        let mut jwks = Vec::new();
+        for (kid, pem) in public_keys {
+            let params = public_key_params(algorithm, pem)
+                .map_err(|err| format!("Invalid public key {}: {}", kid, err))?;
+            let decoding_key = match &params {
+                AlgorithmParameters::RSA(rsa) => DecodingKey::from_rsa_components(&rsa.n, &rsa.e),
+                AlgorithmParameters::OctetKeyPair(okp) => DecodingKey::from_ed_components(&okp.x),
+                _ => unreachable!(),
+            }
+            .map_err(|err| format!("Invalid public key {}: {}", kid, err))?;
+
+            decoding_keys.insert(kid.clone(), decoding_key);
+            jwks.push(Jwk {
+                common: CommonParameters {
+                    public_key_use: Some(PublicKeyUse::Signature),
+                    algorithm: Some(algorithm),
+                    key_id: Some(kid.clone()),
+                    ..Default::default()
+                },
+                algorithm: params,
+            });
+        }
+
+        let default_decoding_key = decoding_keys
+            .get(&signing_kid)
+            .cloned()
+            .ok_or_else(|| format!("Public key of the signing key {} is missing", signing_kid))?;
+
+        Ok(Self {
+            algorithm,
+            signing_kid: Some(signing_kid),
+            encoding_key,
+            decoding_keys,
+            default_decoding_key,
+            jwks: JwkSet { keys: jwks },
+        })
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.signing_kid.clone();
+        encode(&header, claims, &self.encoding_key)
+    }
+
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<T, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        let key = match header.kid {
+            Some(ref kid) => self
+                .decoding_keys
+                .get(kid)
+                .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?,
+            None => &self.default_decoding_key,
+        };
+        Ok(decode::<T>(token, key, &Validation::new(self.algorithm))?.claims)
+    }
+
+    /// 所有有效公钥组成的 JSON Web Key Set。
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+/// 从 PEM 格式的公钥中解析出 JWK 所需的参数。
+///
+/// RSA 公钥支持 PKCS#1（`RSA PUBLIC KEY`）和 SPKI（`PUBLIC KEY`）两种格式，Ed25519 公钥只支持 SPKI 格式。
+fn public_key_params(algorithm: Algorithm, pem: &[u8]) -> Result<AlgorithmParameters, String> {
+    let pem = pem::parse(pem).map_err(|err| err.to_string())?;
+    let key = match pem.tag.as_str() {
+        "PUBLIC KEY" => spki_public_key(&pem.contents)?,
+        "RSA PUBLIC KEY" if algorithm == Algorithm::RS256 => pem.contents,
+        tag => return Err(format!("Unsupported PEM tag {}", tag)),
+    };
+
+    match algorithm {
+        Algorithm::RS256 => {
+            let blocks = from_der(&key).map_err(|err| err.to_string())?;
+            match blocks.first() {
+                Some(ASN1Block::Sequence(_, items)) => match items.as_slice() {
+                    [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e), ..] => {
+                        Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+                            key_type: RSAKeyType::RSA,
+                            n: URL_SAFE_NO_PAD.encode(n.to_bytes_be().1),
+                            e: URL_SAFE_NO_PAD.encode(e.to_bytes_be().1),
+                        }))
+                    }
+                    _ => Err("Not an RSA public key".to_owned()),
+                },
+                _ => Err("Not an RSA public key".to_owned()),
+            }
+        }
+        Algorithm::EdDSA => {
+            if key.len() != 32 {
+                return Err("Not an Ed25519 public key".to_owned());
+            }
+            Ok(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key),
+            }))
+        }
+        _ => Err(format!("{:?} is not an asymmetric algorithm", algorithm)),
+    }
+}
+
+/// 取出 SubjectPublicKeyInfo 中的 `subjectPublicKey` 字段。
+fn spki_public_key(der: &[u8]) -> Result<Vec<u8>, String> {
+    let blocks = from_der(der).map_err(|err| err.to_string())?;
+    match blocks.first() {
+        Some(ASN1Block::Sequence(_, items)) => match items.as_slice() {
+            [ASN1Block::Sequence(..), ASN1Block::BitString(_, _, key)] => Ok(key.clone()),
+            _ => Err("Malformed SubjectPublicKeyInfo".to_owned()),
+        },
+        _ => Err("Malformed SubjectPublicKeyInfo".to_owned()),
+    }
+}
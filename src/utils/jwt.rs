@@ -1,100 +1,116 @@
 use std::sync::Arc;
-use std::{env, future::Future, pin::Pin};
+use std::{future::Future, pin::Pin};
 
 use actix_web::{web::Data, FromRequest};
 use chrono::Utc;
 use entity::user;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
 use redis::aio::MultiplexedConnection;
 use redis::AsyncCommands;
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use sha2::{Digest, Sha256};
 use tokio::sync::Mutex;
 
 use crate::{
     api::auth::{find_user_by_id, JwtToken},
-    contants::{
-        envs::JWT_SECRET, user_type, ACCESS_TOKEN_EXPIRE_SECONDS, ISSUER,
-        REFRESH_TOKEN_EXPIRE_SECONDS, SECRET_KEY_LENGTH,
-    },
+    config::AuthConfig,
+    contants::{user_type, ISSUER, TOKEN_ID_LENGTH},
 };
 
 use super::{
     errors::{internal_server_error, unauthorized},
+    jwk::JwtKeys,
+    logging,
+    metrics::METRICS,
     permission::CheckPermission,
 };
 
-fn sys_jwt_secret() -> String {
-    env::var(JWT_SECRET).expect("Cannot get JWT secret key from environment variable")
-}
-
-pub fn gen_secret_key(different_from: Option<String>) -> String {
+pub fn gen_secret_key(config: &AuthConfig, different_from: Option<String>) -> String {
     loop {
-        let key = OsRng
-            .sample_iter(&Alphanumeric)
-            .take(SECRET_KEY_LENGTH)
-            .map(char::from)
-            .collect();
+        let key = gen_random_string(config.secret_key_length);
         if different_from.is_none() || &key != different_from.as_ref().unwrap() {
             return key;
         }
     }
 }
 
+/// 生成 Token 的唯一编号（`jti`）或 Token 家族编号。
+pub fn gen_token_id() -> String {
+    gen_random_string(TOKEN_ID_LENGTH)
+}
+
+/// 计算 API Key、密码重置凭据等随机凭据的摘要，数据库中只储存摘要。
+///
+/// 这些凭据由 [`gen_token_id`] 生成，本身是高熵随机串，不需要加盐。
+pub fn hash_secret_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn gen_random_string(length: usize) -> String {
+    OsRng
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+/// 签发一对 Access Token 和 Refresh Token。
+///
+/// `refresh_jti` 是 Refresh Token 的编号，调用方需要事先将其记录到 `refresh_token` 表中。
 pub fn issue_acc_ref_token(
     user_id: i32,
     secret_key: String,
+    refresh_jti: String,
+    config: &AuthConfig,
+    keys: &JwtKeys,
 ) -> Result<JwtToken, jsonwebtoken::errors::Error> {
     Ok(JwtToken {
         access_token: issue_token(
+            keys,
             user_id,
             secret_key.clone(),
             TokenType::Access,
-            ACCESS_TOKEN_EXPIRE_SECONDS,
+            config.access_token_lifetime,
+            gen_token_id(),
         )?,
         refresh_token: issue_token(
+            keys,
             user_id,
             secret_key,
             TokenType::Refresh,
-            REFRESH_TOKEN_EXPIRE_SECONDS,
+            config.refresh_token_lifetime,
+            refresh_jti,
         )?,
     })
 }
 
 pub fn issue_token(
+    keys: &JwtKeys,
     user_id: i32,
     secret_key: String,
     token_type: TokenType,
     expire_period: i64,
+    jti: String,
 ) -> Result<String, jsonwebtoken::errors::Error> {
-    JwtClaims {
+    keys.encode(&JwtClaims {
         exp: Utc::now().timestamp() + expire_period,
         iss: ISSUER.to_owned(),
+        jti,
         user_id,
         secret_key,
         typ: token_type,
-    }
-    .try_into()
-}
-
-impl TryInto<String> for JwtClaims {
-    type Error = jsonwebtoken::errors::Error;
-
-    fn try_into(self) -> Result<String, Self::Error> {
-        encode(
-            &Header::default(),
-            &self,
-            &EncodingKey::from_secret(sys_jwt_secret().as_bytes()),
-        )
-    }
+    })
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JwtClaims {
     pub exp: i64,
     pub iss: String,
+    /// Token 的唯一编号。旧版本签发的 Token 没有该字段，默认为空字符串
+    #[serde(default)]
+    pub jti: String,
     pub user_id: i32,
     pub secret_key: String,
     pub typ: TokenType,
@@ -114,20 +130,15 @@ impl FromRequest for JwtClaims {
         req: &actix_web::HttpRequest,
         _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
+        let Some(keys) = req.app_data::<Data<JwtKeys>>() else {
+            return std::future::ready(Err(internal_server_error("JWT keys are not configured")));
+        };
         let token = req
             .headers()
             .get("Authorization")
             .and_then(|header| header.to_str().ok())
             .and_then(|header| header.strip_prefix("Bearer "))
-            .and_then(|token| {
-                decode::<JwtClaims>(
-                    token,
-                    &DecodingKey::from_secret(sys_jwt_secret().as_bytes()),
-                    &Validation::default(),
-                )
-                .ok()
-            })
-            .map(|data| data.claims);
+            .and_then(|token| keys.decode::<JwtClaims>(token).ok());
 
         if let Some(ref token) = token {
             // 验证 token 是否过期
@@ -170,10 +181,13 @@ where
 
             let this_user = if let Some(redis_conn) = redis_conn {
                 let mut redis_conn = redis_conn.lock().await;
-                redis_conn
+                let cached: Option<user::Model> = redis_conn
                     .get(permission.user_id)
                     .await
-                    .map_err(internal_server_error)?
+                    .map_err(internal_server_error)?;
+                let result = if cached.is_some() { "hit" } else { "miss" };
+                METRICS.user_cache.with_label_values(&[result]).inc();
+                cached
             } else {
                 None
             };
@@ -201,6 +215,7 @@ where
             if this_user.secret_key != permission.secret_key {
                 return Err(unauthorized("Invalid secret key"));
             }
+            logging::set_user_id(this_user.id);
             if Self::validate(&this_user, &permission) {
                 Ok(Some(this_user))
             } else {
@@ -217,16 +232,33 @@ impl JwtValidator for AllowRefresh {
     }
 }
 
+/// 只依赖用户信息的权限校验，可同时用于 JWT 和 API Key。
+pub trait RoleValidator {
+    fn validate_role(model: &user::Model) -> bool;
+}
+
 pub struct AllowAdmin;
 impl JwtValidator for AllowAdmin {
-    fn validate(model: &user::Model, permission: &JwtClaims) -> bool {
-        model.role == user_type::ADMIN || AllowSuperAdmin::validate(model, permission)
+    fn validate(model: &user::Model, _: &JwtClaims) -> bool {
+        Self::validate_role(model)
+    }
+}
+
+impl RoleValidator for AllowAdmin {
+    fn validate_role(model: &user::Model) -> bool {
+        model.role == user_type::ADMIN || AllowSuperAdmin::validate_role(model)
     }
 }
 
 pub struct AllowSuperAdmin;
 impl JwtValidator for AllowSuperAdmin {
     fn validate(model: &user::Model, _: &JwtClaims) -> bool {
+        Self::validate_role(model)
+    }
+}
+
+impl RoleValidator for AllowSuperAdmin {
+    fn validate_role(model: &user::Model) -> bool {
         model.role == user_type::SUPER_ADMIN
     }
 }
@@ -0,0 +1,193 @@
+// 结构化日志与请求编号。
+//
+// 每行日志都是一个 JSON 对象。处理请求期间输出的日志会带上请求编号和已认证的用户编号，
+// 请求结束时额外输出一行 `access` 日志，记录路由、状态码和耗时。
+
+use std::cell::Cell;
+use std::future::{ready, Future, Ready};
+use std::io::Write;
+use std::pin::Pin;
+use std::time::Instant;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use chrono::{SecondsFormat, Utc};
+use log::kv::{self, Key, Visitor};
+use log::Record;
+use serde_json::{Map, Value};
+
+use crate::contants::REQUEST_ID_HEADER;
+
+use super::jwt::gen_token_id;
+
+tokio::task_local! {
+    static CONTEXT: RequestContext;
+}
+
+pub struct RequestContext {
+    pub id: String,
+    user_id: Cell<Option<i32>>,
+}
+
+impl RequestContext {
+    pub fn new(id: String) -> Self {
+        Self {
+            id,
+            user_id: Cell::new(None),
+        }
+    }
+
+    /// 在请求上下文中执行 `fut`。
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        CONTEXT.scope(self, fut).await
+    }
+}
+
+/// 当前请求的编号，不在请求中时为空。
+pub fn request_id() -> Option<String> {
+    CONTEXT.try_with(|context| context.id.clone()).ok()
+}
+
+/// 记录当前请求已认证的用户。
+pub fn set_user_id(user_id: i32) {
+    let _ = CONTEXT.try_with(|context| context.user_id.set(Some(user_id)));
+}
+
+/// 初始化日志，默认级别为 `info`，可以通过 `RUST_LOG` 调整。
+pub fn init() {
+    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format(|buf, record| writeln!(buf, "{}", render(record)))
+        .try_init();
+}
+
+/// 将一条日志转换为 JSON。
+pub fn render(record: &Record) -> String {
+    let mut line = Map::new();
+    line.insert(
+        "timestamp".to_owned(),
+        Utc::now()
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+            .into(),
+    );
+    line.insert("level".to_owned(), record.level().as_str().into());
+    line.insert("target".to_owned(), record.target().into());
+    line.insert("message".to_owned(), record.args().to_string().into());
+    let _ = CONTEXT.try_with(|context| {
+        line.insert("request_id".to_owned(), context.id.clone().into());
+        if let Some(user_id) = context.user_id.get() {
+            line.insert("user_id".to_owned(), user_id.into());
+        }
+    });
+    let _ = record.key_values().visit(&mut Fields(&mut line));
+    Value::Object(line).to_string()
+}
+
+/// 将日志的键值对写入 JSON 对象，尽量保留数字和布尔类型。
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> Visitor<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(v) = value.to_u64() {
+            v.into()
+        } else if let Some(v) = value.to_i64() {
+            v.into()
+        } else if let Some(v) = value.to_f64() {
+            v.into()
+        } else if let Some(v) = value.to_bool() {
+            v.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.as_str().to_owned(), value);
+        Ok(())
+    }
+}
+
+/// 客户端提供的请求编号只接受较短的可见字符，否则重新生成，避免污染日志。
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// 为每个请求分配编号并输出访问日志的中间件。
+///
+/// 请求编号取自 `X-Request-Id` 请求头，没有或不合法时生成一个新的，并在响应头中返回。
+pub struct RequestLogger;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestLogger
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestLoggerMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestLoggerMiddleware { service }))
+    }
+}
+
+pub struct RequestLoggerMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestLoggerMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_owned)
+            .unwrap_or_else(gen_token_id);
+        let method = req.method().to_string();
+        let path = req.path().to_owned();
+        // 处理请求的工作几乎都在轮询时进行，只需在上下文中轮询即可
+        let fut = self.service.call(req);
+        let context = RequestContext::new(id.clone());
+
+        Box::pin(context.scope(async move {
+            let mut result = fut.await;
+            let (route, status) = match result {
+                Ok(ref mut res) => {
+                    if let Ok(value) = HeaderValue::from_str(&id) {
+                        let name = HeaderName::from_bytes(REQUEST_ID_HEADER.as_bytes()).unwrap();
+                        res.headers_mut().insert(name, value);
+                    }
+                    (res.request().match_pattern(), res.status())
+                }
+                Err(ref err) => (None, err.as_response_error().status_code()),
+            };
+            log::info!(
+                target: "access",
+                method = method.as_str(),
+                path = path.as_str(),
+                route = route.as_deref().unwrap_or("unmatched"),
+                status = status.as_u16(),
+                duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+                "{} {} {}", method, path, status.as_u16()
+            );
+            result
+        }))
+    }
+}
@@ -0,0 +1,222 @@
+// Prometheus 指标：HTTP 请求、数据库连接池、用户缓存以及业务计数。
+
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::time::Instant;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use entity::TicketType;
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use sea_orm::{ActiveEnum, ConnectionTrait, DatabaseConnection, DbBackend};
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    /// 按方法、路由和状态码统计的请求数
+    pub http_requests: IntCounterVec,
+    /// 按方法和路由统计的请求耗时
+    pub http_duration: HistogramVec,
+    /// 连接池中的连接数，按空闲和使用中区分，在导出时更新
+    pub db_pool: IntGaugeVec,
+    /// 鉴权时在 Redis 中查找用户的结果，`hit` 或 `miss`
+    pub user_cache: IntCounterVec,
+    /// 按订单类型和操作统计的订单数
+    pub orders: IntCounterVec,
+    /// 已售出的书籍册数
+    pub books_sold: IntCounter,
+    /// 按任务名和结果统计的定时任务执行次数
+    pub job_runs: IntCounterVec,
+    /// 按分组统计的被限流的请求数
+    pub rate_limited: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of HTTP requests"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route"],
+        )
+        .unwrap();
+        let db_pool = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Connections in the database pool"),
+            &["state"],
+        )
+        .unwrap();
+        let user_cache = IntCounterVec::new(
+            Opts::new(
+                "user_cache_lookups_total",
+                "Redis lookups of the authenticated user",
+            ),
+            &["result"],
+        )
+        .unwrap();
+        let orders = IntCounterVec::new(
+            Opts::new("orders_total", "Order state changes"),
+            &["type", "action"],
+        )
+        .unwrap();
+        let books_sold = IntCounter::new("books_sold_total", "Copies sold").unwrap();
+        let job_runs = IntCounterVec::new(
+            Opts::new("job_runs_total", "Scheduled job runs"),
+            &["job", "status"],
+        )
+        .unwrap();
+
+        let rate_limited = IntCounterVec::new(
+            Opts::new(
+                "rate_limited_requests_total",
+                "Requests rejected by rate limiting",
+            ),
+            &["group"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_duration.clone())).unwrap();
+        registry.register(Box::new(db_pool.clone())).unwrap();
+        registry.register(Box::new(user_cache.clone())).unwrap();
+        registry.register(Box::new(orders.clone())).unwrap();
+        registry.register(Box::new(books_sold.clone())).unwrap();
+        registry.register(Box::new(job_runs.clone())).unwrap();
+        registry.register(Box::new(rate_limited.clone())).unwrap();
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            db_pool,
+            user_cache,
+            orders,
+            books_sold,
+            job_runs,
+            rate_limited,
+        }
+    }
+
+    /// 记录一次订单操作，例如 `created`、`paid`、`revoked`、`confirmed`。
+    pub fn order(&self, ticket_type: TicketType, action: &str) {
+        let ticket_type = ticket_type.to_value().to_lowercase();
+        self.orders.with_label_values(&[&ticket_type, action]).inc();
+    }
+
+    /// 导出为 Prometheus 文本格式。
+    pub fn render(&self, db: &DatabaseConnection) -> String {
+        if let Some((size, idle)) = pool_stats(db) {
+            self.db_pool.with_label_values(&["idle"]).set(idle as i64);
+            self.db_pool
+                .with_label_values(&["in_use"])
+                .set(size as i64 - idle as i64);
+        }
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+/// 连接池的连接总数与空闲连接数。
+fn pool_stats(db: &DatabaseConnection) -> Option<(u32, usize)> {
+    match db.get_database_backend() {
+        #[cfg(feature = "mysql")]
+        DbBackend::MySql => {
+            let pool = db.get_mysql_connection_pool();
+            Some((pool.size(), pool.num_idle()))
+        }
+        #[cfg(feature = "postgres")]
+        DbBackend::Postgres => {
+            let pool = db.get_postgres_connection_pool();
+            Some((pool.size(), pool.num_idle()))
+        }
+        #[cfg(feature = "sqlite")]
+        DbBackend::Sqlite => {
+            let pool = db.get_sqlite_connection_pool();
+            Some((pool.size(), pool.num_idle()))
+        }
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// 记录每个请求的次数、耗时与状态码的中间件。
+///
+/// 路由使用匹配到的模式（例如 `/sell/{id}/pay`），未匹配任何路由的请求记为 `unmatched`，避免标签数量无限增长。
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let method = req.method().to_string();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let result = fut.await;
+            let (route, status) = match result {
+                Ok(ref res) => (
+                    res.request()
+                        .match_pattern()
+                        .unwrap_or_else(|| "unmatched".to_owned()),
+                    res.status(),
+                ),
+                Err(ref err) => (
+                    "unmatched".to_owned(),
+                    err.as_response_error().status_code(),
+                ),
+            };
+            METRICS
+                .http_requests
+                .with_label_values(&[&method, &route, status.as_str()])
+                .inc();
+            METRICS
+                .http_duration
+                .with_label_values(&[&method, &route])
+                .observe(start.elapsed().as_secs_f64());
+            result
+        })
+    }
+}
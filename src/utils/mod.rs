@@ -1,4 +1,12 @@
+pub mod api_key;
+pub mod cors;
+pub mod db;
 pub mod errors;
 pub mod ext;
+pub mod jwk;
 pub mod jwt;
+pub mod logging;
+pub mod metrics;
+pub mod password;
 pub mod permission;
+pub mod rate_limit;
@@ -0,0 +1,115 @@
+// 密码加盐、校验与密码策略。
+
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use entity::{password_history, user};
+use once_cell::sync::Lazy;
+use rand::rngs::OsRng;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::Deserialize;
+
+use super::errors::{unprocessable_entity, AResult};
+
+static ARGON: Lazy<Argon2> = Lazy::new(Argon2::default);
+
+pub fn to_salted_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(ARGON.hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+/// 校验密码是否与加盐后的密码匹配。密码不匹配时返回 `Ok(false)`。
+pub fn verify_password(
+    password: &str,
+    password_salt: &str,
+) -> Result<bool, argon2::password_hash::Error> {
+    match ARGON.verify_password(password.as_bytes(), &PasswordHash::new(password_salt)?) {
+        Ok(_) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// 密码策略，即配置中的 `password` 部分。
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    /// 最短长度（按字符计）
+    pub min_length: usize,
+    /// 至少需要包含的字符类别数（小写字母、大写字母、数字、其他符号）
+    pub min_classes: usize,
+    /// 新密码不能与最近的多少个密码相同（包括当前密码），为 0 时不检查
+    pub history: u64,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            min_classes: 2,
+            history: 3,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// 检查密码强度。
+    pub fn check(&self, password: &str) -> AResult<()> {
+        if password.chars().count() < self.min_length {
+            return Err(unprocessable_entity(format!(
+                "Password must be at least {} characters long",
+                self.min_length
+            ))
+            .into());
+        }
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ]
+        .into_iter()
+        .filter(|&has| has)
+        .count();
+        if classes < self.min_classes {
+            return Err(unprocessable_entity(format!(
+                "Password must contain at least {} of: lowercase letters, uppercase letters, digits and symbols",
+                self.min_classes
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// 检查新密码是否与用户当前密码或最近使用过的密码相同。
+    pub async fn check_reuse<C: ConnectionTrait>(
+        &self,
+        user: &user::Model,
+        password: &str,
+        db: &C,
+    ) -> AResult<()> {
+        if self.history == 0 {
+            return Ok(());
+        }
+        let mut previous = vec![user.password_salt.clone()];
+        previous.extend(
+            password_history::Entity::find()
+                .filter(password_history::Column::UserId.eq(user.id))
+                .order_by_desc(password_history::Column::CreatedAt)
+                .limit(self.history - 1)
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|history| history.password_salt),
+        );
+        for password_salt in previous {
+            if verify_password(password, &password_salt)? {
+                return Err(unprocessable_entity(format!(
+                    "Password must differ from the last {} passwords",
+                    self.history
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
@@ -154,6 +154,8 @@ where
                             this.data.clone(),
                             this.info.as_ref().unwrap(),
                         )));
+                        // 权限检查尚未被轮询，需要主动唤醒，否则没有其他事件时请求会一直挂起
+                        cx.waker().wake_by_ref();
                         Poll::Pending
                     }
                     Poll::Ready(Err(e)) => Poll::Ready(Err(e.into())),
@@ -0,0 +1,342 @@
+// 令牌桶限流。
+//
+// 路由按前缀分组，每组有独立的限额。已登录或使用 API Key 的请求按用户计数，其余按客户端 IP 计数。
+// 配置了 Redis 时桶的状态保存在 Redis 中，由所有实例共享，否则保存在进程内。
+
+use std::collections::HashMap;
+use std::future::{ready, Future, Ready};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::web::Data;
+use actix_web::{Error, FromRequest};
+use chrono::Utc;
+use log::warn;
+use once_cell::sync::Lazy;
+use redis::aio::MultiplexedConnection;
+use sea_orm::DatabaseConnection;
+use tokio::sync::Mutex;
+
+use crate::config::RateLimitConfig;
+use crate::contants::{
+    API_KEY_HEADER, RATE_LIMIT_LIMIT_HEADER, RATE_LIMIT_REMAINING_HEADER, RATE_LIMIT_RESET_HEADER,
+};
+
+use super::api_key;
+use super::errors::too_many_requests;
+use super::jwt::{JwtClaims, TokenType};
+use super::metrics::METRICS;
+
+/// 进程内最多保存的桶数。达到上限后清理已经补满的桶，仍然过多时按最近使用的时间淘汰到一半
+pub(crate) const MAX_MEMORY_BUCKETS: usize = 10_000;
+
+/// 与 [`refill`] 相同的逻辑，在 Redis 中原子地执行。
+///
+/// 参数依次为容量、每毫秒补充的令牌数与当前时间（毫秒），返回是否允许以及剩余的令牌数。
+///
+/// 测试使用的 Redis 替身不能执行 Lua，而是用 Rust 重新实现了这段脚本，因此脚本本身没有被测试覆盖。
+/// 修改时需要同时修改 [`refill`] 与替身中的实现，并更新测试中记录的脚本摘要。
+pub(crate) const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local per_ms = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local tokens = capacity
+local state = redis.call('GET', KEYS[1])
+if state then
+  local sep = string.find(state, ':')
+  local last = tonumber(string.sub(state, sep + 1))
+  tokens = tonumber(string.sub(state, 1, sep - 1)) + math.max(0, now - last) * per_ms
+  tokens = math.min(capacity, tokens)
+end
+local allowed = 0
+if tokens >= 1 then
+  tokens = tokens - 1
+  allowed = 1
+end
+redis.call('SET', KEYS[1], tokens .. ':' .. now, 'PX', math.ceil((capacity - tokens) / per_ms) + 1)
+return {allowed, tostring(tokens)}
+"#;
+
+/// 通过 EVALSHA 调用，只在 Redis 中没有缓存脚本时才发送脚本内容
+static TOKEN_BUCKET: Lazy<redis::Script> = Lazy::new(|| redis::Script::new(TOKEN_BUCKET_SCRIPT));
+
+#[derive(Debug, Clone)]
+struct Group {
+    name: String,
+    paths: Vec<String>,
+    burst: f64,
+    /// 每毫秒补充的令牌数
+    per_ms: f64,
+}
+
+impl Group {
+    /// 与路径匹配的最长前缀的长度。
+    fn matches(&self, path: &str) -> Option<usize> {
+        self.paths
+            .iter()
+            .filter(|prefix| {
+                path.strip_prefix(prefix.as_str()).map_or(false, |rest| {
+                    prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/')
+                })
+            })
+            .map(String::len)
+            .max()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_ms: i64,
+    /// 桶被补满的时间，此后可以丢弃
+    full_ms: i64,
+}
+
+/// 清理已经补满的桶；仍然超过上限的一半时，淘汰最久没有使用的桶，直到少于上限的一半。
+///
+/// 每次清理后至少还能加入一半上限的新桶才会再次清理，因此平均到每个请求的开销是常数。
+/// 被淘汰的桶再次使用时从满桶开始计数。
+fn evict(memory: &mut HashMap<String, Bucket>, now_ms: i64) {
+    memory.retain(|_, bucket| bucket.full_ms > now_ms);
+    let keep = MAX_MEMORY_BUCKETS / 2;
+    if memory.len() <= keep {
+        return;
+    }
+    let mut updated: Vec<_> = memory.values().map(|bucket| bucket.updated_ms).collect();
+    let cut = updated.len() - keep;
+    let (_, threshold, _) = updated.select_nth_unstable(cut);
+    let threshold = *threshold;
+    // 与分界相同的时间也一并淘汰，保证清理后少于 keep 个
+    memory.retain(|_, bucket| bucket.updated_ms > threshold);
+}
+
+/// 按经过的时间补充令牌后尝试取出一个，返回是否允许。
+fn refill(bucket: &mut Bucket, group: &Group, now_ms: i64) -> bool {
+    let elapsed = (now_ms - bucket.updated_ms).max(0) as f64;
+    bucket.tokens = (bucket.tokens + elapsed * group.per_ms).min(group.burst);
+    bucket.updated_ms = now_ms;
+    let allowed = bucket.tokens >= 1.0;
+    if allowed {
+        bucket.tokens -= 1.0;
+    }
+    bucket.full_ms = now_ms + ((group.burst - bucket.tokens) / group.per_ms).ceil() as i64;
+    allowed
+}
+
+struct Decision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    /// 桶补满所需的秒数
+    reset: u64,
+    /// 下一个令牌补充所需的秒数
+    retry_after: u64,
+}
+
+impl Decision {
+    fn new(allowed: bool, tokens: f64, group: &Group) -> Self {
+        let seconds = |tokens: f64| (tokens.max(0.0) / group.per_ms / 1000.0).ceil() as u64;
+        Self {
+            allowed,
+            limit: group.burst as u32,
+            remaining: tokens.floor() as u32,
+            reset: seconds(group.burst - tokens),
+            retry_after: seconds(1.0 - tokens).max(1),
+        }
+    }
+
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        let mut insert = |name: &str, value: u64| {
+            // from_bytes 会将名称转换为小写
+            let name = HeaderName::from_bytes(name.as_bytes()).unwrap();
+            headers.insert(name, HeaderValue::from(value));
+        };
+        insert(RATE_LIMIT_LIMIT_HEADER, self.limit.into());
+        insert(RATE_LIMIT_REMAINING_HEADER, self.remaining.into());
+        insert(RATE_LIMIT_RESET_HEADER, self.reset);
+        if !self.allowed {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(self.retry_after));
+        }
+    }
+}
+
+/// 限流中间件，在 `main` 中创建一次，由所有工作线程共享进程内的状态。
+#[derive(Clone)]
+pub struct RateLimiter {
+    groups: Arc<Vec<Group>>,
+    trust_forwarded_headers: bool,
+    memory: Arc<std::sync::Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let groups = match config.enabled {
+            true => config
+                .groups
+                .iter()
+                .map(|(name, group)| Group {
+                    name: name.clone(),
+                    paths: group.paths.clone(),
+                    burst: group.burst as f64,
+                    per_ms: group.per_minute as f64 / 60_000.0,
+                })
+                .collect(),
+            false => vec![],
+        };
+        Self {
+            groups: Arc::new(groups),
+            trust_forwarded_headers: config.trust_forwarded_headers,
+            memory: Default::default(),
+        }
+    }
+
+    /// 路径所属的分组，多个分组匹配时取前缀最长的。
+    fn group(&self, path: &str) -> Option<&Group> {
+        self.groups
+            .iter()
+            .filter_map(|group| Some((group.matches(path)?, group)))
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, group)| group)
+    }
+
+    /// 计数的对象：有效的 Access Token 或 API Key 对应的用户，否则为客户端 IP。
+    ///
+    /// 无效的 API Key 按 IP 计数，随意更换请求头不能绕过限流。
+    async fn client(&self, req: &ServiceRequest) -> String {
+        let claims = JwtClaims::from_request(req.request(), &mut Payload::None).into_inner();
+        if let Some(claims) = claims.ok().filter(|claims| claims.typ == TokenType::Access) {
+            return format!("user:{}", claims.user_id);
+        }
+        let key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|header| header.to_str().ok());
+        let db = req.app_data::<Data<DatabaseConnection>>();
+        if let (Some(key), Some(db)) = (key, db) {
+            match api_key::owner_id(db, key).await {
+                Ok(Some(user_id)) => return format!("user:{}", user_id),
+                Ok(None) => {}
+                Err(err) => warn!("Failed to look up the API key owner: {}", err),
+            }
+        }
+        let ip = if self.trust_forwarded_headers {
+            req.connection_info().realip_remote_addr().map(|addr| {
+                match addr.parse::<SocketAddr>() {
+                    Ok(addr) => addr.ip().to_string(),
+                    Err(_) => addr.to_owned(),
+                }
+            })
+        } else {
+            req.peer_addr().map(|addr| addr.ip().to_string())
+        };
+        format!("ip:{}", ip.as_deref().unwrap_or("unknown"))
+    }
+
+    async fn take(
+        &self,
+        key: &str,
+        group: &Group,
+        redis: Option<&Mutex<MultiplexedConnection>>,
+    ) -> Decision {
+        let now = Utc::now().timestamp_millis();
+        if let Some(redis) = redis {
+            let reply: redis::RedisResult<(bool, String)> = TOKEN_BUCKET
+                .key(key)
+                .arg(group.burst)
+                .arg(group.per_ms)
+                .arg(now)
+                .invoke_async(&mut *redis.lock().await)
+                .await;
+            match reply {
+                Ok((allowed, tokens)) => {
+                    return Decision::new(allowed, tokens.parse().unwrap_or(0.0), group)
+                }
+                // Redis 不可用时退回到进程内的计数，而不是拒绝所有请求
+                Err(err) => warn!("Rate limiting falls back to memory: {}", err),
+            }
+        }
+
+        let mut memory = self.memory.lock().unwrap();
+        if memory.len() >= MAX_MEMORY_BUCKETS && !memory.contains_key(key) {
+            evict(&mut memory, now);
+        }
+        let bucket = memory.entry(key.to_owned()).or_insert(Bucket {
+            tokens: group.burst,
+            updated_ms: now,
+            full_ms: now,
+        });
+        let allowed = refill(bucket, group, now);
+        Decision::new(allowed, bucket.tokens, group)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = RateLimiterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimiterMiddleware {
+            service: Rc::new(service),
+            limiter: self.clone(),
+        }))
+    }
+}
+
+pub struct RateLimiterMiddleware<S> {
+    service: Rc<S>,
+    limiter: RateLimiter,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let limiter = self.limiter.clone();
+        Box::pin(async move {
+            let Some(group) = limiter.group(req.path()) else {
+                return Ok(service.call(req).await?.map_into_boxed_body());
+            };
+            let key = format!("rate_limit:{}:{}", group.name, limiter.client(&req).await);
+            let redis = req
+                .app_data::<Data<Option<Mutex<MultiplexedConnection>>>>()
+                .cloned();
+            let redis = redis.as_ref().and_then(|redis| redis.get_ref().as_ref());
+            let decision = limiter.take(&key, group, redis).await;
+
+            let mut res = if decision.allowed {
+                service.call(req).await?.map_into_boxed_body()
+            } else {
+                METRICS.rate_limited.with_label_values(&[&group.name]).inc();
+                req.error_response(too_many_requests(format!(
+                    "Too many requests, retry in {} seconds",
+                    decision.retry_after
+                )))
+            };
+            decision.write_headers(res.headers_mut());
+            Ok(res)
+        })
+    }
+}